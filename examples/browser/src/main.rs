@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::time::Duration;
 use zeroconf::prelude::*;
@@ -20,8 +21,10 @@ fn main() {
 fn on_service_discovered(
     result: zeroconf::Result<ServiceDiscovery>,
     _context: Option<Arc<dyn Any>>,
-) {
+) -> ControlFlow<()> {
     println!("Service discovered: {:?}", result.unwrap());
 
     // ...
+
+    ControlFlow::Continue(())
 }
@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::ops::ControlFlow;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use zeroconf::prelude::*;
@@ -31,7 +32,7 @@ fn main() {
 fn on_service_registered(
     result: zeroconf::Result<ServiceRegistration>,
     context: Option<Arc<dyn Any>>,
-) {
+) -> ControlFlow<()> {
     let service = result.unwrap();
 
     println!("Service registered: {:?}", service);
@@ -48,4 +49,6 @@ fn on_service_registered(
     println!("Context: {:?}", context);
 
     // ...
+
+    ControlFlow::Continue(())
 }
@@ -1,12 +1,47 @@
 //! Trait definition for cross-platform browser
 
-use crate::{EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
+use crate::error::{Error, ErrorKind};
+use crate::interface;
+use crate::prelude::*;
+use crate::txt_record::TxtValueDeserializer;
+use crate::{AddressFamily, NetworkInterface, Result, ServiceType, TxtRecord};
+use serde::de::value::{self, MapDeserializer};
+use serde::de::DeserializeOwned;
 use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::ops::ControlFlow;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Interface for interacting with underlying mDNS implementation service browsing capabilities.
+///
+/// As with [`TMdnsService`], a browser cannot be dropped while its callback is being dispatched,
+/// and the [`ServiceDiscoveredCallback`] is never invoked once dropping has begun: the browse and
+/// resolve operations are freed before the state they share with the callback.
+///
+/// [`TMdnsService`]: ../service/trait.TMdnsService.html
+/// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
 pub trait TMdnsBrowser {
+    /// The [`TEventLoop`] returned by `browse_services()` to drive this browser.
+    ///
+    /// [`TEventLoop`]: ../event_loop/trait.TEventLoop.html
+    type EventLoop<'a>: TEventLoop
+    where
+        Self: 'a;
+
+    /// The [`TResolveHandle`] returned by `resolve_start()`.
+    ///
+    /// [`TResolveHandle`]: trait.TResolveHandle.html
+    type ResolveHandle: TResolveHandle;
+
     /// Creates a new `MdnsBrowser` that browses for the specified `kind` (e.g. `_http._tcp`)
+    ///
+    /// If `service_type` was created with `ServiceType::new_with_domain()`, only its domain is
+    /// browsed, as if `set_domains()` had been called with it.
     fn new(service_type: ServiceType) -> Self;
 
     /// Sets the network interface on which to browse for services on.
@@ -15,6 +50,130 @@ pub trait TMdnsBrowser {
     /// on all available interfaces.
     fn set_network_interface(&mut self, interface: NetworkInterface);
 
+    /// Restricts browsing to services registered on this machine. When enabled, this takes
+    /// precedence over the interface set with `set_network_interface()`.
+    ///
+    /// On macOS this browses on Bonjour's local-only interface. On Linux, Avahi has no
+    /// equivalent scope, so services are browsed as usual and only those Avahi reports as local
+    /// are resolved and passed to the callback.
+    fn set_local_only(&mut self, local_only: bool);
+
+    /// If `true`, services registered by this process are not passed to the callback, for
+    /// applications that both register and browse the same type and want to discover their
+    /// peers only. Defaults to `false`.
+    ///
+    /// A discovery is considered to be this process's own if [`ServiceDiscovery::is_local()`]
+    /// is `true` and a service in this process is registered with the same type and port.
+    /// Names are not compared, since a service may have been renamed after a conflict.
+    ///
+    /// [`ServiceDiscovery::is_local()`]: ../struct.ServiceDiscovery.html#method.is_local
+    fn set_ignore_local(&mut self, ignore_local: bool);
+
+    /// Sets the address family discovered services are resolved to. Discoveries will then only
+    /// carry an address of the requested family.
+    ///
+    /// Defaults to `AddressFamily::Any`.
+    fn set_address_family(&mut self, family: AddressFamily);
+
+    /// Browses for services registered in the specified unicast DNS-SD `domain` (e.g.
+    /// `example.com`) instead of announced via multicast on `.local`.
+    ///
+    /// Wide-area browsing relies on the system's DNS configuration: the configured resolver must
+    /// be able to answer PTR, SRV and TXT queries for the zone.
+    ///
+    /// On Linux, Avahi must additionally be started with `enable-wide-area=yes` in the
+    /// `[wide-area]` section of `avahi-daemon.conf`. On macOS, no additional daemon configuration
+    /// is required.
+    ///
+    /// This replaces any domains set with [`set_domains()`], and cannot be combined with
+    /// [`set_force_multicast()`].
+    ///
+    /// [`set_domains()`]: #tymethod.set_domains
+    /// [`set_force_multicast()`]: #tymethod.set_force_multicast
+    fn set_wide_area(&mut self, domain: &str);
+
+    /// If `true`, services are only browsed for and resolved via multicast DNS, so that a
+    /// unicast DNS server can never answer in place of the local network.
+    ///
+    /// This maps to `kDNSServiceFlagsForceMulticast` on macOS and `AVAHI_LOOKUP_USE_MULTICAST` on
+    /// Linux. It leaves the domains set with [`set_domains()`] or `ServiceType::new_with_domain()`
+    /// unchanged. Forcing multicast while browsing the unicast domain set with
+    /// [`set_wide_area()`] is contradictory, so `browse_services()` then returns `Err` with
+    /// [`ErrorKind::InvalidConfiguration`], whichever of the two was called first. Defaults to
+    /// the platform behavior.
+    ///
+    /// [`set_domains()`]: #tymethod.set_domains
+    /// [`set_wide_area()`]: #tymethod.set_wide_area
+    /// [`ErrorKind::InvalidConfiguration`]: ../error/enum.ErrorKind.html#variant.InvalidConfiguration
+    fn set_force_multicast(&mut self, force_multicast: bool);
+
+    /// Browses each of the specified `domains` (e.g. `local.` and `corp.example.com.`) at the same
+    /// time instead of only the default domain. Discovered services report the domain they were
+    /// found in through [`ServiceDiscovery::domain()`].
+    ///
+    /// One browse operation is started per domain, sharing the browser's connection to the
+    /// daemon. This replaces any domain set with [`set_wide_area()`]; an empty list restores the
+    /// default domain. Whether multicast is forced with `set_force_multicast()` is unaffected.
+    ///
+    /// [`ServiceDiscovery::domain()`]: ../struct.ServiceDiscovery.html#method.domain
+    /// [`set_wide_area()`]: #tymethod.set_wide_area
+    fn set_domains(&mut self, domains: Vec<String>);
+
+    /// If `false`, discovered services are not resolved to an address: discoveries carry the host
+    /// name and port from the service's SRV record, but their address is empty and
+    /// [`ServiceDiscovery::endpoints()`] returns nothing.
+    ///
+    /// This saves a lookup for consumers that resolve host names themselves. On macOS, this skips
+    /// `DNSServiceGetAddrInfo()` after `DNSServiceResolve()`; on Linux, it resolves with
+    /// `AVAHI_LOOKUP_NO_ADDRESS`. Defaults to `true`.
+    ///
+    /// [`ServiceDiscovery::endpoints()`]: ../struct.ServiceDiscovery.html#method.endpoints
+    fn set_resolve_address(&mut self, resolve_address: bool);
+
+    /// Stops the browser once `max_duration` has elapsed since `browse_services()` was called,
+    /// for discoveries that shouldn't run forever in the background.
+    ///
+    /// Services are delivered to the [`ServiceDiscoveredCallback`] as usual until the deadline.
+    /// The callback is then invoked a final time with an `Err` of [`ErrorKind::TimedOut`], and
+    /// nothing is delivered afterwards. The `EventLoop` stays usable; polling it simply has no
+    /// further effect for this browser. The deadline is checked when the `EventLoop` is polled,
+    /// so the final callback happens on the first poll after it has passed.
+    ///
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    /// [`ErrorKind::TimedOut`]: ../error/enum.ErrorKind.html#variant.TimedOut
+    fn set_max_duration(&mut self, max_duration: Duration);
+
+    /// Bounds how many discovered services are resolved at the same time, so that mass discovery
+    /// on a large network does not overwhelm the daemon. Further services are queued and resolved
+    /// as earlier resolves complete or time out. A `max` of `0` is treated as `1`.
+    ///
+    /// By default every discovered service is resolved immediately. Bonjour resolves services
+    /// one at a time already, so on macOS this has no effect.
+    fn set_max_concurrent_resolves(&mut self, max: usize);
+
+    /// Only resolves and passes to the callback services whose instance name starts with
+    /// `prefix` (e.g. `Printer-`), skipping the rest as soon as the daemon reports them. This
+    /// saves resolving every instance on busy networks where only a few are of interest.
+    ///
+    /// The prefix is compared case-sensitively against the unescaped instance name, as it is
+    /// returned by [`ServiceDiscovery::name()`]. An empty `prefix` matches every service, which
+    /// is the default.
+    ///
+    /// [`ServiceDiscovery::name()`]: ../struct.ServiceDiscovery.html#method.name
+    fn set_name_prefix(&mut self, prefix: &str);
+
+    /// Re-resolves every discovered service each time `interval` elapses, e.g. for services whose
+    /// addresses change more often than the daemon notices. Updated discoveries are delivered to
+    /// the [`ServiceDiscoveredCallback`] as usual.
+    ///
+    /// As with `set_max_duration()`, the interval is checked when the `EventLoop` is polled, so
+    /// refreshes happen on the first poll after each interval has elapsed and never cause the
+    /// loop to spin. By default services are only resolved again when the daemon reports a
+    /// change.
+    ///
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    fn set_refresh_interval(&mut self, interval: Duration);
+
     /// Sets the [`ServiceDiscoveredCallback`] that is invoked when the browser has discovered and
     /// resolved a service.
     ///
@@ -24,22 +183,246 @@ pub trait TMdnsBrowser {
         service_discovered_callback: Box<ServiceDiscoveredCallback>,
     );
 
+    /// Sets the [`ServiceRemovedCallback`] that is invoked when a service this browser has
+    /// discovered is removed from the network. Removals are ignored if no callback is set.
+    ///
+    /// The daemons report a service separately for every interface, address family and domain
+    /// it is found on, so the callback is only invoked once the service is gone from all of them
+    /// in a domain.
+    ///
+    /// [`ServiceRemovedCallback`]: ../type.ServiceRemovedCallback.html
+    fn set_service_removed_callback(
+        &mut self,
+        service_removed_callback: Box<ServiceRemovedCallback>,
+    );
+
     /// Sets the optional user context to pass through to the callback. This is useful if you need
     /// to share state between pre and post-callback. The context type must implement `Any`.
     fn set_context(&mut self, context: Box<dyn Any>);
 
     /// Starts the browser. Returns an `EventLoop` which can be called to keep the browser alive.
-    fn browse_services(&mut self) -> Result<EventLoop>;
+    fn browse_services(&mut self) -> Result<Self::EventLoop<'_>>;
+
+    /// Starts the browser like `browse_services()`, but without returning an `EventLoop`. The
+    /// browser is then driven with [`poll()`], so that it can be stored (e.g. in a struct) without
+    /// also having to store an `EventLoop` borrowing it.
+    ///
+    /// [`poll()`]: #tymethod.poll
+    fn start(&mut self) -> Result<()> {
+        self.browse_services().map(|_| ())
+    }
+
+    /// Polls the browser for new events, exactly like `EventLoop::poll()` on the `EventLoop`
+    /// that `browse_services()` returns.
+    ///
+    /// Returns `Err` if the browser has not been started or is paused.
+    fn poll(&self, timeout: Duration) -> Result<()>;
+
+    /// Suspends browsing without tearing down the browser. The underlying browse operation is
+    /// stopped, but the connection to the daemon and the set of discovered services are kept.
+    ///
+    /// The `EventLoop` returned by `browse_services()` must be dropped first, so no callbacks
+    /// are delivered while paused. Does nothing if the browser is not browsing.
+    fn pause(&mut self);
+
+    /// Resumes browsing after `pause()`, returning a new `EventLoop` to drive the browser.
+    ///
+    /// Browsing is restarted, so services that are still present are discovered and passed to
+    /// the callback again. Returns `Err` if the browser is not paused.
+    fn resume(&mut self) -> Result<Self::EventLoop<'_>>;
+
+    /// Stops browsing, as `unregister()` does for a service, and forgets every discovered
+    /// service. Unlike `pause()`, the connection to the daemon is closed as well.
+    ///
+    /// The browser keeps its settings and callback, and can be started again with
+    /// `browse_services()`, which discovers every service that is still present anew. The
+    /// `EventLoop` returned by `browse_services()` must be dropped first. Does nothing if the
+    /// browser is not browsing.
+    fn close(&mut self) -> Result<()>;
+
+    /// Re-resolves the previously discovered service instance with the specified `name`, e.g.
+    /// after failing to connect to it. The updated information is delivered through the
+    /// [`ServiceDiscoveredCallback`].
+    ///
+    /// Returns `Err` if no service with this name has been discovered by this browser, or if it
+    /// has since been removed.
+    ///
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    fn refresh(&mut self, name: &str) -> Result<()>;
+
+    /// Returns how often the service instance with the specified `name` has been announced,
+    /// removed and resolved since browsing started, which helps to identify devices that keep
+    /// dropping off the network. Returns `None` if no such service has been discovered.
+    ///
+    /// Statistics are kept across `pause()` and `resume()`, and forgotten by `close()`.
+    fn instance_stats(&self, name: &str) -> Option<InstanceStats>;
+
+    /// Resolves a single service instance with the specified `name` and `service_type` in the
+    /// default domain without browsing, blocking until it has been resolved or `timeout` has
+    /// elapsed.
+    ///
+    /// The `timeout` covers the whole operation; on Bonjour this includes both looking up the
+    /// SRV/TXT records and, separately, the host's address. A service is only returned once its
+    /// address is known: if the address lookup does not complete in time, `Err` is returned
+    /// rather than a partially resolved service.
+    fn resolve(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+    ) -> Result<ServiceDiscovery>
+    where
+        Self: Sized;
+
+    /// Starts resolving a single service instance like `resolve()`, but without blocking. The
+    /// returned handle is polled to drive the resolve, and can be cancelled, e.g. when the user
+    /// moves on before the service has been resolved.
+    ///
+    /// `callback` is invoked exactly once from [`TResolveHandle::poll()`], with the resolved
+    /// service or with `Err` if resolving failed or `timeout` elapsed, unless the resolve is
+    /// cancelled first.
+    ///
+    /// [`TResolveHandle::poll()`]: trait.TResolveHandle.html#tymethod.poll
+    fn resolve_start(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+        callback: Box<ServiceDiscoveredCallback>,
+    ) -> Result<Self::ResolveHandle>
+    where
+        Self: Sized;
+
+    /// Enumerates the browse domains advertised to this host through `b._dns-sd._udp` PTR
+    /// records, blocking until the daemon has reported all of them or `timeout` has elapsed.
+    ///
+    /// The result can be passed to [`set_domains()`] to browse every advertised domain. Domains
+    /// found before `timeout` elapses are returned rather than an error, since networks are not
+    /// required to advertise any browse domains.
+    ///
+    /// [`set_domains()`]: #tymethod.set_domains
+    fn browse_domains(timeout: Duration) -> Result<Vec<String>>
+    where
+        Self: Sized;
+}
+
+/// Interface for driving a resolve started with [`TMdnsBrowser::resolve_start()`].
+///
+/// [`TMdnsBrowser::resolve_start()`]: trait.TMdnsBrowser.html#tymethod.resolve_start
+pub trait TResolveHandle {
+    /// Processes the daemon's replies, waiting up to `timeout` for one to arrive, and invokes the
+    /// callback once the resolve has completed. Does nothing once the resolve has completed or
+    /// been cancelled.
+    fn poll(&mut self, timeout: Duration) -> Result<()>;
+
+    /// Stops the resolve and frees the daemon resources held for it. The callback is not invoked
+    /// afterwards, even if a reply has already arrived but has not been processed yet.
+    fn cancel(&mut self);
+
+    /// Returns `true` once the resolve has completed or been cancelled.
+    fn is_done(&self) -> bool;
+}
+
+/// Returns `Err` with `ErrorKind::InvalidConfiguration` if multicast is forced with
+/// `TMdnsBrowser::set_force_multicast()` for a browse of the unicast domain set with
+/// `TMdnsBrowser::set_wide_area()`.
+/// Returns `true` if `ip` is a unicast link-local address (`fe80::/10`), which is only meaningful
+/// together with the interface it was seen on.
+fn is_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+pub(crate) fn check_force_multicast(wide_area: bool, force_multicast: bool) -> Result<()> {
+    if wide_area && force_multicast {
+        return Err(Error::with_kind(
+            ErrorKind::InvalidConfiguration,
+            "multicast cannot be forced while browsing a wide-area domain",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if the instance `name` matches the prefix set with
+/// `TMdnsBrowser::set_name_prefix()`.
+pub(crate) fn matches_name_prefix(name: &str, prefix: Option<&str>) -> bool {
+    match prefix {
+        Some(prefix) => name.starts_with(prefix),
+        None => true,
+    }
+}
+
+/// Implements `TMdnsBrowser::resolve()` by polling `resolve_start()` until it completes.
+pub(crate) fn resolve_blocking<B: TMdnsBrowser>(
+    name: &str,
+    service_type: ServiceType,
+    timeout: Duration,
+) -> Result<ServiceDiscovery> {
+    let result: Rc<RefCell<Option<Result<ServiceDiscovery>>>> = Rc::default();
+    let slot = result.clone();
+
+    let mut handle = B::resolve_start(
+        name,
+        service_type,
+        timeout,
+        Box::new(move |r, _| {
+            *slot.borrow_mut() = Some(r);
+            ControlFlow::Break(())
+        }),
+    )?;
+
+    while !handle.is_done() {
+        handle.poll(timeout)?;
+    }
+
+    let result = result.borrow_mut().take();
+    result.unwrap_or_else(|| Err("resolve did not produce a result".into()))
 }
 
 /// Callback invoked from [`MdnsBrowser`] once a service has been discovered and resolved.
 ///
+/// The callback is always invoked on the thread that is polling the browser's [`EventLoop`], so
+/// it may freely mutate captured state without any synchronization. Because of this, it is not
+/// required to be `Send` or `Sync`; any state that must also be observed from another thread
+/// still needs to be shared through the usual primitives (e.g. `Arc<Mutex<_>>`).
+///
+/// Returning `ControlFlow::Break(())` signals that the browser has found what it was looking
+/// for; the [`EventLoop`] stops after the current iteration and any further calls to `poll()`
+/// return immediately. Return `ControlFlow::Continue(())` to keep browsing.
+///
 /// # Arguments
 /// * `discovered_service` - The service that was disovered
 /// * `context` - The optional user context passed through
 ///
 /// [`MdnsBrowser`]: type.MdnsBrowser.html
-pub type ServiceDiscoveredCallback = dyn Fn(Result<ServiceDiscovery>, Option<Arc<dyn Any>>);
+/// [`EventLoop`]: type.EventLoop.html
+pub type ServiceDiscoveredCallback =
+    dyn FnMut(Result<ServiceDiscovery>, Option<Arc<dyn Any>>) -> ControlFlow<()>;
+
+/// Callback invoked from [`MdnsBrowser`] once a service it has discovered is removed from the
+/// network, e.g. because the service was unregistered or its host went away.
+///
+/// The callback is invoked on the same thread as the [`ServiceDiscoveredCallback`], and
+/// returning `ControlFlow::Break(())` stops the [`EventLoop`] in the same way.
+///
+/// # Arguments
+/// * `removed_service` - The service that was removed
+/// * `context` - The optional user context passed through
+///
+/// [`MdnsBrowser`]: type.MdnsBrowser.html
+/// [`ServiceDiscoveredCallback`]: type.ServiceDiscoveredCallback.html
+/// [`EventLoop`]: type.EventLoop.html
+pub type ServiceRemovedCallback =
+    dyn FnMut(ServiceRemoval, Option<Arc<dyn Any>>) -> ControlFlow<()>;
+
+/// Represents a service that has been removed from the network, as passed to the
+/// [`ServiceRemovedCallback`].
+///
+/// [`ServiceRemovedCallback`]: type.ServiceRemovedCallback.html
+#[derive(Debug, Getters, Builder, BuilderDelegate, Clone, PartialEq, Eq)]
+pub struct ServiceRemoval {
+    name: String,
+    service_type: ServiceType,
+    domain: String,
+}
 
 /// Represents a service that has been discovered by a [`MdnsBrowser`].
 ///
@@ -53,6 +436,488 @@ pub struct ServiceDiscovery {
     domain: String,
     host_name: String,
     address: String,
+    /// Every address the service's host was resolved to, of which `address` is the first
+    #[builder(default)]
+    #[serde(default)]
+    addresses: Vec<IpAddr>,
     port: u16,
+    /// `None` if the service's TXT record holds no items, which includes the empty TXT record
+    /// advertised by services registered without one.
     txt: Option<TxtRecord>,
+    #[getter(skip)]
+    #[builder(default)]
+    #[serde(default)]
+    is_local: bool,
+    #[getter(skip)]
+    #[builder(default)]
+    #[serde(default)]
+    is_wide_area: bool,
+    /// The index of the network interface the service was resolved on
+    #[builder(default)]
+    #[serde(default)]
+    interface_index: u32,
+    #[getter(skip)]
+    #[builder(default)]
+    #[serde(default)]
+    more_coming: bool,
+    #[getter(skip)]
+    #[builder(default)]
+    #[serde(default)]
+    records: Vec<ResolvedRecord>,
+}
+
+impl ServiceDiscovery {
+    /// Returns `true` if this service was published by the local machine. This is useful to
+    /// avoid connecting to a service registered by this same process.
+    ///
+    /// On Linux this reflects Avahi's `AVAHI_LOOKUP_RESULT_LOCAL` flag. On macOS, Bonjour does not
+    /// report this directly, so the service is considered local if it was discovered on the
+    /// local-only interface or its host name is the host name of this machine.
+    pub fn is_local(&self) -> bool {
+        self.is_local
+    }
+
+    /// Returns `true` if this service was registered by this process, as described by
+    /// `set_ignore_local()`.
+    pub(crate) fn is_registered_by_this_process(&self) -> bool {
+        self.is_local && crate::service::is_claimed(&self.service_type, self.port)
+    }
+
+    /// Returns `true` if this service was resolved through unicast DNS-SD (see `set_wide_area()`)
+    /// rather than link-local multicast, e.g. to apply a stricter trust policy to it.
+    ///
+    /// On Linux this reflects Avahi's `AVAHI_LOOKUP_RESULT_WIDE_AREA` flag. Bonjour does not
+    /// report which mechanism answered, but multicast DNS only serves the `local` domain, so on
+    /// macOS a service is considered wide-area if it was found in any other domain.
+    pub fn is_wide_area(&self) -> bool {
+        self.is_wide_area
+    }
+
+    /// Returns `true` if more discoveries are expected to be delivered immediately after this
+    /// one. UIs can use this to defer redrawing until a discovery without it arrives.
+    ///
+    /// On macOS this reflects Bonjour's `kDNSServiceFlagsMoreComing` flag. Avahi has no
+    /// equivalent, and its `ALL_FOR_NOW` event is signalled before the services it covers have
+    /// been resolved, so on Linux this is `true` while other services are still being resolved.
+    pub fn more_coming(&self) -> bool {
+        self.more_coming
+    }
+
+    /// Returns the DNS records that were seen while resolving this service (SRV, TXT, and A or
+    /// AAAA), for investigating why a field holds an unexpected value.
+    ///
+    /// Neither daemon passes the raw records to clients, so they are reassembled from the
+    /// resolve replies: on Linux from the `AvahiServiceResolver` callback, and on macOS from the
+    /// `DNSServiceResolve()` and `DNSServiceGetAddrInfo()` replies. Avahi reports no TTLs, and
+    /// neither daemon reports the priority and weight of the SRV record, which are encoded as
+    /// `0`. Discoveries that were not resolved by a daemon, such as those of the mock backend,
+    /// have no records.
+    pub fn records(&self) -> Vec<ResolvedRecord> {
+        self.records.clone()
+    }
+
+    #[cfg(feature = "mock")]
+    pub(crate) fn set_more_coming(&mut self, more_coming: bool) {
+        self.more_coming = more_coming;
+    }
+
+    #[cfg(feature = "mock")]
+    pub(crate) fn clear_address(&mut self) {
+        self.address.clear();
+        self.addresses.clear();
+    }
+
+    #[cfg(feature = "mock")]
+    pub(crate) fn set_txt(&mut self, txt: Option<TxtRecord>) {
+        self.txt = txt;
+    }
+
+    /// Returns the name of the network interface the service was resolved on (e.g. `en0`), or
+    /// `None` if the interface is unknown or no longer exists.
+    pub fn interface_name(&self) -> Option<String> {
+        interface::interface_name(self.interface_index)
+    }
+
+    /// Returns the socket addresses this service can be connected to, combining each of its
+    /// [`addresses()`] with the service's port. Link-local IPv6 addresses are scoped to the
+    /// interface the service was resolved on, so they can be connected to as-is.
+    ///
+    /// On macOS these are all the A and AAAA records of the service's host. Avahi resolves a
+    /// single address per interface and address family, so on Linux these are the addresses
+    /// resolved so far on each of them, and a service reachable over several is reported again
+    /// with more addresses as they are resolved. A discovery built with `address` only yields
+    /// that address.
+    ///
+    /// [`addresses()`]: #method.addresses
+    pub fn endpoints(&self) -> Vec<SocketAddr> {
+        self.ip_addresses()
+            .into_iter()
+            .map(|ip| match ip {
+                IpAddr::V6(ip) if is_link_local(&ip) => {
+                    SocketAddr::V6(SocketAddrV6::new(ip, self.port, 0, self.interface_index))
+                }
+                ip => SocketAddr::new(ip, self.port),
+            })
+            .collect()
+    }
+
+    /// Returns the resolved addresses, falling back to `address` for discoveries that were built
+    /// without `addresses`.
+    fn ip_addresses(&self) -> Vec<IpAddr> {
+        if self.addresses.is_empty() {
+            self.address.parse().into_iter().collect()
+        } else {
+            self.addresses.clone()
+        }
+    }
+
+    /// Returns the number of addresses this discovery was resolved to, which is the number of
+    /// [`endpoints()`], or `0` if the address was not resolved (see `set_resolve_address()`).
+    ///
+    /// [`endpoints()`]: #method.endpoints
+    pub fn address_count(&self) -> usize {
+        self.ip_addresses().len()
+    }
+
+    /// Returns `true` if this discovery was resolved to an IPv4 address.
+    pub fn has_ipv4(&self) -> bool {
+        self.ip_addresses().iter().any(|ip| ip.is_ipv4())
+    }
+
+    /// Returns `true` if this discovery was resolved to an IPv6 address.
+    pub fn has_ipv6(&self) -> bool {
+        self.ip_addresses().iter().any(|ip| ip.is_ipv6())
+    }
+
+    /// Returns a URL for an `_http._tcp` or `_https._tcp` service (e.g. `http://my-host.local:8080/`),
+    /// or `None` for any other service type.
+    ///
+    /// The URL points at the service's host name, or at its address if no host name is known,
+    /// with a link-local IPv6 address scoped to `interface_index` as described in RFC 6874. Its
+    /// path is taken from the `path` TXT key as described in the DNS-SD specification for
+    /// HTTP services, defaulting to `/`.
+    pub fn to_url(&self) -> Option<String> {
+        let scheme = match self.service_type.name().as_str() {
+            "http" => "http",
+            "https" => "https",
+            _ => return None,
+        };
+
+        let host = match self.host_name.trim_end_matches('.') {
+            "" => match self.address.parse() {
+                // RFC 6874 zone ID, with the `%` percent-encoded
+                Ok(IpAddr::V6(ip)) if is_link_local(&ip) && self.interface_index != 0 => {
+                    format!("[{}%25{}]", ip, self.interface_index)
+                }
+                _ if self.address.contains(':') => format!("[{}]", self.address),
+                _ => self.address.clone(),
+            },
+            host_name => host_name.to_string(),
+        };
+
+        let path = self
+            .txt
+            .as_ref()
+            .and_then(|t| t.get("path"))
+            .unwrap_or_default();
+
+        let slash = if path.starts_with('/') { "" } else { "/" };
+
+        Some(format!(
+            "{}://{}:{}{}{}",
+            scheme, host, self.port, slash, path
+        ))
+    }
+
+    /// Deserializes the service's TXT record into `T`, mapping each key to a field of the same
+    /// name. A service without a TXT record is treated as an empty one.
+    ///
+    /// TXT values are stored as strings and are parsed into numeric and boolean fields as
+    /// needed. Returns `Err` if a required field is missing or a value cannot be parsed into the
+    /// type of its field.
+    pub fn txt_as<T: DeserializeOwned>(&self) -> std::result::Result<T, value::Error> {
+        let map = self.txt.as_ref().map(|t| t.to_map()).unwrap_or_default();
+        T::deserialize(MapDeserializer::new(
+            map.into_iter().map(|(k, v)| (k, TxtValueDeserializer(v))),
+        ))
+    }
+}
+
+/// A DNS record seen while resolving a [`ServiceDiscovery`], as returned by
+/// [`ServiceDiscovery::records()`].
+///
+/// [`ServiceDiscovery`]: struct.ServiceDiscovery.html
+/// [`ServiceDiscovery::records()`]: struct.ServiceDiscovery.html#method.records
+#[derive(Debug, Getters, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ResolvedRecord {
+    /// The name the record is registered under: the service instance for SRV and TXT records,
+    /// and the host name for address records
+    name: String,
+    /// The DNS record type, e.g. [`ResolvedRecord::SRV`]
+    ///
+    /// [`ResolvedRecord::SRV`]: #associatedconstant.SRV
+    rrtype: u16,
+    /// The record data, encoded as it is sent on the wire
+    rdata: Vec<u8>,
+    /// The time to live of the record in seconds, or `None` if the daemon did not report it
+    ttl: Option<u32>,
+}
+
+impl ResolvedRecord {
+    /// The record type of IPv4 address records.
+    pub const A: u16 = 1;
+    /// The record type of TXT records.
+    pub const TXT: u16 = 16;
+    /// The record type of IPv6 address records.
+    pub const AAAA: u16 = 28;
+    /// The record type of SRV records.
+    pub const SRV: u16 = 33;
+
+    /// Creates the SRV record of the service instance `name` pointing at `port` on `target`.
+    pub(crate) fn srv(name: &str, port: u16, target: &str, ttl: Option<u32>) -> Self {
+        let mut rdata = vec![0, 0, 0, 0];
+        rdata.extend_from_slice(&port.to_be_bytes());
+        for label in target.split('.').filter(|l| !l.is_empty()) {
+            rdata.push(label.len() as u8);
+            rdata.extend_from_slice(label.as_bytes());
+        }
+        rdata.push(0);
+
+        Self {
+            name: name.to_string(),
+            rrtype: Self::SRV,
+            rdata,
+            ttl,
+        }
+    }
+
+    /// Creates the TXT record of the service instance `name` from its encoded `txt` data.
+    pub(crate) fn txt(name: &str, txt: &[u8], ttl: Option<u32>) -> Self {
+        Self {
+            name: name.to_string(),
+            rrtype: Self::TXT,
+            // an empty TXT record holds a single empty string
+            rdata: if txt.is_empty() {
+                vec![0]
+            } else {
+                txt.to_vec()
+            },
+            ttl,
+        }
+    }
+
+    /// Creates the A or AAAA record of `host_name` for `address`.
+    pub(crate) fn address(host_name: &str, address: IpAddr, ttl: Option<u32>) -> Self {
+        let (rrtype, rdata) = match address {
+            IpAddr::V4(ip) => (Self::A, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (Self::AAAA, ip.octets().to_vec()),
+        };
+
+        Self {
+            name: host_name.to_string(),
+            rrtype,
+            rdata,
+            ttl,
+        }
+    }
+}
+
+/// Statistics about a single service instance seen by a browser, as returned by
+/// [`TMdnsBrowser::instance_stats()`].
+///
+/// [`TMdnsBrowser::instance_stats()`]: trait.TMdnsBrowser.html#tymethod.instance_stats
+#[derive(Debug, Getters, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceStats {
+    /// The number of times the daemon has announced the instance
+    times_seen: u32,
+    /// The number of times the daemon has reported the instance as removed
+    times_removed: u32,
+    /// The number of times the instance has been resolved, including `refresh()` and the
+    /// refresh interval
+    times_resolved: u32,
+    /// When the instance was last announced or resolved
+    last_seen: Instant,
+}
+
+/// The `InstanceStats` of every instance seen by a browser, keyed by instance name.
+#[derive(Debug, Default)]
+pub(crate) struct InstanceStatsTable(HashMap<String, InstanceStats>);
+
+impl InstanceStatsTable {
+    pub(crate) fn get(&self, name: &str) -> Option<InstanceStats> {
+        self.0.get(name).copied()
+    }
+
+    pub(crate) fn record_seen(&mut self, name: &str) {
+        let now = Instant::now();
+        let stats = self.0.entry(name.to_string()).or_insert(InstanceStats {
+            times_seen: 0,
+            times_removed: 0,
+            times_resolved: 0,
+            last_seen: now,
+        });
+
+        stats.times_seen += 1;
+        stats.last_seen = now;
+    }
+
+    pub(crate) fn record_removed(&mut self, name: &str) {
+        if let Some(stats) = self.0.get_mut(name) {
+            stats.times_removed += 1;
+        }
+    }
+
+    pub(crate) fn record_resolved(&mut self, name: &str) {
+        if let Some(stats) = self.0.get_mut(name) {
+            stats.times_resolved += 1;
+            stats.last_seen = Instant::now();
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// An event reported by a [`ServiceIter`].
+///
+/// [`ServiceIter`]: struct.ServiceIter.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServiceEvent {
+    /// A service has been discovered and resolved
+    Discovered(ServiceDiscovery),
+    /// A previously discovered service has been removed from the network
+    Removed {
+        /// The name of the service instance
+        name: String,
+        /// The type of the service
+        service_type: ServiceType,
+        /// The domain the service was removed from
+        domain: String,
+    },
+}
+
+impl From<ServiceRemoval> for ServiceEvent {
+    fn from(removal: ServiceRemoval) -> Self {
+        Self::Removed {
+            name: removal.name,
+            service_type: removal.service_type,
+            domain: removal.domain,
+        }
+    }
+}
+
+/// A blocking iterator over the services discovered by a browser, created by calling
+/// `into_iter()` on a [`MdnsBrowser`] (e.g. with `for event in browser { ... }`).
+///
+/// The browser is started when the iterator is created and `next()` polls it until a service
+/// has been discovered or removed. The browser's callbacks are replaced, since these events are
+/// returned from `next()` instead. Browsing stops when the iterator is dropped.
+///
+/// Iteration ends after an error has been returned from polling the browser, or once no event
+/// arrives within the timeout set with [`with_timeout()`]. Without a timeout, `next()` blocks
+/// until the next event.
+///
+/// [`MdnsBrowser`]: ../type.MdnsBrowser.html
+/// [`with_timeout()`]: #method.with_timeout
+pub struct ServiceIter<B: TMdnsBrowser> {
+    browser: B,
+    events: Rc<RefCell<VecDeque<Result<ServiceEvent>>>>,
+    error: Option<Error>,
+    timeout: Option<Duration>,
+    done: bool,
+}
+
+impl<B: TMdnsBrowser> ServiceIter<B> {
+    pub(crate) fn new(mut browser: B) -> Self {
+        let events: Rc<RefCell<VecDeque<Result<ServiceEvent>>>> = Rc::default();
+
+        browser.set_service_discovered_callback(Box::new({
+            let events = events.clone();
+            move |service, _| {
+                events
+                    .borrow_mut()
+                    .push_back(service.map(ServiceEvent::Discovered));
+                ControlFlow::Continue(())
+            }
+        }));
+
+        browser.set_service_removed_callback(Box::new({
+            let events = events.clone();
+            move |removal, _| {
+                events.borrow_mut().push_back(Ok(removal.into()));
+                ControlFlow::Continue(())
+            }
+        }));
+
+        let error = browser.start().err();
+
+        Self {
+            browser,
+            events,
+            error,
+            timeout: None,
+            done: false,
+        }
+    }
+
+    /// Ends iteration once `next()` has waited for `timeout` without an event arriving.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the browser being iterated on.
+    pub fn browser(&self) -> &B {
+        &self.browser
+    }
+}
+
+impl<B: TMdnsBrowser> Iterator for ServiceIter<B> {
+    type Item = Result<ServiceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            self.done = true;
+            return Some(Err(error));
+        }
+
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let mut polled = false;
+
+        loop {
+            if let Some(event) = self.events.borrow_mut().pop_front() {
+                return Some(event);
+            } else if self.done {
+                return None;
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => Duration::from_secs(1),
+            };
+
+            // poll at least once, so that a zero timeout still picks up ready events
+            if polled && remaining.is_zero() {
+                return None;
+            }
+
+            if let Err(error) = self.browser.poll(remaining) {
+                self.done = true;
+                return Some(Err(error));
+            }
+
+            polled = true;
+        }
+    }
+}
+
+impl<B: TMdnsBrowser + fmt::Debug> fmt::Debug for ServiceIter<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceIter")
+            .field("browser", &self.browser)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
 }
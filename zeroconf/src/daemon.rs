@@ -0,0 +1,31 @@
+//! Information about the mDNS daemon backing this crate.
+
+use crate::Result;
+
+/// Describes the mDNS implementation in use, as returned by [`daemon_info()`].
+///
+/// [`daemon_info()`]: fn.daemon_info.html
+#[derive(Clone, Debug, Getters, PartialEq, Eq)]
+pub struct DaemonInfo {
+    /// The name of the backend (`avahi` or `bonjour`)
+    backend: String,
+    /// The version of the daemon, if it could be determined
+    version: Option<String>,
+}
+
+/// Returns the name and version of the mDNS daemon used by this crate.
+///
+/// On Linux, this connects to the Avahi daemon and fails if it is not running. The version is
+/// the string reported by `avahi_client_get_version_string()` (e.g. `avahi 0.8`). On macOS, the
+/// version is that of mDNSResponder (e.g. `1310.80.1`).
+pub fn daemon_info() -> Result<DaemonInfo> {
+    #[cfg(target_os = "linux")]
+    let (backend, version) = ("avahi", crate::linux::avahi_util::daemon_version()?);
+    #[cfg(target_vendor = "apple")]
+    let (backend, version) = ("bonjour", crate::macos::bonjour_util::daemon_version());
+
+    Ok(DaemonInfo {
+        backend: backend.to_string(),
+        version,
+    })
+}
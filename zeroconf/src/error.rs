@@ -6,6 +6,76 @@ use std::fmt;
 #[derive(new, Debug, Clone, PartialEq, Eq)]
 pub struct Error {
     description: String,
+    #[new(value = "ErrorKind::Other")]
+    kind: ErrorKind,
+    #[new(value = "None")]
+    raw_code: Option<i32>,
+}
+
+/// Categorizes an [`Error`] so that callers can react to specific failures.
+///
+/// [`Error`]: struct.Error.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A service with the same type, name, domain and port is already registered by this
+    /// process.
+    NameConflict,
+    /// `register()` was called on a service that is already registered and has not been
+    /// unregistered since.
+    AlreadyRegistered,
+    /// A time limit, such as the one set with `MdnsBrowser::set_max_duration()`, has elapsed.
+    TimedOut,
+    /// A temporary failure that the operation recovered from, such as one reported with
+    /// `EventLoop::last_error()`.
+    Transient,
+    /// The requested operation is not supported by this platform's mDNS implementation.
+    Unsupported,
+    /// A `ServiceType`, or a key inserted into a `TxtRecord`, does not conform to DNS-SD
+    /// (RFC 6763). Also returned by `TxtRecord::validate()`.
+    InvalidServiceType,
+    /// A service's settings are invalid in combination, such as an SRV target host name without
+    /// a domain. Returned by `register()` before the daemon is contacted.
+    InvalidConfiguration,
+    /// Data received from the network about a discovered service, such as its name or TXT
+    /// record, is malformed. Only that discovery is affected; the browser keeps running.
+    Malformed,
+    /// Any other error.
+    Other,
+}
+
+impl Error {
+    /// Creates a new `Error` of the specified `kind`.
+    pub fn with_kind(kind: ErrorKind, description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            kind,
+            raw_code: None,
+        }
+    }
+
+    /// Attaches the error code reported by the daemon to this error.
+    pub(crate) fn with_raw_code(mut self, raw_code: i32) -> Self {
+        self.raw_code = Some(raw_code);
+        self
+    }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the error code reported by the daemon, if this error originated from one: an
+    /// Avahi error code (e.g. `AVAHI_ERR_INVALID_SERVICE_TYPE`) on Linux, or a
+    /// `DNSServiceErrorType` (e.g. `kDNSServiceErr_BadParam`) on macOS.
+    ///
+    /// This is preserved for cross-referencing the daemon's documentation; use [`kind()`] to
+    /// react to specific failures.
+    ///
+    /// [`kind()`]: #method.kind
+    pub fn raw_code(&self) -> Option<i32> {
+        self.raw_code
+    }
 }
 
 impl std::error::Error for Error {}
@@ -1,11 +1,73 @@
 //! Trait definition for cross-platform event loop
 
-use crate::Result;
+use crate::error::{Error, ErrorKind};
+use crate::{Result, ServiceRegistration};
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
 use std::time::Duration;
 
 /// A handle on the underlying implementation to poll the event loop. Typically, `poll()`
 /// is called in a loop to keep a `MdnsService` or `MdnsBrowser` running.
 pub trait TEventLoop {
     /// Polls for new events.
+    ///
+    /// Once a callback has returned `ControlFlow::Break(())`, this becomes a no-op.
     fn poll(&self, timeout: Duration) -> Result<()>;
+
+    /// Processes all events that are ready without blocking, returning how many were handled.
+    ///
+    /// Returns `Ok(0)` if nothing was ready or a callback has returned `ControlFlow::Break(())`.
+    fn try_poll(&self) -> Result<usize>;
+
+    /// Polls the event loop until the service it drives has been registered, returning the
+    /// registration or the error reported to the [`ServiceRegisteredCallback`]. The event loop
+    /// remains usable afterwards, e.g. to keep driving a browser.
+    ///
+    /// Returns immediately if the registration was already established. Fails if `timeout`
+    /// elapses first, or if this event loop was not returned by `MdnsService::register()`.
+    ///
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    fn wait_for_registration(&self, timeout: Duration) -> Result<ServiceRegistration>;
+
+    /// Returns `true` if a callback has requested that this event loop stop.
+    fn is_stopped(&self) -> bool;
+
+    /// Returns the most recent non-fatal error that `poll()` or `try_poll()` recovered from
+    /// instead of returning it, with [`ErrorKind::Transient`]. Fatal errors are always returned
+    /// from `poll()`.
+    ///
+    /// On macOS, `kDNSServiceErr_Transient`, `kDNSServiceErr_Timeout` and
+    /// `kDNSServiceErr_NoRouter` reported while processing results are non-fatal. Avahi reports
+    /// no such errors, so on Linux this always returns `None`.
+    ///
+    /// [`ErrorKind::Transient`]: ../error/enum.ErrorKind.html#variant.Transient
+    fn last_error(&self) -> Option<Error> {
+        None
+    }
+
+    /// Passes the socket this event loop performs I/O on to `configure`, so that socket options
+    /// this crate does not wrap can be set on it. `configure` is invoked immediately, so this
+    /// should be called before the first `poll()`.
+    ///
+    /// Only macOS supports this: the socket is the connection to mDNSResponder returned by
+    /// `DNSServiceRefSockFD()`, since the multicast socket itself is owned by the daemon. Avahi
+    /// does not expose any of its sockets, so on Linux this returns `Err` with
+    /// [`ErrorKind::Unsupported`]. An error returned by `configure` is passed through.
+    ///
+    /// Only available on Unix platforms, where the socket is a `RawFd`.
+    ///
+    /// [`ErrorKind::Unsupported`]: ../error/enum.ErrorKind.html#variant.Unsupported
+    #[cfg(unix)]
+    fn configure_socket(&self, configure: impl FnOnce(RawFd) -> io::Result<()>) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let _ = configure;
+        Err(Error::with_kind(
+            ErrorKind::Unsupported,
+            "this event loop does not expose its socket",
+        ))
+    }
 }
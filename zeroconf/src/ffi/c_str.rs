@@ -1,5 +1,7 @@
 //! Utilities related to c-string handling
 
+use crate::error::{Error, ErrorKind};
+use crate::Result;
 use libc::c_char;
 use std::ffi::{CStr, CString};
 
@@ -26,6 +28,29 @@ pub unsafe fn raw_to_str<'a>(s: *const c_char) -> &'a str {
     CStr::from_ptr(s).to_str().unwrap()
 }
 
+/// Returns the specified `*const c_char` as a `&'a str`, or `Err` with [`ErrorKind::Malformed`]
+/// if it is not valid UTF-8. Ownership is not taken.
+///
+/// Unlike [`raw_to_str()`], this does not panic on strings received from peers on the network,
+/// which may be advertising malformed names.
+///
+/// # Safety
+/// This function is unsafe due to a call to the unsafe function [`CStr::from_ptr()`].
+///
+/// [`ErrorKind::Malformed`]: ../../error/enum.ErrorKind.html#variant.Malformed
+/// [`raw_to_str()`]: fn.raw_to_str.html
+/// [`CStr::from_ptr()`]: https://doc.rust-lang.org/std/ffi/struct.CStr.html#method.from_ptr
+pub unsafe fn try_raw_to_str<'a>(s: *const c_char) -> Result<&'a str> {
+    assert_not_null!(s);
+    let c_str = CStr::from_ptr(s);
+    c_str.to_str().map_err(|_| {
+        Error::with_kind(
+            ErrorKind::Malformed,
+            format!("`{}` is not valid UTF-8", c_str.to_string_lossy()),
+        )
+    })
+}
+
 /// Copies the specified `*const c_char` into a `String`.
 ///
 /// # Safety
@@ -55,6 +80,19 @@ mod tests {
         unsafe { raw_to_str(ptr::null() as *const c_char) };
     }
 
+    #[test]
+    fn try_raw_to_str_success() {
+        let c_string = c_string!("foo");
+        unsafe { assert_eq!(try_raw_to_str(c_string.as_ptr()), Ok("foo")) };
+    }
+
+    #[test]
+    fn try_raw_to_str_invalid_utf8() {
+        let c_string = CString::new(vec![b'f', 0xff, b'o']).unwrap();
+        let err = unsafe { try_raw_to_str(c_string.as_ptr()) }.unwrap_err();
+        assert_eq!(ErrorKind::Malformed, err.kind());
+    }
+
     #[test]
     fn copy_raw_success() {
         let c_string = c_string!("foo");
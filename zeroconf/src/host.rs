@@ -0,0 +1,79 @@
+//! Resolving host names independently of service discovery.
+
+use crate::Result;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Resolves the specified `host` (e.g. the `host_name()` of a [`ServiceDiscovery`]) to all of
+/// its IPv4 and IPv6 addresses, blocking until they have been resolved or `timeout` has elapsed.
+///
+/// A `host` without a domain (e.g. `my-host`) is looked up in `domain`, or in `local` if `domain`
+/// is `None`. A `host` that already ends with `domain`, or any qualified `host` if `domain` is
+/// `None`, is looked up as is. Names outside `local` are resolved through unicast DNS, which on
+/// Linux requires Avahi to be started with `enable-wide-area=yes`.
+///
+/// Addresses resolved before `timeout` elapses are returned; `Err` with
+/// [`ErrorKind::TimedOut`] is returned if none were. On Linux this uses
+/// `avahi_host_name_resolver_new()`, which reports a single address per address family. On macOS
+/// this uses `DNSServiceGetAddrInfo()`, which reports every address of the host.
+///
+/// [`ServiceDiscovery`]: ../struct.ServiceDiscovery.html
+/// [`ErrorKind::TimedOut`]: ../error/enum.ErrorKind.html#variant.TimedOut
+pub fn resolve_host(host: &str, domain: Option<&str>, timeout: Duration) -> Result<Vec<IpAddr>> {
+    let fqdn = qualify(host, domain);
+
+    #[cfg(target_os = "linux")]
+    let addresses = crate::linux::avahi_util::resolve_host(&fqdn, timeout)?;
+    #[cfg(target_vendor = "apple")]
+    let addresses = crate::macos::bonjour_util::resolve_host(&fqdn, timeout)?;
+
+    Ok(addresses)
+}
+
+/// Returns the fully qualified name to look `host` up under, without a trailing dot.
+fn qualify(host: &str, domain: Option<&str>) -> String {
+    let host = host.trim_end_matches('.');
+
+    match domain.map(|d| d.trim_matches('.')) {
+        Some(domain) if is_in_domain(host, domain) => host.to_string(),
+        Some(domain) => format!("{}.{}", host, domain),
+        None if host.contains('.') => host.to_string(),
+        None => format!("{}.local", host),
+    }
+}
+
+/// Returns `true` if `name` ends with `domain`, ignoring case.
+pub(crate) fn is_in_domain(name: &str, domain: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_matches('.').to_ascii_lowercase();
+
+    name.ends_with(&format!(".{}", domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualify_defaults_to_local() {
+        assert_eq!("my-host.local", qualify("my-host", None));
+    }
+
+    #[test]
+    fn qualify_keeps_qualified_host() {
+        assert_eq!("my-host.local", qualify("my-host.local.", None));
+        assert_eq!("my-host.example.com", qualify("my-host.example.com", None));
+    }
+
+    #[test]
+    fn qualify_custom_domain() {
+        assert_eq!(
+            "my-host.example.com",
+            qualify("my-host", Some("example.com."))
+        );
+        assert_eq!(
+            "my-host.Example.com",
+            qualify("my-host.Example.com", Some("example.com"))
+        );
+    }
+}
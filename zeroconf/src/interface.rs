@@ -1,8 +1,135 @@
+use crate::ffi::c_str;
+use crate::Result;
+use libc::{c_char, c_int, ifaddrs};
+use std::ptr;
+use std::sync::Mutex;
+
+static INTERFACE_NAMES: Mutex<Vec<(u32, String)>> = Mutex::new(Vec::new());
+
 /// Represents a network interface for mDNS services
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NetworkInterface {
     /// No interface specified, bind to all available interfaces
+    #[default]
     Unspec,
     /// An interface at a specified index
     AtIndex(u32),
 }
+
+/// The IP address family to resolve discovered services to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Resolve to either IPv4 or IPv6 addresses
+    #[default]
+    Any,
+    /// Resolve to IPv4 addresses only
+    V4,
+    /// Resolve to IPv6 addresses only
+    V6,
+}
+
+/// Describes a network interface present on this machine, as returned by [`list_interfaces()`].
+///
+/// [`list_interfaces()`]: fn.list_interfaces.html
+#[derive(Clone, Debug, Getters, PartialEq, Eq)]
+pub struct NetworkInterfaceInfo {
+    index: u32,
+    name: String,
+    #[getter(skip)]
+    is_up: bool,
+    #[getter(skip)]
+    is_loopback: bool,
+    #[getter(skip)]
+    supports_multicast: bool,
+}
+
+impl NetworkInterfaceInfo {
+    /// Returns the [`NetworkInterface`] that can be passed to `set_network_interface()` to bind to
+    /// this interface.
+    ///
+    /// [`NetworkInterface`]: enum.NetworkInterface.html
+    pub fn interface(&self) -> NetworkInterface {
+        NetworkInterface::AtIndex(self.index)
+    }
+
+    /// Returns `true` if the interface is administratively up.
+    pub fn is_up(&self) -> bool {
+        self.is_up
+    }
+
+    /// Returns `true` if this is a loopback interface.
+    pub fn is_loopback(&self) -> bool {
+        self.is_loopback
+    }
+
+    /// Returns `true` if the interface supports multicast, which is required for mDNS.
+    pub fn supports_multicast(&self) -> bool {
+        self.supports_multicast
+    }
+}
+
+/// Returns the network interfaces present on this machine.
+///
+/// Internally this walks the list returned by `getifaddrs()`, which contains one entry per
+/// address; each interface is only reported once.
+pub fn list_interfaces() -> Result<Vec<NetworkInterfaceInfo>> {
+    let mut addrs: *mut ifaddrs = ptr::null_mut();
+
+    if unsafe { libc::getifaddrs(&mut addrs) } < 0 {
+        return Err("getifaddrs(): returned error status".into());
+    }
+
+    let mut interfaces: Vec<NetworkInterfaceInfo> = vec![];
+    let mut cur = addrs;
+
+    while !cur.is_null() {
+        let ifa = unsafe { &*cur };
+        cur = ifa.ifa_next;
+
+        let name = unsafe { c_str::copy_raw(ifa.ifa_name) };
+        if interfaces.iter().any(|i| i.name == name) {
+            continue;
+        }
+
+        let flags = ifa.ifa_flags as c_int;
+
+        interfaces.push(NetworkInterfaceInfo {
+            index: unsafe { libc::if_nametoindex(ifa.ifa_name) },
+            name,
+            is_up: flags & libc::IFF_UP != 0,
+            is_loopback: flags & libc::IFF_LOOPBACK != 0,
+            supports_multicast: flags & libc::IFF_MULTICAST != 0,
+        });
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+
+    Ok(interfaces)
+}
+
+/// Returns the name of the network interface at `index` (e.g. `en0`), or `None` if no such
+/// interface exists.
+///
+/// Internally this calls `if_indextoname()`. Names that were found are cached, since an index is
+/// not reused for another interface while the system is running.
+pub(crate) fn interface_name(index: u32) -> Option<String> {
+    if index == 0 {
+        return None;
+    }
+
+    let mut names = INTERFACE_NAMES.lock().unwrap();
+
+    if let Some((_, name)) = names.iter().find(|(i, _)| *i == index) {
+        return Some(name.clone());
+    }
+
+    let mut buf = [0 as c_char; libc::IF_NAMESIZE];
+    if unsafe { libc::if_indextoname(index, buf.as_mut_ptr()) }.is_null() {
+        return None;
+    }
+
+    let name = unsafe { c_str::copy_raw(buf.as_ptr()) };
+    names.push((index, name.clone()));
+
+    Some(name)
+}
@@ -18,6 +18,7 @@
 //!
 //! ```no_run
 //! use std::any::Any;
+//! use std::ops::ControlFlow;
 //! use std::sync::{Arc, Mutex};
 //! use std::time::Duration;
 //! use zeroconf::prelude::*;
@@ -50,7 +51,7 @@
 //! fn on_service_registered(
 //!     result: zeroconf::Result<ServiceRegistration>,
 //!     context: Option<Arc<dyn Any>>,
-//! ) {
+//! ) -> ControlFlow<()> {
 //!     let service = result.unwrap();
 //!
 //!     println!("Service registered: {:?}", service);
@@ -67,12 +68,20 @@
 //!     println!("Context: {:?}", context);
 //!
 //!     // ...
+//!
+//!     ControlFlow::Continue(())
 //! }
 //! ```
 //!
 //! ## Browsing services
+//!
+//! Callbacks return a [`ControlFlow`]. Returning `ControlFlow::Break(())` from a callback stops
+//! the [`EventLoop`] after the current iteration, which is useful when looking for a single
+//! service.
+//!
 //! ```no_run
 //! use std::any::Any;
+//! use std::ops::ControlFlow;
 //! use std::sync::Arc;
 //! use std::time::Duration;
 //! use zeroconf::prelude::*;
@@ -94,19 +103,65 @@
 //! fn on_service_discovered(
 //!     result: zeroconf::Result<ServiceDiscovery>,
 //!     _context: Option<Arc<dyn Any>>,
-//! ) {
+//! ) -> ControlFlow<()> {
 //!     println!("Service discovered: {:?}", result.unwrap());
 //!
 //!     // ...
+//!
+//!     ControlFlow::Continue(())
 //! }
 //! ```
 //!
+//! ## Generic code
+//!
+//! [`MdnsService`] and [`MdnsBrowser`] are aliases for the platform implementations, which all
+//! implement the [`TMdnsService`] and [`TMdnsBrowser`] traits found in the prelude. Code that
+//! should work with any backend can be written against the traits instead:
+//!
+//! ```no_run
+//! use std::ops::ControlFlow;
+//! use std::time::Duration;
+//! use zeroconf::prelude::*;
+//! use zeroconf::{MdnsBrowser, ServiceType};
+//!
+//! fn browse<B: TMdnsBrowser>(service_type: ServiceType) -> zeroconf::Result<()> {
+//!     let mut browser = B::new(service_type);
+//!
+//!     browser.set_service_discovered_callback(Box::new(|result, _| {
+//!         println!("Service discovered: {:?}", result);
+//!         ControlFlow::Continue(())
+//!     }));
+//!
+//!     let event_loop = browser.browse_services()?;
+//!
+//!     while !event_loop.is_stopped() {
+//!         event_loop.poll(Duration::from_secs(0))?;
+//!     }
+//!
+//!     Ok(())
+//! }
+//!
+//! fn main() -> zeroconf::Result<()> {
+//!     browse::<MdnsBrowser>(ServiceType::new("http", "tcp")?)
+//! }
+//! ```
+//!
+//! ## Testing
+//!
+//! Code written against the traits can be unit tested without a daemon by enabling the `mock`
+//! feature and using the in-memory backend in the [`mock`] module.
+//!
 //! [ZeroConf/mDNS]: https://en.wikipedia.org/wiki/Zero-configuration_networking
 //! [Bonjour]: https://en.wikipedia.org/wiki/Bonjour_(software)
 //! [Avahi]: https://en.wikipedia.org/wiki/Avahi_(software)
 //! [`MdnsService`]: type.MdnsService.html
 //! [`MdnsBrowser`]: type.MdnsBrowser.html
 //! [`Any`]: https://doc.rust-lang.org/std/any/trait.Any.html
+//! [`ControlFlow`]: https://doc.rust-lang.org/std/ops/enum.ControlFlow.html
+//! [`EventLoop`]: type.EventLoop.html
+//! [`TMdnsService`]: service/trait.TMdnsService.html
+//! [`TMdnsBrowser`]: browser/trait.TMdnsBrowser.html
+//! [`mock`]: mock/index.html
 
 #![allow(clippy::needless_doctest_main)]
 #[macro_use]
@@ -140,8 +195,12 @@ mod service_type;
 mod tests;
 
 pub mod browser;
+pub mod daemon;
 pub mod error;
 pub mod event_loop;
+pub mod host;
+#[cfg(feature = "mock")]
+pub mod mock;
 pub mod prelude;
 pub mod service;
 pub mod txt_record;
@@ -151,9 +210,14 @@ pub mod linux;
 #[cfg(target_vendor = "apple")]
 pub mod macos;
 
-pub use browser::{ServiceDiscoveredCallback, ServiceDiscovery};
+pub use browser::{
+    InstanceStats, ResolvedRecord, ServiceDiscoveredCallback, ServiceDiscovery, ServiceEvent,
+    ServiceIter, ServiceRemoval, ServiceRemovedCallback,
+};
+pub use daemon::{daemon_info, DaemonInfo};
+pub use host::resolve_host;
 pub use interface::*;
-pub use service::{ServiceRegisteredCallback, ServiceRegistration};
+pub use service::{PublishFlags, ServiceInfo, ServiceRegisteredCallback, ServiceRegistration};
 pub use service_type::*;
 
 /// Type alias for the platform-specific mDNS browser implementation
@@ -163,6 +227,15 @@ pub type MdnsBrowser = linux::browser::AvahiMdnsBrowser;
 #[cfg(target_vendor = "apple")]
 pub type MdnsBrowser = macos::browser::BonjourMdnsBrowser;
 
+/// Type alias for the platform-specific handle of a resolve started with
+/// `MdnsBrowser::resolve_start()`
+#[cfg(target_os = "linux")]
+pub type ResolveHandle = linux::browser::AvahiResolveHandle;
+/// Type alias for the platform-specific handle of a resolve started with
+/// `MdnsBrowser::resolve_start()`
+#[cfg(target_vendor = "apple")]
+pub type ResolveHandle = macos::browser::BonjourResolveHandle;
+
 /// Type alias for the platform-specific mDNS service implementation
 #[cfg(target_os = "linux")]
 pub type MdnsService = linux::service::AvahiMdnsService;
@@ -1,9 +1,22 @@
 //! Utilities related to Avahi
 
-use crate::NetworkInterface;
-use avahi_sys::{avahi_address_snprint, avahi_strerror, AvahiAddress};
-use libc::c_char;
-use std::ffi::CStr;
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::host_resolver::{ManagedAvahiHostNameResolver, ManagedAvahiHostNameResolverParams};
+use super::poll::ManagedAvahiSimplePoll;
+use crate::error::{Error, ErrorKind};
+use crate::ffi::{AsRaw, FromRaw};
+use crate::prelude::*;
+use crate::{AddressFamily, NetworkInterface, Result};
+use avahi_sys::{
+    avahi_address_snprint, avahi_strerror, AvahiAddress, AvahiAddress__bindgen_ty_1, AvahiClient,
+    AvahiClientFlags, AvahiHostNameResolver, AvahiIPv4Address, AvahiIPv6Address, AvahiIfIndex,
+    AvahiLookupResultFlags, AvahiProtocol, AvahiResolverEvent,
+};
+use libc::{c_char, c_int, c_void, ifaddrs, sockaddr_in, sockaddr_in6};
+use std::ffi::{CStr, CString};
+use std::net::IpAddr;
+use std::ptr;
+use std::time::{Duration, Instant};
 
 /// Converts the specified `*const AvahiAddress` to a `String`.
 ///
@@ -37,6 +50,42 @@ pub fn get_error<'a>(code: i32) -> &'a str {
     }
 }
 
+/// Returns an `Error` for the failure of the specified `client`, carrying the code returned by
+/// `avahi_client_errno()`.
+///
+/// # Safety
+/// This function is unsafe because of the internal Avahi call.
+pub unsafe fn client_error(client: *mut AvahiClient) -> Error {
+    let code = avahi_sys::avahi_client_errno(client);
+    Error::from(format!("client failure: `{}`", get_error(code))).with_raw_code(code)
+}
+
+/// Returns an `Error` for the failure of the specified `client` like `client_error()`, but with
+/// `ErrorKind::Transient`, for one-shot operations that may be retried once the daemon is back.
+///
+/// # Safety
+/// This function is unsafe because of the internal Avahi call.
+pub unsafe fn transient_client_error(client: *mut AvahiClient) -> Error {
+    let code = avahi_sys::avahi_client_errno(client);
+    Error::with_kind(
+        ErrorKind::Transient,
+        format!("client failure: `{}`", get_error(code)),
+    )
+    .with_raw_code(code)
+}
+
+/// Returns the alternative for the specified service `name` that Avahi recommends after a name
+/// collision, e.g. `"My Service #2"` for `"My Service"`.
+pub fn alternative_service_name(name: &CStr) -> CString {
+    unsafe {
+        let alt = avahi_sys::avahi_alternative_service_name(name.as_ptr());
+        assert_not_null!(alt);
+        let result = CStr::from_ptr(alt).to_owned();
+        avahi_sys::avahi_free(alt as *mut c_void);
+        result
+    }
+}
+
 /// Converts the specified [`NetworkInterface`] to the Avahi expected value.
 ///
 /// [`NetworkInterface`]: ../../enum.NetworkInterface.html
@@ -47,13 +96,201 @@ pub fn interface_index(interface: NetworkInterface) -> i32 {
     }
 }
 
+/// Converts the specified [`AddressFamily`] to the Avahi expected value.
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn address_protocol(family: AddressFamily) -> AvahiProtocol {
+    match family {
+        AddressFamily::Any => avahi_sys::AVAHI_PROTO_UNSPEC,
+        AddressFamily::V4 => avahi_sys::AVAHI_PROTO_INET,
+        AddressFamily::V6 => avahi_sys::AVAHI_PROTO_INET6,
+    }
+}
+
+/// Returns the Avahi interface index of the machine's loopback interface.
+pub fn loopback_interface_index() -> Result<AvahiIfIndex> {
+    crate::list_interfaces()?
+        .iter()
+        .find(|i| i.is_loopback())
+        .map(|i| *i.index() as AvahiIfIndex)
+        .ok_or_else(|| "could not find loopback interface".into())
+}
+
+/// Returns the addresses assigned to the specified interface, along with the index of the
+/// interface they belong to.
+///
+/// If `interface` is `AVAHI_IF_UNSPEC`, addresses of every interface that is up are returned,
+/// except those on loopback interfaces.
+pub fn local_addresses(interface: AvahiIfIndex) -> Result<Vec<(AvahiIfIndex, AvahiAddress)>> {
+    let mut addrs: *mut ifaddrs = ptr::null_mut();
+
+    if unsafe { libc::getifaddrs(&mut addrs) } < 0 {
+        return Err("getifaddrs(): returned error status".into());
+    }
+
+    let mut addresses = vec![];
+    let mut cur = addrs;
+
+    while !cur.is_null() {
+        let ifa = unsafe { &*cur };
+        cur = ifa.ifa_next;
+
+        let flags = ifa.ifa_flags as c_int;
+        let index = unsafe { libc::if_nametoindex(ifa.ifa_name) } as AvahiIfIndex;
+
+        let is_included = if interface == avahi_sys::AVAHI_IF_UNSPEC {
+            flags & libc::IFF_UP != 0 && flags & libc::IFF_LOOPBACK == 0
+        } else {
+            index == interface
+        };
+
+        if ifa.ifa_addr.is_null() || !is_included {
+            continue;
+        }
+
+        let address = match unsafe { (*ifa.ifa_addr).sa_family } as c_int {
+            libc::AF_INET => AvahiAddress {
+                proto: avahi_sys::AVAHI_PROTO_INET,
+                data: AvahiAddress__bindgen_ty_1 {
+                    ipv4: AvahiIPv4Address {
+                        address: unsafe { (*(ifa.ifa_addr as *const sockaddr_in)).sin_addr.s_addr },
+                    },
+                },
+            },
+            libc::AF_INET6 => AvahiAddress {
+                proto: avahi_sys::AVAHI_PROTO_INET6,
+                data: AvahiAddress__bindgen_ty_1 {
+                    ipv6: AvahiIPv6Address {
+                        address: unsafe {
+                            (*(ifa.ifa_addr as *const sockaddr_in6)).sin6_addr.s6_addr
+                        },
+                    },
+                },
+            },
+            _ => continue,
+        };
+
+        addresses.push((index, address));
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+
+    Ok(addresses)
+}
+
+/// Connects to the Avahi daemon to query its version string.
+pub fn daemon_version() -> Result<Option<String>> {
+    let poll = ManagedAvahiSimplePoll::new()?;
+
+    let client = ManagedAvahiClient::new(
+        ManagedAvahiClientParams::builder()
+            .poll(&poll)
+            .flags(AvahiClientFlags(0))
+            .callback(None)
+            .userdata(ptr::null_mut())
+            .build()?,
+    )?;
+
+    Ok(client.version_string().ok().map(String::from))
+}
+
+/// Resolves `host` to its IPv4 and IPv6 addresses, blocking until both have been resolved or
+/// `timeout` has elapsed.
+///
+/// An `AvahiHostNameResolver` reports a single address, so one resolver is created per address
+/// family. Names outside `local` are looked up with `AVAHI_LOOKUP_USE_WIDE_AREA`. Addresses
+/// resolved before `timeout` elapses are returned rather than an error.
+pub fn resolve_host(host: &str, timeout: Duration) -> Result<Vec<IpAddr>> {
+    let deadline = Instant::now() + timeout;
+    let poll = ManagedAvahiSimplePoll::new()?;
+
+    let client = ManagedAvahiClient::new(
+        ManagedAvahiClientParams::builder()
+            .poll(&poll)
+            .flags(AvahiClientFlags(0))
+            .callback(None)
+            .userdata(ptr::null_mut())
+            .build()?,
+    )?;
+
+    let name = c_string!(host);
+    let flags = if crate::host::is_in_domain(host, "local") {
+        0
+    } else {
+        avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_WIDE_AREA
+    };
+
+    let mut context = Box::new(HostResolverContext::default());
+
+    let mut resolvers = vec![];
+    for aprotocol in [avahi_sys::AVAHI_PROTO_INET, avahi_sys::AVAHI_PROTO_INET6] {
+        resolvers.push(ManagedAvahiHostNameResolver::new(
+            ManagedAvahiHostNameResolverParams::builder()
+                .client(&client)
+                .interface(avahi_sys::AVAHI_IF_UNSPEC)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .name(name.as_ptr())
+                .aprotocol(aprotocol)
+                .flags(flags)
+                .callback(Some(host_name_resolver_callback))
+                .userdata(context.as_raw())
+                .build()?,
+        )?);
+    }
+
+    // `finished` is incremented by the callback, through the pointer passed as userdata
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if context.finished == resolvers.len() || remaining == Duration::from_secs(0) {
+            break;
+        }
+
+        poll.iterate(remaining.as_millis().min(i32::MAX as u128) as i32);
+    }
+
+    if !context.addresses.is_empty() {
+        Ok(context.addresses)
+    } else if context.finished == resolvers.len() {
+        Err(format!("could not resolve host `{}`", host).into())
+    } else {
+        Err(Error::with_kind(
+            ErrorKind::TimedOut,
+            format!("timed out resolving host `{}`", host),
+        ))
+    }
+}
+
+#[derive(Default, FromRaw, AsRaw)]
+struct HostResolverContext {
+    addresses: Vec<IpAddr>,
+    finished: usize,
+}
+
+unsafe extern "C" fn host_name_resolver_callback(
+    _resolver: *mut AvahiHostNameResolver,
+    _interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiResolverEvent,
+    _name: *const c_char,
+    addr: *const AvahiAddress,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let context = HostResolverContext::from_raw(userdata);
+
+    if event == avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FOUND {
+        if let Ok(address) = avahi_address_to_string(addr).parse() {
+            context.addresses.push(address);
+        }
+    }
+
+    context.finished += 1;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use avahi_sys::{
-        AvahiAddress__bindgen_ty_1, AvahiIPv4Address, AvahiIPv6Address, AVAHI_PROTO_INET,
-        AVAHI_PROTO_INET6,
-    };
+    use avahi_sys::{AVAHI_PROTO_INET, AVAHI_PROTO_INET6};
 
     #[test]
     fn get_error_returns_valid_error_string() {
@@ -97,4 +334,33 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn address_protocol_maps_address_family() {
+        assert_eq!(
+            address_protocol(AddressFamily::Any),
+            avahi_sys::AVAHI_PROTO_UNSPEC
+        );
+        assert_eq!(address_protocol(AddressFamily::V4), AVAHI_PROTO_INET);
+        assert_eq!(address_protocol(AddressFamily::V6), AVAHI_PROTO_INET6);
+    }
+
+    #[test]
+    fn local_addresses_on_loopback_only_returns_loopback() {
+        let loopback = loopback_interface_index().unwrap();
+
+        let addresses = local_addresses(loopback).unwrap();
+        assert!(addresses.iter().all(|(i, _)| *i == loopback));
+
+        let addresses: Vec<String> = addresses
+            .iter()
+            .map(|(_, a)| unsafe { avahi_address_to_string(a) })
+            .collect();
+        assert!(addresses.contains(&"127.0.0.1".to_string()));
+
+        assert!(local_addresses(avahi_sys::AVAHI_IF_UNSPEC)
+            .unwrap()
+            .iter()
+            .all(|(i, _)| *i != loopback));
+    }
 }
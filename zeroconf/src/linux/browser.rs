@@ -2,6 +2,7 @@
 
 use super::avahi_util;
 use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::domain_browser::{ManagedAvahiDomainBrowser, ManagedAvahiDomainBrowserParams};
 use super::poll::ManagedAvahiSimplePoll;
 use super::raw_browser::{ManagedAvahiServiceBrowser, ManagedAvahiServiceBrowserParams};
 use super::{
@@ -10,49 +11,121 @@ use super::{
     },
     string_list::ManagedAvahiStringList,
 };
-use crate::ffi::{c_str, AsRaw, FromRaw};
+use crate::browser::{self, InstanceStatsTable, TResolveHandle};
+use crate::error::{Error, ErrorKind};
+use crate::ffi::{c_str, AsRaw, FromRaw, UnwrapOrNull};
 use crate::prelude::*;
+use crate::txt_record;
 use crate::Result;
 use crate::{
-    EventLoop, NetworkInterface, ServiceDiscoveredCallback, ServiceDiscovery, ServiceType,
-    TxtRecord,
+    AddressFamily, EventLoop, InstanceStats, NetworkInterface, ResolvedRecord,
+    ServiceDiscoveredCallback, ServiceDiscovery, ServiceEvent, ServiceIter, ServiceRemoval,
+    ServiceRemovedCallback, ServiceType, TxtRecord,
 };
 use avahi_sys::{
-    AvahiAddress, AvahiBrowserEvent, AvahiClient, AvahiClientFlags, AvahiClientState, AvahiIfIndex,
-    AvahiLookupResultFlags, AvahiProtocol, AvahiResolverEvent, AvahiServiceBrowser,
-    AvahiServiceResolver, AvahiStringList,
+    AvahiAddress, AvahiBrowserEvent, AvahiClient, AvahiClientFlags, AvahiClientState,
+    AvahiDomainBrowser, AvahiIfIndex, AvahiLookupFlags, AvahiLookupResultFlags, AvahiProtocol,
+    AvahiResolverEvent, AvahiServiceBrowser, AvahiServiceResolver, AvahiStringList,
 };
 use libc::{c_char, c_void};
 use std::any::Any;
-use std::ffi::CString;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CStr, CString};
+use std::net::IpAddr;
+use std::ops::ControlFlow;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{fmt, ptr};
 
 #[derive(Debug)]
 pub struct AvahiMdnsBrowser {
     client: Option<Arc<ManagedAvahiClient>>,
     poll: Option<Arc<ManagedAvahiSimplePoll>>,
-    browser: Option<ManagedAvahiServiceBrowser>,
-    kind: CString,
-    interface_index: AvahiIfIndex,
+    no_fail: bool,
+    paused: bool,
     context: *mut AvahiBrowserContext,
 }
 
 impl TMdnsBrowser for AvahiMdnsBrowser {
+    type EventLoop<'a> = EventLoop<'a>;
+    type ResolveHandle = AvahiResolveHandle;
+
     fn new(service_type: ServiceType) -> Self {
-        Self {
+        let domain = service_type.explicit_domain().map(|d| d.to_string());
+
+        let mut context = AvahiBrowserContext::default();
+        context.kind = c_string!(service_type.as_registration_string());
+
+        let mut browser = Self {
             client: None,
             poll: None,
-            browser: None,
-            kind: c_string!(service_type.to_string()),
-            context: Box::into_raw(Box::default()),
-            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            context: Box::into_raw(Box::new(context)),
+            no_fail: false,
+            paused: false,
+        };
+
+        if let Some(domain) = domain {
+            browser.set_domains(vec![domain]);
         }
+
+        browser
     }
 
     fn set_network_interface(&mut self, interface: NetworkInterface) {
-        self.interface_index = avahi_util::interface_index(interface);
+        unsafe { (*self.context).interface_index = avahi_util::interface_index(interface) };
+    }
+
+    fn set_ignore_local(&mut self, ignore_local: bool) {
+        unsafe { (*self.context).ignore_local = ignore_local };
+    }
+
+    fn set_local_only(&mut self, local_only: bool) {
+        unsafe { (*self.context).local_only = local_only };
+    }
+
+    fn set_address_family(&mut self, family: AddressFamily) {
+        unsafe { (*self.context).address_protocol = avahi_util::address_protocol(family) };
+    }
+
+    fn set_wide_area(&mut self, domain: &str) {
+        unsafe {
+            (*self.context).domains = vec![c_string!(domain)];
+            (*self.context).wide_area = true;
+        };
+    }
+
+    fn set_force_multicast(&mut self, force_multicast: bool) {
+        unsafe { (*self.context).force_multicast = force_multicast };
+    }
+
+    fn set_resolve_address(&mut self, resolve_address: bool) {
+        unsafe { (*self.context).resolve_address = resolve_address };
+    }
+
+    fn set_domains(&mut self, domains: Vec<String>) {
+        unsafe {
+            (*self.context).domains = domains.iter().map(|d| c_string!(d.as_str())).collect();
+            (*self.context).wide_area = false;
+        }
+    }
+
+    fn set_max_duration(&mut self, max_duration: Duration) {
+        unsafe { (*self.context).max_duration = Some(max_duration) };
+    }
+
+    fn set_max_concurrent_resolves(&mut self, max: usize) {
+        unsafe { (*self.context).max_concurrent_resolves = Some(max.max(1)) };
+    }
+
+    fn set_name_prefix(&mut self, prefix: &str) {
+        let prefix = Some(prefix.to_string()).filter(|p| !p.is_empty());
+        unsafe { (*self.context).name_prefix = prefix };
+    }
+
+    fn set_refresh_interval(&mut self, interval: Duration) {
+        unsafe { (*self.context).refresh_interval = Some(interval) };
     }
 
     fn set_service_discovered_callback(
@@ -62,6 +135,13 @@ impl TMdnsBrowser for AvahiMdnsBrowser {
         unsafe { (*self.context).service_discovered_callback = Some(service_discovered_callback) };
     }
 
+    fn set_service_removed_callback(
+        &mut self,
+        service_removed_callback: Box<ServiceRemovedCallback>,
+    ) {
+        unsafe { (*self.context).service_removed_callback = Some(service_removed_callback) };
+    }
+
     fn set_context(&mut self, context: Box<dyn Any>) {
         unsafe { (*self.context).user_context = Some(Arc::from(context)) };
     }
@@ -69,80 +149,648 @@ impl TMdnsBrowser for AvahiMdnsBrowser {
     fn browse_services(&mut self) -> Result<EventLoop> {
         debug!("Browsing services: {:?}", self);
 
+        unsafe {
+            let context = &mut *self.context;
+            browser::check_force_multicast(context.wide_area, context.force_multicast)?;
+            context.stopped.store(false, Ordering::SeqCst);
+            context.expires_at = context.max_duration.map(|d| Instant::now() + d);
+            context.expired = false;
+            context.next_refresh_at = None;
+        }
+
         self.poll = Some(Arc::new(ManagedAvahiSimplePoll::new()?));
 
+        let flags = if self.no_fail {
+            AvahiClientFlags::AVAHI_CLIENT_NO_FAIL
+        } else {
+            AvahiClientFlags(0)
+        };
+
         self.client = Some(Arc::new(ManagedAvahiClient::new(
             ManagedAvahiClientParams::builder()
                 .poll(self.poll.as_ref().unwrap())
-                .flags(AvahiClientFlags(0))
-                .callback(Some(client_callback))
-                .userdata(ptr::null_mut())
+                .flags(flags)
+                .callback(Some(browser_client_callback))
+                .userdata(self.context as *mut c_void)
                 .build()?,
         )?));
 
+        unsafe { (*self.context).client = self.client.clone() };
+
+        self.paused = false;
+        unsafe { (*self.context).start_browsers()? };
+
+        Ok(self.event_loop(self.poll.as_ref().unwrap()))
+    }
+
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        match &self.poll {
+            Some(poll) if unsafe { (*self.context).is_browsing() } => {
+                self.event_loop(poll).poll(timeout)
+            }
+            _ => Err("browser is not browsing".into()),
+        }
+    }
+
+    /// Frees the underlying `AvahiServiceBrowser` along with any pending resolvers, keeping the
+    /// `AvahiClient` connected.
+    fn pause(&mut self) {
+        let context = unsafe { &mut *self.context };
+
+        if context.is_browsing() {
+            context.browsers.clear();
+            context.pending_start = false;
+            context.resolvers = ServiceResolverSet::default();
+            context.queued_resolves.clear();
+            self.paused = true;
+        }
+    }
+
+    /// Creates a new `AvahiServiceBrowser` on the existing `AvahiClient`, which reports every
+    /// service in the daemon's cache again.
+    fn resume(&mut self) -> Result<EventLoop<'_>> {
+        if !self.paused {
+            return Err("browser is not paused".into());
+        }
+
         unsafe {
-            (*self.context).client = self.client.clone();
+            (*self.context).stopped.store(false, Ordering::SeqCst);
+            (*self.context).next_refresh_at = None;
+            (*self.context).start_browsers()?;
+        }
 
-            self.browser = Some(ManagedAvahiServiceBrowser::new(
-                ManagedAvahiServiceBrowserParams::builder()
-                    .client((*self.context).client.as_ref().unwrap())
-                    .interface(self.interface_index)
-                    .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
-                    .kind(self.kind.as_ptr())
-                    .domain(ptr::null_mut())
-                    .flags(0)
-                    .callback(Some(browse_callback))
-                    .userdata(self.context as *mut c_void)
-                    .build()?,
-            )?);
+        self.paused = false;
+
+        Ok(self.event_loop(self.poll.as_ref().unwrap()))
+    }
+
+    /// Frees the underlying `AvahiServiceBrowser` objects before disconnecting the `AvahiClient`.
+    fn close(&mut self) -> Result<()> {
+        let context = unsafe { &mut *self.context };
+
+        context.stopped.store(true, Ordering::SeqCst);
+        context.browsers.clear();
+        context.pending_start = false;
+        context.resolvers = ServiceResolverSet::default();
+        context.queued_resolves.clear();
+        context.services.clear();
+        context.resolved_addresses.clear();
+        context.stats.clear();
+        context.client = None;
+
+        self.client = None;
+        self.poll = None;
+        self.paused = false;
+
+        Ok(())
+    }
+
+    /// Re-resolves the previously discovered service with the specified `name`.
+    ///
+    /// Avahi provides no way for clients to evict a single entry from the daemon's cache, so a
+    /// new `AvahiServiceResolver` is created for every interface, protocol and domain the
+    /// instance was found on instead. The resolved services are delivered once the `EventLoop`
+    /// is polled.
+    fn refresh(&mut self, name: &str) -> Result<()> {
+        let context = unsafe { &mut *self.context };
+
+        let services = context
+            .services
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no discovered service named `{}`", name))?;
+
+        for service in services {
+            resolve(
+                context,
+                service.interface,
+                service.protocol,
+                service.name.as_ptr(),
+                service.kind.as_ptr(),
+                service.domain.as_ptr(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn instance_stats(&self, name: &str) -> Option<InstanceStats> {
+        unsafe { (*self.context).stats.get(name) }
+    }
+
+    /// Resolves a single service instance in the default domain.
+    ///
+    /// Avahi resolves the SRV/TXT records and the host's address in a single operation, so
+    /// `Err` is returned if that does not complete within `timeout`.
+    fn resolve(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+    ) -> Result<ServiceDiscovery> {
+        browser::resolve_blocking::<Self>(name, service_type, timeout)
+    }
+
+    /// Creates an `AvahiServiceResolver` on a dedicated `AvahiClient`, both of which are freed
+    /// once the resolve completes or is cancelled. If the client fails meanwhile, e.g. because
+    /// the daemon restarts, the handle's `poll()` returns `Err` with `ErrorKind::Transient`.
+    fn resolve_start(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+        mut callback: Box<ServiceDiscoveredCallback>,
+    ) -> Result<AvahiResolveHandle> {
+        let deadline = Instant::now() + timeout;
+        let poll = ManagedAvahiSimplePoll::new()?;
+        let mut failure: Box<Option<Error>> = Box::default();
+
+        let client = Arc::new(ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(&poll)
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(&mut *failure as *mut Option<Error> as *mut c_void)
+                .build()?,
+        )?);
+
+        let mut context = Box::new(AvahiBrowserContext::default());
+        context.client = Some(client);
+        // the resolve is complete once the callback has been invoked
+        context.service_discovered_callback = Some(Box::new(move |r, c| {
+            let _ = callback(r, c);
+            ControlFlow::Break(())
+        }));
+
+        let c_name = c_string!(name);
+        let kind = c_string!(service_type.as_registration_string());
+        let domain = service_type.explicit_domain().map(|d| c_string!(d));
+
+        resolve(
+            &mut context,
+            avahi_sys::AVAHI_IF_UNSPEC,
+            avahi_sys::AVAHI_PROTO_UNSPEC,
+            c_name.as_ptr(),
+            kind.as_ptr(),
+            domain.as_ref().map(|d| d.as_ptr()).unwrap_or_null(),
+        )?;
+
+        Ok(AvahiResolveHandle {
+            context: Some(context),
+            failure,
+            poll,
+            deadline,
+            name: name.to_string(),
+        })
+    }
+
+    fn browse_domains(timeout: Duration) -> Result<Vec<String>> {
+        let deadline = Instant::now() + timeout;
+        let poll = ManagedAvahiSimplePoll::new()?;
+        let mut context = Box::new(AvahiDomainBrowserContext::default());
+
+        let client = ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(&poll)
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(&mut context.error as *mut Option<Error> as *mut c_void)
+                .build()?,
+        )?;
+
+        let _browser = ManagedAvahiDomainBrowser::new(
+            ManagedAvahiDomainBrowserParams::builder()
+                .client(&client)
+                .interface(avahi_sys::AVAHI_IF_UNSPEC)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .domain(ptr::null())
+                .kind(avahi_sys::AvahiDomainBrowserType_AVAHI_DOMAIN_BROWSER_BROWSE)
+                .flags(0)
+                .callback(Some(domain_browse_callback))
+                .userdata(context.as_raw())
+                .build()?,
+        )?;
+
+        // `done` and `error` are set by the callbacks, through the pointers passed as userdata
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if context.done || context.error.is_some() || remaining == Duration::from_secs(0) {
+                break;
+            }
+
+            poll.iterate(remaining.as_millis().min(i32::MAX as u128) as i32);
+        }
+
+        match context.error.take() {
+            Some(e) => Err(e),
+            None => Ok(context.domains),
         }
+    }
+}
 
-        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+impl AvahiMdnsBrowser {
+    /// If `true`, `browse_services()` succeeds even if the Avahi daemon is not running yet.
+    /// Browsing then starts once the daemon becomes available, so the callback is not invoked
+    /// until it has, e.g. when an application starts before `avahi-daemon` during boot.
+    ///
+    /// This creates the client with `AVAHI_CLIENT_NO_FAIL`. If the daemon goes away after the
+    /// client has connected, browsing stops until it is back and then starts over, so services
+    /// that are still present are discovered again. Defaults to `false`.
+    pub fn set_no_fail(&mut self, no_fail: bool) {
+        self.no_fail = no_fail;
+    }
+
+    /// Returns an `EventLoop` on `poll` that also ends the browse once the maximum duration has
+    /// elapsed, and refreshes discovered services at the refresh interval.
+    fn event_loop(&self, poll: &Arc<ManagedAvahiSimplePoll>) -> EventLoop<'_> {
+        let context = self.context;
+        EventLoop::new(poll.clone(), unsafe { (*context).stopped.clone() }).with_after_poll(
+            move || unsafe {
+                (*context).expire_if_due();
+                (*context).refresh_if_due();
+            },
+        )
+    }
+}
+
+/// Avahi implementation of [`TResolveHandle`], returned by `AvahiMdnsBrowser::resolve_start()`.
+///
+/// [`TResolveHandle`]: ../../browser/trait.TResolveHandle.html
+#[derive(Debug)]
+pub struct AvahiResolveHandle {
+    // the resolver and client must be freed before the poll and the failure they report to
+    context: Option<Box<AvahiBrowserContext>>,
+    failure: Box<Option<Error>>,
+    poll: ManagedAvahiSimplePoll,
+    deadline: Instant,
+    name: String,
+}
+
+impl TResolveHandle for AvahiResolveHandle {
+    fn poll(&mut self, timeout: Duration) -> Result<()> {
+        let context = match &mut self.context {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            context.deliver(Err(Error::with_kind(
+                ErrorKind::TimedOut,
+                format!("timed out resolving service `{}`", self.name),
+            )));
+        } else {
+            let sleep_time = timeout.min(remaining).as_millis().min(i32::MAX as u128);
+            self.poll.iterate(sleep_time as i32);
+        }
+
+        if let Some(e) = self.failure.take() {
+            self.context = None;
+            return Err(e);
+        }
+
+        if context.stopped.load(Ordering::SeqCst) {
+            self.context = None;
+        }
+
+        Ok(())
+    }
+
+    /// Frees the `AvahiServiceResolver` and disconnects its `AvahiClient`.
+    fn cancel(&mut self) {
+        self.context = None;
+    }
+
+    fn is_done(&self) -> bool {
+        self.context.is_none()
+    }
+}
+
+impl IntoIterator for AvahiMdnsBrowser {
+    type Item = Result<ServiceEvent>;
+    type IntoIter = ServiceIter<Self>;
+
+    /// Starts browsing and returns a blocking iterator over the discovered services.
+    fn into_iter(self) -> ServiceIter<Self> {
+        ServiceIter::new(self)
     }
 }
 
 impl Drop for AvahiMdnsBrowser {
+    /// Frees the browsers, the resolvers and the client before the context passed to their
+    /// callbacks, so that no callback can observe a freed context.
     fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
-        // browser must be freed first
-        self.browser = None;
+        unsafe {
+            let context = &mut *self.context;
+            context.browsers.clear();
+            context.queued_resolves.clear();
+            context.resolvers = ServiceResolverSet::default();
+            context.client = None;
+
+            self.client = None;
+            self.poll = None;
+
+            drop(Box::from_raw(self.context));
+        }
     }
 }
 
 #[derive(FromRaw, AsRaw)]
 struct AvahiBrowserContext {
+    // the browsers must be freed before the client
+    browsers: Vec<ManagedAvahiServiceBrowser>,
     client: Option<Arc<ManagedAvahiClient>>,
+    kind: CString,
+    interface_index: AvahiIfIndex,
+    domains: Vec<CString>,
+    pending_start: bool,
     resolvers: ServiceResolverSet,
+    max_concurrent_resolves: Option<usize>,
+    queued_resolves: VecDeque<BrowsedService>,
+    name_prefix: Option<String>,
+    stats: InstanceStatsTable,
+    // Avahi reports an instance once for every interface, protocol and domain it is found on
+    services: HashMap<String, Vec<BrowsedService>>,
+    // the address each copy of a service was last resolved to
+    resolved_addresses: HashMap<BrowsedService, IpAddr>,
+    wide_area: bool,
+    force_multicast: bool,
+    local_only: bool,
+    address_protocol: AvahiProtocol,
+    resolve_address: bool,
+    ignore_local: bool,
+    max_duration: Option<Duration>,
+    expires_at: Option<Instant>,
+    expired: bool,
+    refresh_interval: Option<Duration>,
+    next_refresh_at: Option<Instant>,
     service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    service_removed_callback: Option<Box<ServiceRemovedCallback>>,
     user_context: Option<Arc<dyn Any>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for AvahiBrowserContext {
+    /// Frees the browsers and resolvers before the client, since freeing the client also frees
+    /// everything that was created on it.
+    fn drop(&mut self) {
+        self.browsers.clear();
+        self.queued_resolves.clear();
+        self.resolvers = ServiceResolverSet::default();
+        self.client = None;
+    }
 }
 
 impl AvahiBrowserContext {
-    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
-        if let Some(f) = &self.service_discovered_callback {
-            f(result, self.user_context.clone());
+    /// Returns the flags browsers and resolvers are created with for the configured domain
+    /// settings.
+    fn lookup_flags(&self) -> AvahiLookupFlags {
+        if self.wide_area {
+            avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_WIDE_AREA
+        } else if self.force_multicast {
+            avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_MULTICAST
+        } else {
+            0
+        }
+    }
+
+    /// Creates one `AvahiServiceBrowser` per configured domain, or a single one for the default
+    /// domain if none are configured.
+    ///
+    /// If the client has not connected to the daemon yet, which only happens with
+    /// `set_no_fail()`, the browsers are created by the client callback once it has.
+    unsafe fn start_browsers(&mut self) -> Result<()> {
+        self.browsers.clear();
+
+        let client = self.client.clone().unwrap();
+        if client.state() != avahi_sys::AvahiClientState_AVAHI_CLIENT_S_RUNNING {
+            debug!("Waiting for the Avahi daemon to start browsing");
+            self.pending_start = true;
+            return Ok(());
+        }
+
+        self.pending_start = false;
+
+        let domains = if self.domains.is_empty() {
+            vec![ptr::null()]
+        } else {
+            self.domains.iter().map(|d| d.as_ptr()).collect()
+        };
+
+        for domain in domains {
+            let browser = ManagedAvahiServiceBrowser::new(
+                ManagedAvahiServiceBrowserParams::builder()
+                    .client(&client)
+                    .interface(self.interface_index)
+                    .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                    .kind(self.kind.as_ptr())
+                    .domain(domain)
+                    .flags(self.lookup_flags())
+                    .callback(Some(browse_callback))
+                    .userdata(self.as_raw())
+                    .build()?,
+            )?;
+
+            self.browsers.push(browser);
+        }
+
+        Ok(())
+    }
+
+    fn is_browsing(&self) -> bool {
+        !self.browsers.is_empty() || self.pending_start
+    }
+
+    fn invoke_callback(&mut self, result: Result<ServiceDiscovery>) {
+        if let Ok(service) = &result {
+            self.stats.record_resolved(service.name());
+        }
+
+        if self.ignore_local && matches!(&result, Ok(s) if s.is_registered_by_this_process()) {
+            debug!("Ignoring service registered by this process");
+            return;
+        }
+
+        if !self.expire_if_due() {
+            self.deliver(result);
+        }
+    }
+
+    /// Ends the browse if the maximum duration has elapsed, notifying the callback the first
+    /// time. Returns `true` if the browse has ended.
+    fn expire_if_due(&mut self) -> bool {
+        if !self.expired && matches!(self.expires_at, Some(at) if Instant::now() >= at) {
+            self.expired = true;
+            self.deliver(Err(Error::with_kind(
+                ErrorKind::TimedOut,
+                "browser reached its maximum duration",
+            )));
+        }
+
+        self.expired
+    }
+
+    /// Resolves every discovered service again if the refresh interval has elapsed since the
+    /// previous refresh, or since browsing started.
+    fn refresh_if_due(&mut self) {
+        let interval = match self.refresh_interval {
+            Some(interval) if self.is_browsing() && !self.expired => interval,
+            _ => return,
+        };
+
+        let now = Instant::now();
+        match self.next_refresh_at {
+            Some(at) if now < at => return,
+            Some(_) => {}
+            None => {
+                self.next_refresh_at = Some(now + interval);
+                return;
+            }
+        }
+
+        self.next_refresh_at = Some(now + interval);
+
+        let services: Vec<BrowsedService> = self.services.values().flatten().cloned().collect();
+        for service in services {
+            let result = resolve(
+                self,
+                service.interface,
+                service.protocol,
+                service.name.as_ptr(),
+                service.kind.as_ptr(),
+                service.domain.as_ptr(),
+            );
+
+            if let Err(e) = result {
+                self.invoke_callback(Err(e));
+            }
+        }
+    }
+
+    fn deliver(&mut self, result: Result<ServiceDiscovery>) {
+        if let Some(f) = &mut self.service_discovered_callback {
+            if let ControlFlow::Break(()) = f(result, self.user_context.clone()) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
         } else {
             panic!("attempted to invoke browser callback but none was set");
         }
     }
+
+    /// Records a copy of a service reported by a browser. Returns `true` if it is the first copy
+    /// of the instance, i.e. the instance has just been announced.
+    fn add_service(&mut self, service: BrowsedService) -> bool {
+        let name = service.name.to_string_lossy().into_owned();
+        let copies = self.services.entry(name).or_default();
+        let is_new = copies.is_empty();
+
+        if !copies.contains(&service) {
+            copies.push(service);
+        }
+
+        is_new
+    }
+
+    /// Forgets a copy of a service reported as removed by a browser, and reports the removal of
+    /// the instance once its last copy in that domain is gone.
+    fn remove_service(&mut self, service: &BrowsedService) -> Result<()> {
+        // the names were checked to be UTF-8 when the service was added
+        let name = service.name.to_string_lossy();
+
+        let copies = match self.services.get_mut(name.as_ref()) {
+            Some(copies) if copies.contains(service) => copies,
+            _ => return Ok(()),
+        };
+
+        copies.retain(|s| s != service);
+        self.resolved_addresses.remove(service);
+        let in_domain = copies.iter().any(|s| s.domain == service.domain);
+
+        if copies.is_empty() {
+            self.services.remove(name.as_ref());
+            self.stats.record_removed(&name);
+        }
+
+        if in_domain {
+            Ok(())
+        } else {
+            self.invoke_removed(service)
+        }
+    }
+
+    /// Passes the removal of a previously discovered service to the removed callback, if set.
+    fn invoke_removed(&mut self, service: &BrowsedService) -> Result<()> {
+        if self.expire_if_due() {
+            return Ok(());
+        }
+
+        // the names were checked to be UTF-8 when the service was added
+        let removal = ServiceRemoval::builder()
+            .name(service.name.to_string_lossy().into_owned())
+            .service_type(ServiceType::from_str(&service.kind.to_string_lossy())?)
+            .domain(service.domain.to_string_lossy().into_owned())
+            .build()?;
+
+        if let Some(f) = &mut self.service_removed_callback {
+            if let ControlFlow::Break(()) = f(removal, self.user_context.clone()) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for AvahiBrowserContext {
     fn default() -> Self {
         AvahiBrowserContext {
+            browsers: vec![],
             client: None,
+            kind: CString::default(),
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            domains: vec![],
+            pending_start: false,
             resolvers: ServiceResolverSet::default(),
+            max_concurrent_resolves: None,
+            queued_resolves: VecDeque::new(),
+            name_prefix: None,
+            stats: InstanceStatsTable::default(),
+            services: HashMap::new(),
+            resolved_addresses: HashMap::new(),
+            wide_area: false,
+            force_multicast: false,
+            local_only: false,
+            address_protocol: avahi_sys::AVAHI_PROTO_UNSPEC,
+            resolve_address: true,
+            ignore_local: false,
+            max_duration: None,
+            expires_at: None,
+            expired: false,
+            refresh_interval: None,
+            next_refresh_at: None,
             service_discovered_callback: None,
+            service_removed_callback: None,
             user_context: None,
+            stopped: Arc::default(),
         }
     }
 }
 
+/// A service instance reported by the browser on one interface, protocol and domain, kept so it
+/// can be resolved again later.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct BrowsedService {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: CString,
+    kind: CString,
+    domain: CString,
+}
+
 impl fmt::Debug for AvahiBrowserContext {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("AvahiBrowserContext")
             .field("client", &self.client)
             .field("resolvers", &self.resolvers)
+            .field("services", &self.services)
             .finish()
     }
 }
@@ -155,17 +803,51 @@ unsafe extern "C" fn browse_callback(
     name: *const c_char,
     kind: *const c_char,
     domain: *const c_char,
-    _flags: AvahiLookupResultFlags,
+    flags: AvahiLookupResultFlags,
     userdata: *mut c_void,
 ) {
     let context = AvahiBrowserContext::from_raw(userdata);
 
     match event {
         avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => {
+            let is_local = flags & avahi_sys::AvahiLookupResultFlags_AVAHI_LOOKUP_RESULT_LOCAL != 0;
+            if context.expire_if_due() || (context.local_only && !is_local) {
+                return;
+            }
+
+            let instance = match c_str::try_raw_to_str(name) {
+                Ok(instance) => instance,
+                Err(e) => {
+                    context.invoke_callback(Err(e));
+                    return;
+                }
+            };
+
+            if !browser::matches_name_prefix(instance, context.name_prefix.as_deref()) {
+                debug!(
+                    "Skipping service `{}` not matching the name prefix",
+                    instance
+                );
+                return;
+            }
+
             if let Err(e) = handle_browser_new(context, interface, protocol, name, kind, domain) {
                 context.invoke_callback(Err(e));
             }
         }
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_REMOVE => {
+            // services with malformed names were never added
+            let service = match browsed_service(interface, protocol, name, kind, domain) {
+                Ok(service) => service,
+                Err(_) => return,
+            };
+
+            context.queued_resolves.retain(|s| *s != service);
+
+            if let Err(e) = context.remove_service(&service) {
+                context.invoke_callback(Err(e));
+            }
+        }
         avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {
             context.invoke_callback(Err("browser failure".into()))
         }
@@ -173,7 +855,42 @@ unsafe extern "C" fn browse_callback(
     };
 }
 
-fn handle_browser_new(
+unsafe fn handle_browser_new(
+    context: &mut AvahiBrowserContext,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    kind: *const c_char,
+    domain: *const c_char,
+) -> Result<()> {
+    let service = browsed_service(interface, protocol, name, kind, domain)?;
+
+    if context.add_service(service) {
+        context.stats.record_seen(c_str::try_raw_to_str(name)?);
+    }
+
+    resolve(context, interface, protocol, name, kind, domain)
+}
+
+unsafe fn browsed_service(
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    kind: *const c_char,
+    domain: *const c_char,
+) -> Result<BrowsedService> {
+    Ok(BrowsedService {
+        interface,
+        protocol,
+        name: c_string!(c_str::try_raw_to_str(name)?),
+        kind: c_string!(c_str::try_raw_to_str(kind)?),
+        domain: c_string!(c_str::try_raw_to_str(domain)?),
+    })
+}
+
+/// Starts resolving the specified service, or queues it if the maximum number of concurrent
+/// resolves has been reached.
+fn resolve(
     context: &mut AvahiBrowserContext,
     interface: AvahiIfIndex,
     protocol: AvahiProtocol,
@@ -181,6 +898,14 @@ fn handle_browser_new(
     kind: *const c_char,
     domain: *const c_char,
 ) -> Result<()> {
+    if matches!(context.max_concurrent_resolves, Some(max) if context.resolvers.len() >= max) {
+        context
+            .queued_resolves
+            .push_back(unsafe { browsed_service(interface, protocol, name, kind, domain)? });
+
+        return Ok(());
+    }
+
     let raw_context = context.as_raw();
     context.resolvers.insert(ManagedAvahiServiceResolver::new(
         ManagedAvahiServiceResolverParams::builder()
@@ -190,8 +915,12 @@ fn handle_browser_new(
             .name(name)
             .kind(kind)
             .domain(domain)
-            .aprotocol(avahi_sys::AVAHI_PROTO_UNSPEC)
-            .flags(0)
+            .aprotocol(context.address_protocol)
+            .flags(if context.resolve_address {
+                context.lookup_flags()
+            } else {
+                context.lookup_flags() | avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_NO_ADDRESS
+            })
             .callback(Some(resolve_callback))
             .userdata(raw_context)
             .build()?,
@@ -201,8 +930,8 @@ fn handle_browser_new(
 
 unsafe extern "C" fn resolve_callback(
     resolver: *mut AvahiServiceResolver,
-    _interface: AvahiIfIndex,
-    _protocol: AvahiProtocol,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
     event: AvahiResolverEvent,
     name: *const c_char,
     kind: *const c_char,
@@ -211,57 +940,88 @@ unsafe extern "C" fn resolve_callback(
     addr: *const AvahiAddress,
     port: u16,
     txt: *mut AvahiStringList,
-    _flags: AvahiLookupResultFlags,
+    flags: AvahiLookupResultFlags,
     userdata: *mut c_void,
 ) {
-    let name = c_str::raw_to_str(name);
-    let kind = c_str::raw_to_str(kind);
-    let domain = c_str::raw_to_str(domain);
-
     let context = AvahiBrowserContext::from_raw(userdata);
 
-    match event {
-        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FAILURE => {
-            context.invoke_callback(Err(format!(
-                "failed to resolve service `{}` of type `{}` in domain `{}`",
-                name, kind, domain
-            )
-            .into()));
-        }
-        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FOUND => {
-            let result = handle_resolver_found(
-                context,
-                c_str::raw_to_str(host_name),
-                addr,
-                name,
-                kind,
-                domain,
-                port,
-                txt,
-            );
-
-            if let Err(e) = result {
-                context.invoke_callback(Err(e));
-            }
-        }
-        _ => {}
+    let result = match event {
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FAILURE => Err(format!(
+            "failed to resolve service `{}` of type `{}` in domain `{}`",
+            CStr::from_ptr(name).to_string_lossy(),
+            CStr::from_ptr(kind).to_string_lossy(),
+            CStr::from_ptr(domain).to_string_lossy()
+        )
+        .into()),
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FOUND => handle_resolver_found(
+            context, interface, protocol, host_name, addr, name, kind, domain, port, txt, flags,
+        ),
+        _ => Ok(()),
     };
 
+    // a malformed service is reported without affecting the others being resolved
+    if let Err(e) = result {
+        context.invoke_callback(Err(e));
+    }
+
     context.resolvers.remove_raw(resolver);
+
+    if let Some(service) = context.queued_resolves.pop_front() {
+        let result = resolve(
+            context,
+            service.interface,
+            service.protocol,
+            service.name.as_ptr(),
+            service.kind.as_ptr(),
+            service.domain.as_ptr(),
+        );
+
+        if let Err(e) = result {
+            context.invoke_callback(Err(e));
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
 unsafe fn handle_resolver_found(
-    context: &AvahiBrowserContext,
-    host_name: &str,
+    context: &mut AvahiBrowserContext,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    host_name: *const c_char,
     addr: *const AvahiAddress,
-    name: &str,
-    kind: &str,
-    domain: &str,
+    name: *const c_char,
+    kind: *const c_char,
+    domain: *const c_char,
     port: u16,
     txt: *mut AvahiStringList,
+    flags: AvahiLookupResultFlags,
 ) -> Result<()> {
-    let address = avahi_util::avahi_address_to_string(addr);
+    let service = browsed_service(interface, protocol, name, kind, domain)?;
+    let host_name = c_str::try_raw_to_str(host_name)?;
+    let name = c_str::try_raw_to_str(name)?;
+    let kind = c_str::try_raw_to_str(kind)?;
+    let domain = c_str::try_raw_to_str(domain)?;
+
+    // no address is reported when resolving with `AVAHI_LOOKUP_NO_ADDRESS`
+    let address = if addr.is_null() {
+        String::new()
+    } else {
+        avahi_util::avahi_address_to_string(addr)
+    };
+
+    // a resolver reports a single address, so the service's other addresses are the ones its
+    // copies on the other interfaces and address families were resolved to
+    let mut addresses = vec![];
+    if let Ok(ip) = address.parse::<IpAddr>() {
+        addresses.push(ip);
+        context.resolved_addresses.insert(service.clone(), ip);
+    }
+    for (copy, ip) in &context.resolved_addresses {
+        let is_same = copy.name == service.name && copy.domain == service.domain;
+        if is_same && !addresses.contains(ip) {
+            addresses.push(*ip);
+        }
+    }
 
     let txt = if txt.is_null() {
         None
@@ -269,14 +1029,38 @@ unsafe fn handle_resolver_found(
         Some(TxtRecord::from(ManagedAvahiStringList::clone_raw(txt)))
     };
 
+    if let Some(txt) = &txt {
+        txt_record::check_received(txt.iter_raw())?;
+    }
+
+    let full_name = format!("{}.{}.{}", name, kind, domain);
+    let mut records = vec![
+        ResolvedRecord::srv(&full_name, port, host_name, None),
+        ResolvedRecord::txt(
+            &full_name,
+            &txt.as_ref().map(|t| t.to_bytes()).unwrap_or_default(),
+            None,
+        ),
+    ];
+    if let Ok(ip) = address.parse() {
+        records.push(ResolvedRecord::address(host_name, ip, None));
+    }
+
     let result = ServiceDiscovery::builder()
         .name(name.to_string())
         .service_type(ServiceType::from_str(kind)?)
         .domain(domain.to_string())
         .host_name(host_name.to_string())
         .address(address)
+        .addresses(addresses)
         .port(port)
         .txt(txt)
+        .is_local(flags & avahi_sys::AvahiLookupResultFlags_AVAHI_LOOKUP_RESULT_LOCAL != 0)
+        .is_wide_area(flags & avahi_sys::AvahiLookupResultFlags_AVAHI_LOOKUP_RESULT_WIDE_AREA != 0)
+        .interface_index(interface as u32)
+        // the resolver that found this service is removed after the callback returns
+        .more_coming(context.resolvers.len() > 1 || !context.queued_resolves.is_empty())
+        .records(records)
         .build()
         .unwrap();
 
@@ -287,13 +1071,87 @@ unsafe fn handle_resolver_found(
     Ok(())
 }
 
-extern "C" fn client_callback(
-    _client: *mut AvahiClient,
+#[derive(Default, FromRaw, AsRaw)]
+struct AvahiDomainBrowserContext {
+    domains: Vec<String>,
+    error: Option<Error>,
+    done: bool,
+}
+
+unsafe extern "C" fn domain_browse_callback(
+    _browser: *mut AvahiDomainBrowser,
+    _interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiBrowserEvent,
+    domain: *const c_char,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let context = AvahiDomainBrowserContext::from_raw(userdata);
+
+    match event {
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => match c_str::try_raw_to_str(domain) {
+            Ok(domain) => {
+                if !context.domains.iter().any(|d| d == domain) {
+                    context.domains.push(domain.to_string());
+                }
+            }
+            Err(e) => {
+                context.error = Some(e);
+                context.done = true;
+            }
+        },
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_ALL_FOR_NOW => context.done = true,
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {
+            context.error = Some("domain browser failure".into());
+            context.done = true;
+        }
+        _ => {}
+    }
+}
+
+unsafe extern "C" fn browser_client_callback(
+    client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiBrowserContext::from_raw(userdata);
+
+    match state {
+        // the client is not stored in the context yet while it is being created
+        avahi_sys::AvahiClientState_AVAHI_CLIENT_S_RUNNING
+            if context.pending_start && context.client.is_some() =>
+        {
+            if let Err(e) = context.start_browsers() {
+                context.invoke_callback(Err(e));
+            }
+        }
+        // with `AVAHI_CLIENT_NO_FAIL`, a client whose daemon went away waits for it to come back,
+        // and the browsers and resolvers created on the old connection are no longer valid
+        avahi_sys::AvahiClientState_AVAHI_CLIENT_CONNECTING if context.is_browsing() => {
+            debug!("Lost the Avahi daemon, browsing again once it is back");
+            context.browsers.clear();
+            context.resolvers = ServiceResolverSet::default();
+            context.queued_resolves.clear();
+            context.services.clear();
+            context.resolved_addresses.clear();
+            context.pending_start = true;
+        }
+        avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE => {
+            context.invoke_callback(Err(avahi_util::client_error(client)))
+        }
+        _ => {}
+    }
+}
+
+/// Client callback for one-shot operations, which stores a client failure, e.g. when the daemon
+/// restarts, in the `Option<Error>` passed as userdata for the operation to return.
+unsafe extern "C" fn client_callback(
+    client: *mut AvahiClient,
     state: AvahiClientState,
-    _userdata: *mut c_void,
+    userdata: *mut c_void,
 ) {
-    // TODO: handle this better
     if let avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE = state {
-        panic!("client failure");
+        *(userdata as *mut Option<Error>) = Some(avahi_util::transient_client_error(client));
     }
 }
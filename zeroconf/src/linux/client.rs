@@ -2,11 +2,13 @@
 
 use super::avahi_util;
 use super::poll::ManagedAvahiSimplePoll;
+use crate::error::Error;
 use crate::ffi::c_str;
 use crate::Result;
 use avahi_sys::{
-    avahi_client_free, avahi_client_get_host_name, avahi_client_new, avahi_simple_poll_get,
-    AvahiClient, AvahiClientCallback, AvahiClientFlags,
+    avahi_client_free, avahi_client_get_host_name, avahi_client_get_state,
+    avahi_client_get_version_string, avahi_client_new, avahi_simple_poll_get, AvahiClient,
+    AvahiClientCallback, AvahiClientFlags, AvahiClientState,
 };
 use libc::{c_int, c_void};
 
@@ -46,11 +48,11 @@ impl ManagedAvahiClient {
 
         match err {
             0 => Ok(Self(client)),
-            _ => Err(format!(
+            _ => Err(Error::from(format!(
                 "could not initialize AvahiClient: {}",
                 avahi_util::get_error(err)
-            )
-            .into()),
+            ))
+            .with_raw_code(err)),
         }
     }
 
@@ -61,6 +63,25 @@ impl ManagedAvahiClient {
         unsafe { get_host_name(self.0) }
     }
 
+    /// Delegate function for [`avahi_client_get_version_string()`].
+    ///
+    /// [`avahi_client_get_version_string()`]: https://avahi.org/doxygen/html/client_8h.html
+    pub fn version_string<'a>(&self) -> Result<&'a str> {
+        let version = unsafe { avahi_client_get_version_string(self.0) };
+        if !version.is_null() {
+            Ok(unsafe { c_str::raw_to_str(version) })
+        } else {
+            Err("could not get version string from AvahiClient".into())
+        }
+    }
+
+    /// Delegate function for [`avahi_client_get_state()`].
+    ///
+    /// [`avahi_client_get_state()`]: https://avahi.org/doxygen/html/client_8h.html
+    pub fn state(&self) -> AvahiClientState {
+        unsafe { avahi_client_get_state(self.0) }
+    }
+
     pub(super) fn inner(&self) -> *mut AvahiClient {
         self.0
     }
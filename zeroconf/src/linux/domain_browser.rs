@@ -0,0 +1,77 @@
+//! Rust friendly `AvahiDomainBrowser` wrappers/helpers
+
+use super::client::ManagedAvahiClient;
+use crate::Result;
+use avahi_sys::{
+    avahi_domain_browser_free, avahi_domain_browser_new, AvahiDomainBrowser,
+    AvahiDomainBrowserCallback, AvahiDomainBrowserType, AvahiIfIndex, AvahiLookupFlags,
+    AvahiProtocol,
+};
+use libc::{c_char, c_void};
+
+/// Wraps the `AvahiDomainBrowser` type from the raw Avahi bindings.
+///
+/// This struct allocates a new `*mut AvahiDomainBrowser` when `ManagedAvahiDomainBrowser::new()`
+/// is invoked and calls the Avahi function responsible for freeing the browser on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiDomainBrowser(*mut AvahiDomainBrowser);
+
+impl ManagedAvahiDomainBrowser {
+    /// Intializes the underlying `*mut AvahiDomainBrowser` and verifies it was created;
+    /// returning `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiDomainBrowserParams {
+            client,
+            interface,
+            protocol,
+            domain,
+            kind,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiDomainBrowserParams,
+    ) -> Result<Self> {
+        let browser = unsafe {
+            avahi_domain_browser_new(
+                client.inner(),
+                interface,
+                protocol,
+                domain,
+                kind,
+                flags,
+                callback,
+                userdata,
+            )
+        };
+
+        if browser.is_null() {
+            Err("could not initialize Avahi domain browser".into())
+        } else {
+            Ok(Self(browser))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiDomainBrowser {
+    fn drop(&mut self) {
+        unsafe { avahi_domain_browser_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiDomainBrowser` with
+/// `ManagedAvahiDomainBrowser::new()`.
+///
+/// See [`avahi_domain_browser_new()`] for more information about these parameters.
+///
+/// [`avahi_domain_browser_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiDomainBrowserParams<'a> {
+    client: &'a ManagedAvahiClient,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    domain: *const c_char,
+    kind: AvahiDomainBrowserType,
+    flags: AvahiLookupFlags,
+    callback: AvahiDomainBrowserCallback,
+    userdata: *mut c_void,
+}
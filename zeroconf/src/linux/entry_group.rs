@@ -4,9 +4,10 @@ use super::string_list::ManagedAvahiStringList;
 use crate::ffi::UnwrapMutOrNull;
 use crate::Result;
 use avahi_sys::{
-    avahi_entry_group_add_service_strlst, avahi_entry_group_commit, avahi_entry_group_free,
-    avahi_entry_group_is_empty, avahi_entry_group_new, avahi_entry_group_reset, AvahiClient,
-    AvahiEntryGroup, AvahiEntryGroupCallback, AvahiIfIndex, AvahiProtocol, AvahiPublishFlags,
+    avahi_entry_group_add_address, avahi_entry_group_add_service_strlst, avahi_entry_group_commit,
+    avahi_entry_group_free, avahi_entry_group_is_empty, avahi_entry_group_new,
+    avahi_entry_group_reset, AvahiAddress, AvahiClient, AvahiEntryGroup, AvahiEntryGroupCallback,
+    AvahiIfIndex, AvahiProtocol, AvahiPublishFlags,
 };
 use libc::{c_char, c_void};
 
@@ -80,6 +81,28 @@ impl ManagedAvahiEntryGroup {
         avahi!(avahi_entry_group_commit(self.0), "could not commit service")
     }
 
+    /// Delegate function for [`avahi_entry_group_add_address()`].
+    ///
+    /// Unlike `add_service()`, this does not commit the group; the address is published along
+    /// with the next committed service.
+    ///
+    /// [`avahi_entry_group_add_address()`]: https://avahi.org/doxygen/html/publish_8h.html
+    pub fn add_address(
+        &mut self,
+        AddAddressParams {
+            interface,
+            protocol,
+            flags,
+            name,
+            address,
+        }: AddAddressParams,
+    ) -> Result<()> {
+        avahi!(
+            avahi_entry_group_add_address(self.0, interface, protocol, flags, name, address),
+            "could not register address"
+        )
+    }
+
     /// Delegate function for [`avahi_entry_group_reset()`].
     ///
     /// [`avahi_entry_group_reset()`]: https://avahi.org/doxygen/html/publish_8h.html#a1293bbccf878dbeb9916660022bc71b2
@@ -124,3 +147,17 @@ pub struct AddServiceParams<'a> {
     port: u16,
     txt: Option<&'a ManagedAvahiStringList>,
 }
+
+/// Holds parameters for `ManagedAvahiEntryGroup::add_address()`.
+///
+/// See [`avahi_entry_group_add_address()`] for more information about these parameters.
+///
+/// [`avahi_entry_group_add_address()`]: https://avahi.org/doxygen/html/publish_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct AddAddressParams {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    flags: AvahiPublishFlags,
+    name: *const c_char,
+    address: *const AvahiAddress,
+}
@@ -2,24 +2,103 @@
 
 use super::poll::ManagedAvahiSimplePoll;
 use crate::event_loop::TEventLoop;
-use crate::Result;
+use crate::service::RegistrationSlot;
+use crate::{Result, ServiceRegistration};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(new)]
 pub struct AvahiEventLoop<'a> {
     poll: Arc<ManagedAvahiSimplePoll>,
+    stopped: Arc<AtomicBool>,
+    #[new(default)]
+    registration: Option<RegistrationSlot>,
+    #[new(default)]
+    after_poll: Option<Box<dyn Fn()>>,
     phantom: PhantomData<&'a ManagedAvahiSimplePoll>,
 }
 
+impl<'a> AvahiEventLoop<'a> {
+    pub(crate) fn with_registration(mut self, registration: RegistrationSlot) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    /// Sets a function to run at the end of every `poll()` and `try_poll()`, e.g. to check a
+    /// deadline while no events arrive.
+    pub(crate) fn with_after_poll(mut self, after_poll: impl Fn() + 'static) -> Self {
+        self.after_poll = Some(Box::new(after_poll));
+        self
+    }
+
+    fn run_after_poll(&self) {
+        if let Some(f) = &self.after_poll {
+            f();
+        }
+    }
+}
+
 impl<'a> TEventLoop for AvahiEventLoop<'a> {
-    /// Polls for new events.
+    /// Polls for new events, blocking for up to `timeout` until one arrives.
     ///
-    /// Internally calls `ManagedAvahiSimplePoll::iterate(0)`, the `timeout` parameter does not
-    /// currently do anything in the Avahi implementation.
-    fn poll(&self, _timeout: Duration) -> Result<()> {
-        self.poll.iterate(0);
+    /// Internally calls `ManagedAvahiSimplePoll::iterate()` with `timeout` in milliseconds.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        if !self.is_stopped() {
+            self.poll.iterate(sleep_time(timeout));
+        }
+        self.run_after_poll();
         Ok(())
     }
+
+    /// Internally calls `ManagedAvahiSimplePoll::iterate(0)` until no file descriptors are
+    /// ready. The count returned is the number of ready descriptors that were dispatched.
+    fn try_poll(&self) -> Result<usize> {
+        let mut handled = 0;
+
+        while !self.is_stopped() {
+            match self.poll.iterate(0) {
+                0 => break,
+                ready => handled += ready,
+            }
+        }
+
+        self.run_after_poll();
+
+        Ok(handled)
+    }
+
+    fn wait_for_registration(&self, timeout: Duration) -> Result<ServiceRegistration> {
+        let registration = self
+            .registration
+            .as_ref()
+            .ok_or("event loop is not driving a service")?;
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(result) = registration.lock().unwrap().clone() {
+                return result;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err("timed out waiting for registration".into());
+            } else if self.is_stopped() {
+                return Err("event loop stopped before registration".into());
+            }
+
+            self.poll.iterate(sleep_time(remaining));
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+}
+
+/// Converts `timeout` to the milliseconds `avahi_simple_poll_iterate()` sleeps for.
+fn sleep_time(timeout: Duration) -> i32 {
+    timeout.as_millis().min(i32::MAX as u128) as i32
 }
@@ -0,0 +1,77 @@
+//! Rust friendly `AvahiHostNameResolver` wrappers/helpers
+
+use super::client::ManagedAvahiClient;
+use crate::Result;
+use avahi_sys::{
+    avahi_host_name_resolver_free, avahi_host_name_resolver_new, AvahiHostNameResolver,
+    AvahiHostNameResolverCallback, AvahiIfIndex, AvahiLookupFlags, AvahiProtocol,
+};
+use libc::{c_char, c_void};
+
+/// Wraps the `AvahiHostNameResolver` type from the raw Avahi bindings.
+///
+/// This struct allocates a new `*mut AvahiHostNameResolver` when
+/// `ManagedAvahiHostNameResolver::new()` is invoked and calls the Avahi function responsible for
+/// freeing the resolver on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiHostNameResolver(*mut AvahiHostNameResolver);
+
+impl ManagedAvahiHostNameResolver {
+    /// Initializes the underlying `*mut AvahiHostNameResolver` and verifies it was created;
+    /// returning `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiHostNameResolverParams {
+            client,
+            interface,
+            protocol,
+            name,
+            aprotocol,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiHostNameResolverParams,
+    ) -> Result<Self> {
+        let resolver = unsafe {
+            avahi_host_name_resolver_new(
+                client.inner(),
+                interface,
+                protocol,
+                name,
+                aprotocol,
+                flags,
+                callback,
+                userdata,
+            )
+        };
+
+        if resolver.is_null() {
+            Err("could not initialize Avahi host name resolver".into())
+        } else {
+            Ok(Self(resolver))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiHostNameResolver {
+    fn drop(&mut self) {
+        unsafe { avahi_host_name_resolver_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiHostNameResolver` with
+/// `ManagedAvahiHostNameResolver::new()`.
+///
+/// See [`avahi_host_name_resolver_new()`] for more information about these parameters.
+///
+/// [`avahi_host_name_resolver_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiHostNameResolverParams<'a> {
+    client: &'a ManagedAvahiClient,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    aprotocol: AvahiProtocol,
+    flags: AvahiLookupFlags,
+    callback: AvahiHostNameResolverCallback,
+    userdata: *mut c_void,
+}
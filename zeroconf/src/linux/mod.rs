@@ -9,8 +9,10 @@
 pub mod avahi_util;
 pub mod browser;
 pub mod client;
+pub mod domain_browser;
 pub mod entry_group;
 pub mod event_loop;
+pub mod host_resolver;
 pub mod poll;
 pub mod raw_browser;
 pub mod resolver;
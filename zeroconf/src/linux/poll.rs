@@ -3,15 +3,20 @@
 use crate::Result;
 use avahi_sys::{
     avahi_simple_poll_free, avahi_simple_poll_iterate, avahi_simple_poll_loop,
-    avahi_simple_poll_new, AvahiSimplePoll,
+    avahi_simple_poll_new, avahi_simple_poll_set_func, pollfd, AvahiSimplePoll,
 };
+use libc::{c_int, c_uint, c_void};
+use std::cell::Cell;
 
 /// Wraps the `AvahiSimplePoll` type from the raw Avahi bindings.
 ///
 /// This struct allocates a new `*mut AvahiSimplePoll` when `ManagedAvahiClient::new()` is invoked
 /// and calls the Avahi function responsible for freeing the poll on `trait Drop`.
 #[derive(Debug)]
-pub struct ManagedAvahiSimplePoll(*mut AvahiSimplePoll);
+pub struct ManagedAvahiSimplePoll {
+    poll: *mut AvahiSimplePoll,
+    ready: Box<Cell<c_int>>,
+}
 
 impl ManagedAvahiSimplePoll {
     /// Initializes the underlying `*mut AvahiSimplePoll` and verifies it was created; returning
@@ -19,10 +24,20 @@ impl ManagedAvahiSimplePoll {
     pub fn new() -> Result<Self> {
         let poll = unsafe { avahi_simple_poll_new() };
         if poll.is_null() {
-            Err("could not initialize AvahiSimplePoll".into())
-        } else {
-            Ok(Self(poll))
+            return Err("could not initialize AvahiSimplePoll".into());
         }
+
+        let ready = Box::new(Cell::new(0));
+
+        unsafe {
+            avahi_simple_poll_set_func(
+                poll,
+                Some(poll_func),
+                &*ready as *const Cell<c_int> as *mut c_void,
+            )
+        };
+
+        Ok(Self { poll, ready })
     }
 
     /// Delegate function for [`avahi_simple_poll_loop()`].
@@ -30,25 +45,40 @@ impl ManagedAvahiSimplePoll {
     /// [`avahi_simple_poll_loop()`]: https://avahi.org/doxygen/html/simple-watch_8h.html#a14b4cb29832e8c3de609d4c4e5611985
     pub fn start_loop(&self) -> Result<()> {
         avahi!(
-            avahi_simple_poll_loop(self.0),
+            avahi_simple_poll_loop(self.poll),
             "could not start AvahiSimplePoll"
         )
     }
 
-    /// Delegate function for [`avahi_simple_poll_iterate()`].
+    /// Delegate function for [`avahi_simple_poll_iterate()`]. Returns the number of file
+    /// descriptors that were ready to be dispatched.
     ///
     /// [`avahi_simple_poll_iterate()`]: https://avahi.org/doxygen/html/simple-watch_8h.html#ad5b7c9d3b7a6584d609241ee6f472a2e
-    pub fn iterate(&self, sleep_time: i32) {
-        unsafe { avahi_simple_poll_iterate(self.0, sleep_time) };
+    pub fn iterate(&self, sleep_time: i32) -> usize {
+        self.ready.set(0);
+        unsafe { avahi_simple_poll_iterate(self.poll, sleep_time) };
+        self.ready.get().max(0) as usize
     }
 
     pub(super) fn inner(&self) -> *mut AvahiSimplePoll {
-        self.0
+        self.poll
     }
 }
 
 impl Drop for ManagedAvahiSimplePoll {
     fn drop(&mut self) {
-        unsafe { avahi_simple_poll_free(self.0) };
+        unsafe { avahi_simple_poll_free(self.poll) };
     }
 }
+
+/// Same as Avahi's default poll function, but records how many descriptors were ready.
+unsafe extern "C" fn poll_func(
+    ufds: *mut pollfd,
+    nfds: c_uint,
+    timeout: c_int,
+    userdata: *mut c_void,
+) -> c_int {
+    let ready = libc::poll(ufds as *mut libc::pollfd, nfds as libc::nfds_t, timeout);
+    (*(userdata as *const Cell<c_int>)).set(ready);
+    ready
+}
@@ -96,4 +96,8 @@ impl ServiceResolverSet {
     pub fn remove_raw(&mut self, raw: *mut AvahiServiceResolver) {
         self.resolvers.remove(&raw);
     }
+
+    pub fn len(&self) -> usize {
+        self.resolvers.len()
+    }
 }
@@ -2,13 +2,17 @@
 
 use super::avahi_util;
 use super::client::{self, ManagedAvahiClient, ManagedAvahiClientParams};
-use super::entry_group::{AddServiceParams, ManagedAvahiEntryGroup, ManagedAvahiEntryGroupParams};
+use super::entry_group::{
+    AddAddressParams, AddServiceParams, ManagedAvahiEntryGroup, ManagedAvahiEntryGroupParams,
+};
 use super::poll::ManagedAvahiSimplePoll;
+use crate::error::{Error, ErrorKind};
 use crate::ffi::{c_str, AsRaw, FromRaw, UnwrapOrNull};
 use crate::prelude::*;
+use crate::service::{self, RegistrationClaim, RegistrationSlot};
 use crate::{
-    EventLoop, NetworkInterface, Result, ServiceRegisteredCallback, ServiceRegistration,
-    ServiceType, TxtRecord,
+    EventLoop, NetworkInterface, PublishFlags, Result, ServiceRegisteredCallback,
+    ServiceRegistration, ServiceType, TxtRecord,
 };
 use avahi_sys::{
     AvahiClient, AvahiClientFlags, AvahiClientState, AvahiEntryGroup, AvahiEntryGroupState,
@@ -18,26 +22,40 @@ use libc::c_void;
 use std::any::Any;
 use std::ffi::CString;
 use std::fmt::{self, Formatter};
+use std::ops::ControlFlow;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct AvahiMdnsService {
     client: Option<ManagedAvahiClient>,
     poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    no_fail: bool,
     context: *mut AvahiServiceContext,
 }
 
 impl TMdnsService for AvahiMdnsService {
+    type EventLoop<'a> = EventLoop<'a>;
+
     fn new(service_type: ServiceType, port: u16) -> Self {
-        Self {
+        let domain = service_type.explicit_domain().map(|d| d.to_string());
+
+        let mut service = Self {
             client: None,
             poll: None,
+            no_fail: false,
             context: Box::into_raw(Box::new(AvahiServiceContext::new(
-                &service_type.to_string(),
+                &service_type.as_registration_string(),
                 port,
             ))),
+        };
+
+        if let Some(domain) = domain {
+            service.set_domain(&domain);
         }
+
+        service
     }
 
     /// Sets the name to register this service under. If no name is set, the client's host name
@@ -54,6 +72,10 @@ impl TMdnsService for AvahiMdnsService {
         unsafe { (*self.context).interface_index = avahi_util::interface_index(interface) };
     }
 
+    fn set_local_only(&mut self, local_only: bool) {
+        unsafe { (*self.context).local_only = local_only };
+    }
+
     fn set_domain(&mut self, domain: &str) {
         unsafe { (*self.context).domain = Some(c_string!(domain)) };
     }
@@ -66,10 +88,33 @@ impl TMdnsService for AvahiMdnsService {
         unsafe { (*self.context).txt_record = Some(txt_record) };
     }
 
+    fn set_txt_records(&mut self, txt_records: Vec<TxtRecord>) {
+        if txt_records.len() > 1 {
+            warn!(
+                "Avahi supports a single TXT record per service, ignoring {} additional records",
+                txt_records.len() - 1
+            );
+        }
+
+        unsafe { (*self.context).txt_record = txt_records.into_iter().next() };
+    }
+
     fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
         unsafe { (*self.context).registered_callback = Some(registered_callback) };
     }
 
+    fn set_additional_record_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Err(Error::with_kind(
+            ErrorKind::Unsupported,
+            "additional records are only supported by Bonjour",
+        ))
+    }
+
+    fn set_publish_flags(&mut self, flags: PublishFlags) -> Result<()> {
+        unsafe { (*self.context).publish_flags = flags };
+        Ok(())
+    }
+
     fn set_context(&mut self, context: Box<dyn Any>) {
         unsafe { (*self.context).user_context = Some(Arc::from(context)) };
     }
@@ -77,24 +122,166 @@ impl TMdnsService for AvahiMdnsService {
     fn register(&mut self) -> Result<EventLoop> {
         debug!("Registering service: {:?}", self);
 
+        if unsafe { (*self.context).claim.is_some() } {
+            return Err(Error::with_kind(
+                ErrorKind::AlreadyRegistered,
+                "service is already registered",
+            ));
+        }
+
+        let claim = unsafe {
+            let context = &*self.context;
+            let host = context.host_fqdn.as_ref().or(context.host.as_ref());
+
+            service::validate_settings(
+                host.map(|h| h.to_str().unwrap()),
+                context.domain.as_ref().map(|d| d.to_str().unwrap()),
+            )?;
+
+            RegistrationClaim::new(
+                context.kind.to_str().unwrap(),
+                context.name.as_ref().map(|n| n.to_str().unwrap()),
+                context.domain.as_ref().map(|d| d.to_str().unwrap()),
+                context.port,
+            )?
+        };
+
+        unsafe {
+            (*self.context).stopped.store(false, Ordering::SeqCst);
+            (*self.context).registration.lock().unwrap().take();
+        }
+
         self.poll = Some(Arc::new(ManagedAvahiSimplePoll::new()?));
 
+        let flags = if self.no_fail {
+            AvahiClientFlags::AVAHI_CLIENT_NO_FAIL
+        } else {
+            AvahiClientFlags(0)
+        };
+
         self.client = Some(ManagedAvahiClient::new(
             ManagedAvahiClientParams::builder()
                 .poll(self.poll.as_ref().unwrap())
-                .flags(AvahiClientFlags(0))
+                .flags(flags)
                 .callback(Some(client_callback))
                 .userdata(self.context as *mut c_void)
                 .build()?,
         )?);
 
-        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+        unsafe { (*self.context).claim = Some(claim) };
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone(), unsafe {
+            (*self.context).stopped.clone()
+        })
+        .with_registration(unsafe { (*self.context).registration.clone() }))
+    }
+
+    fn unregister(&mut self) -> Result<()> {
+        if unsafe { (*self.context).claim.take() }.is_none() {
+            return Ok(());
+        }
+
+        debug!("Unregistering service: {:?}", self);
+
+        unsafe {
+            let context = &mut *self.context;
+
+            // the entry group belongs to the client and must be freed first
+            if let Some(mut group) = context.group.take() {
+                group.reset();
+            }
+
+            context.registrations.clear();
+            context.registration.lock().unwrap().take();
+            context.stopped.store(true, Ordering::SeqCst);
+        }
+
+        self.client = None;
+        self.poll = None;
+
+        Ok(())
+    }
+
+    /// Resets the entry group and commits it again, which makes Avahi probe and announce the
+    /// service anew.
+    fn reannounce(&mut self) -> Result<EventLoop<'_>> {
+        if unsafe { (*self.context).claim.is_none() } {
+            return Err("service is not registered".into());
+        }
+
+        debug!("Reannouncing service: {:?}", self);
+
+        unsafe {
+            let context = &mut *self.context;
+
+            if let Some(registration) = context.registrations.first() {
+                context.name = Some(c_string!(registration.name().as_str()));
+            }
+
+            context.registrations.clear();
+            context.registration.lock().unwrap().take();
+            context.stopped.store(false, Ordering::SeqCst);
+
+            // without a group the client is not running yet and will publish the service itself
+            if let Some(group) = &mut context.group {
+                group.reset();
+                create_service(self.client.as_ref().unwrap().inner(), context)?;
+            }
+        }
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone(), unsafe {
+            (*self.context).stopped.clone()
+        })
+        .with_registration(unsafe { (*self.context).registration.clone() }))
+    }
+
+    fn registrations(&self) -> Vec<ServiceRegistration> {
+        unsafe { (*self.context).registrations.clone() }
+    }
+}
+
+impl AvahiMdnsService {
+    /// Advertises the service under the specified host `fqdn` (e.g. `my-host.local`) instead of
+    /// the host name Avahi is configured with.
+    ///
+    /// Unlike `set_host()`, which points the SRV record at a host that is expected to publish its
+    /// own address records, this also publishes this machine's addresses under `fqdn`, so that
+    /// several distinct host names can be advertised from one process. If neither is set, Avahi
+    /// advertises the host name it is configured with, including after it renames the host
+    /// following a host name conflict.
+    pub fn set_host_fqdn(&mut self, fqdn: &str) {
+        unsafe { (*self.context).host_fqdn = Some(c_string!(fqdn)) };
+    }
+
+    /// If `true`, `register()` succeeds even if the Avahi daemon is not running yet. The service
+    /// is then published once the daemon becomes available, so the registered callback is not
+    /// invoked until it has, e.g. when an application starts before `avahi-daemon` during boot.
+    ///
+    /// This creates the client with `AVAHI_CLIENT_NO_FAIL`. If the daemon goes away after the
+    /// client has connected, `registrations()` is empty until it is back, at which point the
+    /// service is published again and the registered callback invoked again. Defaults to
+    /// `false`.
+    pub fn set_no_fail(&mut self, no_fail: bool) {
+        self.no_fail = no_fail;
     }
 }
 
 impl Drop for AvahiMdnsService {
+    /// Frees the entry group and the client before the context passed to their callbacks, so
+    /// that no callback can observe a freed context.
     fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
+        unsafe {
+            // withdraw the records while the client is still connected so the daemon announces
+            // their removal
+            if let Some(mut group) = (*self.context).group.take() {
+                group.reset();
+            }
+
+            self.client = None;
+            self.poll = None;
+
+            drop(Box::from_raw(self.context));
+        }
     }
 }
 
@@ -104,12 +291,19 @@ struct AvahiServiceContext {
     kind: CString,
     port: u16,
     group: Option<ManagedAvahiEntryGroup>,
+    registrations: Vec<ServiceRegistration>,
     txt_record: Option<TxtRecord>,
     interface_index: AvahiIfIndex,
+    local_only: bool,
     domain: Option<CString>,
     host: Option<CString>,
+    host_fqdn: Option<CString>,
+    publish_flags: PublishFlags,
     registered_callback: Option<Box<ServiceRegisteredCallback>>,
     user_context: Option<Arc<dyn Any>>,
+    registration: RegistrationSlot,
+    stopped: Arc<AtomicBool>,
+    claim: Option<RegistrationClaim>,
 }
 
 impl AvahiServiceContext {
@@ -119,18 +313,29 @@ impl AvahiServiceContext {
             kind: c_string!(kind),
             port,
             group: None,
+            registrations: vec![],
             txt_record: None,
             interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            local_only: false,
             domain: None,
             host: None,
+            host_fqdn: None,
+            publish_flags: PublishFlags::empty(),
             registered_callback: None,
             user_context: None,
+            registration: RegistrationSlot::default(),
+            stopped: Arc::default(),
+            claim: None,
         }
     }
 
-    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
-        if let Some(f) = &self.registered_callback {
-            f(result, self.user_context.clone());
+    fn invoke_callback(&mut self, result: Result<ServiceRegistration>) {
+        *self.registration.lock().unwrap() = Some(result.clone());
+
+        if let Some(f) = &mut self.registered_callback {
+            if let ControlFlow::Break(()) = f(result, self.user_context.clone()) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
         } else {
             panic!("attempted to invoke service callback but none was set");
         }
@@ -162,13 +367,24 @@ unsafe extern "C" fn client_callback(
             }
         }
         avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE => {
-            context.invoke_callback(Err("client failure".into()))
+            context.invoke_callback(Err(avahi_util::client_error(client)))
+        }
+        // with `AVAHI_CLIENT_NO_FAIL`, a client whose daemon went away waits for it to come back,
+        // and the group created on the old connection is no longer valid
+        avahi_sys::AvahiClientState_AVAHI_CLIENT_CONNECTING => {
+            if context.group.take().is_some() {
+                debug!("Lost the Avahi daemon, publishing again once it is back");
+            }
+            context.registrations.clear();
+            context.registration.lock().unwrap().take();
         }
         avahi_sys::AvahiClientState_AVAHI_CLIENT_S_REGISTERING => {
             if let Some(g) = &mut context.group {
                 debug!("Group reset");
                 g.reset();
             }
+            context.registrations.clear();
+            context.registration.lock().unwrap().take();
         }
         _ => {}
     };
@@ -194,43 +410,116 @@ unsafe fn create_service(
         )?);
     }
 
+    let interface = if context.local_only {
+        avahi_util::loopback_interface_index()?
+    } else {
+        context.interface_index
+    };
+
+    // without a host, Avahi points the SRV record at its own host name, following any rename
+    let host = context.host_fqdn.as_ref().or(context.host.as_ref());
+
     let group = context.group.as_mut().unwrap();
 
     if group.is_empty() {
+        if let Some(host) = &context.host_fqdn {
+            debug!("Adding addresses for {:?}", host);
+
+            for (index, address) in avahi_util::local_addresses(interface)? {
+                group.add_address(
+                    AddAddressParams::builder()
+                        .interface(index)
+                        .protocol(address.proto)
+                        // the reverse mapping already belongs to the machine's own host name
+                        .flags(avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_REVERSE)
+                        .name(host.as_ptr())
+                        .address(&address)
+                        .build()?,
+                )?;
+            }
+        }
+
         debug!("Adding service");
 
-        group.add_service(
+        let result = group.add_service(
             AddServiceParams::builder()
-                .interface(context.interface_index)
+                .interface(interface)
                 .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
-                .flags(0)
+                .flags(context.publish_flags.bits())
                 .name(context.name.as_ref().unwrap().as_ptr())
                 .kind(context.kind.as_ptr())
                 .domain(context.domain.as_ref().map(|d| d.as_ptr()).unwrap_or_null())
-                .host(context.host.as_ref().map(|h| h.as_ptr()).unwrap_or_null())
+                .host(host.map(|h| h.as_ptr()).unwrap_or_null())
                 .port(context.port)
                 .txt(context.txt_record.as_ref().map(|t| t.inner()))
                 .build()?,
-        )
+        );
+
+        match result {
+            // the name is taken by a service published through the same daemon
+            Err(e) if e.raw_code() == Some(avahi_sys::AVAHI_ERR_COLLISION) => {
+                rename_service(context);
+                create_service(client, context)
+            }
+            result => result,
+        }
     } else {
         Ok(())
     }
 }
 
 unsafe extern "C" fn entry_group_callback(
-    _group: *mut AvahiEntryGroup,
+    group: *mut AvahiEntryGroup,
     state: AvahiEntryGroupState,
     userdata: *mut c_void,
 ) {
-    if let avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_ESTABLISHED = state {
-        let context = AvahiServiceContext::from_raw(userdata);
-        if let Err(e) = handle_group_established(context) {
-            context.invoke_callback(Err(e));
+    let context = AvahiServiceContext::from_raw(userdata);
+
+    let result = match state {
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_ESTABLISHED => {
+            handle_group_established(context)
+        }
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_COLLISION => {
+            handle_group_collision(group, context)
         }
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        context.invoke_callback(Err(e));
     }
 }
 
-unsafe fn handle_group_established(context: &AvahiServiceContext) -> Result<()> {
+unsafe fn handle_group_collision(
+    group: *mut AvahiEntryGroup,
+    context: &mut AvahiServiceContext,
+) -> Result<()> {
+    // another host advertises the same name; the renamed service is reported once established
+    rename_service(context);
+    create_service(avahi_sys::avahi_entry_group_get_client(group), context)
+}
+
+/// Switches the service to the alternative name Avahi suggests after a name collision and resets
+/// the group so the service can be added again under it.
+fn rename_service(context: &mut AvahiServiceContext) {
+    let name = avahi_util::alternative_service_name(context.name.as_ref().unwrap());
+
+    debug!("Service name collision, renaming to {:?}", name);
+
+    if let Some(claim) = &mut context.claim {
+        claim.rename(name.to_str().unwrap());
+    }
+
+    context.name = Some(name);
+    context.registrations.clear();
+    context.registration.lock().unwrap().take();
+
+    if let Some(g) = &mut context.group {
+        g.reset();
+    }
+}
+
+unsafe fn handle_group_established(context: &mut AvahiServiceContext) -> Result<()> {
     debug!("Group established");
 
     let result = ServiceRegistration::builder()
@@ -238,9 +527,17 @@ unsafe fn handle_group_established(context: &AvahiServiceContext) -> Result<()>
         .service_type(ServiceType::from_str(&c_str::copy_raw(
             context.kind.as_ptr(),
         ))?)
-        .domain("local".to_string())
+        .port(context.port)
+        .domain(
+            context
+                .domain
+                .as_ref()
+                .map(|d| d.to_string_lossy().trim_end_matches('.').to_string())
+                .unwrap_or_else(|| "local".to_string()),
+        )
         .build()?;
 
+    context.registrations = vec![result.clone()];
     context.invoke_callback(Ok(result));
 
     Ok(())
@@ -9,7 +9,7 @@ use avahi_sys::{
 };
 use libc::{c_char, c_void};
 use std::marker::PhantomData;
-use std::ptr;
+use std::{ptr, slice};
 
 /// Wraps the `AvahiStringList` pointer from the raw Avahi bindings.
 ///
@@ -127,6 +127,11 @@ impl<'a> AvahiStringListNode<'a> {
         }
     }
 
+    /// Returns the raw `key=value` text of this node, borrowed from the list.
+    pub fn text(&self) -> &'a [u8] {
+        unsafe { slice::from_raw_parts((*self.list).text.as_ptr(), (*self.list).size) }
+    }
+
     /// Returns the `AvahiPair` for this list.
     pub fn get_pair(&mut self) -> AvahiPair {
         let mut key: *mut c_char = ptr::null_mut();
@@ -1,7 +1,7 @@
 //! Avahi implementation for cross-platform TXT record.
 
 use super::string_list::{AvahiStringListNode, ManagedAvahiStringList};
-use crate::txt_record::TTxtRecord;
+use crate::txt_record::{self, TTxtRecord};
 use crate::Result;
 use libc::c_char;
 use std::cell::UnsafeCell;
@@ -14,6 +14,8 @@ impl TTxtRecord for AvahiTxtRecord {
     }
 
     fn insert(&mut self, key: &str, value: &str) -> Result<()> {
+        txt_record::validate_entry(key, value.as_bytes())?;
+
         unsafe {
             self.inner_mut().add_pair(
                 c_string!(key).as_ptr() as *const c_char,
@@ -67,15 +69,22 @@ impl TTxtRecord for AvahiTxtRecord {
     }
 
     fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (String, String)> + 'a> {
-        Box::new(Iter::new(self.inner_mut().head()))
+        Box::new(self.entries())
+    }
+
+    /// Borrows the text of each `AvahiStringList` node.
+    fn iter_raw<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a [u8], &'a [u8])> + 'a> {
+        Box::new(RawIter {
+            node: self.entries().node,
+        })
     }
 
     fn keys<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a> {
-        Box::new(Keys(Iter::new(self.inner_mut().head())))
+        Box::new(Keys(self.entries()))
     }
 
     fn values<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a> {
-        Box::new(Values(Iter::new(self.inner_mut().head())))
+        Box::new(Values(self.entries()))
     }
 }
 
@@ -88,6 +97,15 @@ impl AvahiTxtRecord {
     pub(crate) fn inner(&self) -> &ManagedAvahiStringList {
         unsafe { &*self.0.get() }
     }
+
+    /// An empty record is a null `AvahiStringList`, which has no head node to start from.
+    fn entries(&self) -> Iter<'_> {
+        if self.is_empty() {
+            Iter { node: None }
+        } else {
+            Iter::new(self.inner_mut().head())
+        }
+    }
 }
 
 impl From<ManagedAvahiStringList> for AvahiTxtRecord {
@@ -97,6 +115,8 @@ impl From<ManagedAvahiStringList> for AvahiTxtRecord {
 }
 
 impl Clone for AvahiTxtRecord {
+    /// Copies the underlying `AvahiStringList`, so the clone can be modified independently of
+    /// `self`.
     fn clone(&self) -> Self {
         Self::from(self.inner().clone())
     }
@@ -128,11 +148,28 @@ impl Iterator for Iter<'_> {
 
         Some((
             pair.key().as_str().unwrap().to_string(),
-            pair.value().as_str().unwrap().to_string(),
+            // a key without `=` has no value
+            pair.value().as_str().unwrap_or_default().to_string(),
         ))
     }
 }
 
+pub struct RawIter<'a> {
+    node: Option<AvahiStringListNode<'a>>,
+}
+
+impl<'a> Iterator for RawIter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.node.take()?;
+        let text = n.text();
+        self.node = n.next();
+
+        Some(txt_record::split_entry(text))
+    }
+}
+
 pub struct Keys<'a>(Iter<'a>);
 
 impl Iterator for Keys<'_> {
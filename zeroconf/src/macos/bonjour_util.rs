@@ -1,18 +1,44 @@
 //! Utilities related to Bonjour
 
 use super::constants;
-use crate::NetworkInterface;
+use super::service_ref::{GetAddressInfoParams, ManagedDNSServiceRef};
+use crate::error::{Error, ErrorKind};
+use crate::ffi::{self, AsRaw, FromRaw};
+use crate::prelude::*;
+use crate::{AddressFamily, NetworkInterface, Result};
+use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceProtocol, DNSServiceRef};
+use libc::{c_char, c_int, c_void, sockaddr_in, sockaddr_in6};
+use std::ffi::CStr;
+use std::mem;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
 
 /// Normalizes the specified domain `&str` to conform to a standard enforced by this crate.
 ///
 /// Bonjour suffixes domains with a final `'.'` character in some contexts but is not required by
 /// the standard. This function removes the final dot if present.
 pub fn normalize_domain(domain: &str) -> String {
-    if domain.chars().nth(domain.len() - 1).unwrap() == '.' {
-        String::from(&domain[..domain.len() - 1])
-    } else {
-        String::from(domain)
+    String::from(domain.strip_suffix('.').unwrap_or(domain))
+}
+
+/// Returns `true` if the specified host name (e.g. `my-mac.local.`) refers to this machine.
+///
+/// Names are compared case-insensitively and without the `.local` suffix, since `gethostname()`
+/// may or may not include it.
+pub fn is_local_host_name(host_name: &str) -> bool {
+    let mut buf = [0 as c_char; 256];
+
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) } != 0 {
+        return false;
     }
+
+    let local = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+
+    strip_local(&normalize_domain(host_name)).eq_ignore_ascii_case(strip_local(&local))
+}
+
+fn strip_local(host_name: &str) -> &str {
+    host_name.strip_suffix(".local").unwrap_or(host_name)
 }
 
 /// Converts the specified [`NetworkInterface`] to the Bonjour expected value.
@@ -24,3 +50,161 @@ pub fn interface_index(interface: NetworkInterface) -> u32 {
         NetworkInterface::AtIndex(i) => i,
     }
 }
+
+/// Converts the specified [`AddressFamily`] to the Bonjour expected value.
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn address_protocol(family: AddressFamily) -> DNSServiceProtocol {
+    match family {
+        AddressFamily::Any => 0,
+        AddressFamily::V4 => bonjour_sys::kDNSServiceProtocol_IPv4 as DNSServiceProtocol,
+        AddressFamily::V6 => bonjour_sys::kDNSServiceProtocol_IPv6 as DNSServiceProtocol,
+    }
+}
+
+/// Returns the version of mDNSResponder, or `None` if it could not be determined.
+///
+/// The daemon reports its version as a single integer in the same format as `_DNS_SD_H`, i.e.
+/// `major * 10000 + minor * 100 + patch`.
+pub fn daemon_version() -> Option<String> {
+    let mut version: u32 = 0;
+    let mut size = mem::size_of::<u32>() as u32;
+
+    let err = unsafe {
+        bonjour_sys::DNSServiceGetProperty(
+            constants::BONJOUR_PROPERTY_DAEMON_VERSION.as_ptr() as *const c_char,
+            &mut version as *mut u32 as *mut c_void,
+            &mut size,
+        )
+    };
+
+    if err != 0 {
+        return None;
+    }
+
+    Some(format!(
+        "{}.{}.{}",
+        version / 10000,
+        version / 100 % 100,
+        version % 100
+    ))
+}
+
+/// Converts the specified `sockaddr` reported by `DNSServiceGetAddrInfo` to an `IpAddr`.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference.
+pub unsafe fn sockaddr_to_ip(address: *const bonjour_sys::sockaddr) -> IpAddr {
+    assert_not_null!(address);
+
+    match (*address).sa_family as c_int {
+        libc::AF_INET6 => {
+            let address = address as *const sockaddr_in6;
+            IpAddr::from((*address).sin6_addr.s6_addr)
+        }
+        _ => {
+            // on macOS the bytes are swapped for the ip
+            let address = address as *const sockaddr_in;
+            IpAddr::from((*address).sin_addr.s_addr.to_le_bytes())
+        }
+    }
+}
+
+/// Resolves `host` to its IPv4 and IPv6 addresses, blocking until the daemon has reported every
+/// address it currently knows of or `timeout` has elapsed.
+///
+/// Replies are processed until one arrives without `kDNSServiceFlagsMoreComing`. Addresses
+/// resolved before `timeout` elapses are returned rather than an error.
+pub fn resolve_host(host: &str, timeout: Duration) -> Result<Vec<IpAddr>> {
+    let deadline = Instant::now() + timeout;
+    let hostname = c_string!(host);
+    let mut context = Box::new(HostContext::default());
+    let mut service = ManagedDNSServiceRef::default();
+
+    service.get_address_info(
+        GetAddressInfoParams::builder()
+            .flags(0)
+            .interface_index(constants::BONJOUR_IF_UNSPEC)
+            .protocol(address_protocol(AddressFamily::Any))
+            .hostname(hostname.as_ptr())
+            .callback(Some(host_address_callback))
+            .context(context.as_raw())
+            .build()?,
+    )?;
+
+    // `done` is set by the callback, through the pointer passed as context
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if context.done || unsafe { ffi::macos::read_select(service.sock_fd(), remaining)? } == 0 {
+            break;
+        }
+
+        service.process_result()?;
+    }
+
+    if let Some(e) = context.error.take() {
+        Err(e)
+    } else if context.addresses.is_empty() {
+        Err(Error::with_kind(
+            ErrorKind::TimedOut,
+            format!("timed out resolving host `{}`", host),
+        ))
+    } else {
+        Ok(context.addresses)
+    }
+}
+
+#[derive(Default, FromRaw, AsRaw)]
+struct HostContext {
+    addresses: Vec<IpAddr>,
+    error: Option<Error>,
+    done: bool,
+}
+
+unsafe extern "C" fn host_address_callback(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    _hostname: *const c_char,
+    address: *const bonjour_sys::sockaddr,
+    _ttl: u32,
+    context: *mut c_void,
+) {
+    let context = HostContext::from_raw(context);
+
+    if error != 0 {
+        context.error = Some(
+            Error::from(format!(
+                "host_address_callback() reported error (code: {})",
+                error
+            ))
+            .with_raw_code(error),
+        );
+        context.done = true;
+        return;
+    }
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd != 0 {
+        let address = sockaddr_to_ip(address);
+        if !context.addresses.contains(&address) {
+            context.addresses.push(address);
+        }
+    }
+
+    context.done = flags & bonjour_sys::kDNSServiceFlagsMoreComing == 0;
+}
+
+/// Returns `true` if `error` is a temporary condition that does not invalidate the operation it
+/// was reported for, so that processing its results may simply continue.
+///
+/// These are `kDNSServiceErr_Transient`, `kDNSServiceErr_Timeout` and `kDNSServiceErr_NoRouter`
+/// (no network is available yet). Any other error is fatal.
+pub fn is_transient_error(error: DNSServiceErrorType) -> bool {
+    matches!(
+        error,
+        bonjour_sys::kDNSServiceErr_Transient
+            | bonjour_sys::kDNSServiceErr_Timeout
+            | bonjour_sys::kDNSServiceErr_NoRouter
+    )
+}
@@ -1,46 +1,134 @@
 //! Bonjour implementation for cross-platform browser
 
 use super::service_ref::{
-    BrowseServicesParams, GetAddressInfoParams, ManagedDNSServiceRef, ServiceResolveParams,
+    BrowseServicesParams, EnumerateDomainsParams, GetAddressInfoParams, ManagedDNSServiceRef,
+    ServiceResolveParams,
 };
 use super::txt_record_ref::ManagedTXTRecordRef;
 use super::{bonjour_util, constants};
-use crate::ffi::{c_str, AsRaw, FromRaw};
+use crate::browser::{self, InstanceStatsTable, TResolveHandle};
+use crate::error::{Error, ErrorKind};
+use crate::ffi::c_str;
+use crate::ffi::{self, AsRaw, FromRaw, UnwrapOrNull};
 use crate::prelude::*;
-use crate::{EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
-use crate::{ServiceDiscoveredCallback, ServiceDiscovery};
-use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
-use libc::{c_char, c_uchar, c_void, sockaddr_in};
+use crate::txt_record;
+use crate::{AddressFamily, EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
+use crate::{
+    InstanceStats, ResolvedRecord, ServiceDiscoveredCallback, ServiceDiscovery, ServiceEvent,
+    ServiceIter, ServiceRemoval, ServiceRemovedCallback,
+};
+use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceProtocol, DNSServiceRef};
+use libc::{c_char, c_uchar, c_void};
 use std::any::Any;
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::fmt::{self, Formatter};
 use std::net::IpAddr;
-use std::ptr;
+use std::ops::ControlFlow;
+use std::slice;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct BonjourMdnsBrowser {
     service: Arc<Mutex<ManagedDNSServiceRef>>,
     kind: CString,
     interface_index: u32,
+    local_only: bool,
+    domains: Vec<CString>,
+    domain_browsers: Vec<ManagedDNSServiceRef>,
+    wide_area: bool,
+    force_multicast: bool,
+    browsing: bool,
+    paused: bool,
     context: *mut BonjourBrowserContext,
 }
 
 impl TMdnsBrowser for BonjourMdnsBrowser {
+    type EventLoop<'a> = EventLoop<'a>;
+    type ResolveHandle = BonjourResolveHandle;
+
     fn new(service_type: ServiceType) -> Self {
-        Self {
+        let domain = service_type.explicit_domain().map(|d| d.to_string());
+
+        let mut browser = Self {
             service: Arc::default(),
-            kind: c_string!(service_type.to_string()),
+            kind: c_string!(service_type.as_registration_string()),
             interface_index: constants::BONJOUR_IF_UNSPEC,
+            local_only: false,
+            domains: vec![],
+            domain_browsers: vec![],
+            wide_area: false,
+            force_multicast: false,
+            browsing: false,
+            paused: false,
             context: Box::into_raw(Box::default()),
+        };
+
+        if let Some(domain) = domain {
+            browser.set_domains(vec![domain]);
         }
+
+        browser
     }
 
     fn set_network_interface(&mut self, interface: NetworkInterface) {
         self.interface_index = bonjour_util::interface_index(interface);
     }
 
+    fn set_ignore_local(&mut self, ignore_local: bool) {
+        unsafe { (*self.context).ignore_local = ignore_local };
+    }
+
+    fn set_local_only(&mut self, local_only: bool) {
+        self.local_only = local_only;
+    }
+
+    fn set_address_family(&mut self, family: AddressFamily) {
+        unsafe { (*self.context).address_protocol = bonjour_util::address_protocol(family) };
+    }
+
+    fn set_wide_area(&mut self, domain: &str) {
+        self.domains = vec![c_string!(domain)];
+        self.wide_area = true;
+        self.update_lookup_flags();
+    }
+
+    /// Resolution of discovered services already forces multicast by default; this additionally
+    /// applies `kDNSServiceFlagsForceMulticast` to the browse operation itself.
+    fn set_force_multicast(&mut self, force_multicast: bool) {
+        self.force_multicast = force_multicast;
+        self.update_lookup_flags();
+    }
+
+    fn set_resolve_address(&mut self, resolve_address: bool) {
+        unsafe { (*self.context).resolve_address = resolve_address };
+    }
+
+    fn set_domains(&mut self, domains: Vec<String>) {
+        self.domains = domains.iter().map(|d| c_string!(d.as_str())).collect();
+        self.wide_area = false;
+        self.update_lookup_flags();
+    }
+
+    fn set_max_duration(&mut self, max_duration: Duration) {
+        unsafe { (*self.context).max_duration = Some(max_duration) };
+    }
+
+    /// Does nothing, as each service is resolved before the next browse result is processed.
+    fn set_max_concurrent_resolves(&mut self, _max: usize) {}
+
+    fn set_name_prefix(&mut self, prefix: &str) {
+        let prefix = Some(prefix.to_string()).filter(|p| !p.is_empty());
+        unsafe { (*self.context).name_prefix = prefix };
+    }
+
+    fn set_refresh_interval(&mut self, interval: Duration) {
+        unsafe { (*self.context).refresh_interval = Some(interval) };
+    }
+
     fn set_service_discovered_callback(
         &mut self,
         service_discovered_callback: Box<ServiceDiscoveredCallback>,
@@ -48,6 +136,13 @@ impl TMdnsBrowser for BonjourMdnsBrowser {
         unsafe { (*self.context).service_discovered_callback = Some(service_discovered_callback) };
     }
 
+    fn set_service_removed_callback(
+        &mut self,
+        service_removed_callback: Box<ServiceRemovedCallback>,
+    ) {
+        unsafe { (*self.context).service_removed_callback = Some(service_removed_callback) };
+    }
+
     fn set_context(&mut self, context: Box<dyn Any>) {
         unsafe { (*self.context).user_context = Some(Arc::from(context)) };
     }
@@ -55,46 +150,548 @@ impl TMdnsBrowser for BonjourMdnsBrowser {
     fn browse_services(&mut self) -> Result<EventLoop> {
         debug!("Browsing services: {:?}", self);
 
-        self.service.lock().unwrap().browse_services(
-            BrowseServicesParams::builder()
-                .flags(0)
-                .interface_index(self.interface_index)
-                .regtype(self.kind.as_ptr())
-                .domain(ptr::null_mut())
-                .callback(Some(browse_callback))
-                .context(self.context as *mut c_void)
+        browser::check_force_multicast(self.wide_area, self.force_multicast)?;
+
+        unsafe {
+            let ctx = &mut *self.context;
+            ctx.stopped.store(false, Ordering::SeqCst);
+            ctx.expires_at = ctx.max_duration.map(|d| Instant::now() + d);
+            ctx.expired = false;
+            ctx.next_refresh_at = None;
+        }
+
+        self.paused = false;
+        self.start_browse()?;
+
+        Ok(self.event_loop())
+    }
+
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        if !self.browsing {
+            return Err("browser is not browsing".into());
+        }
+
+        self.event_loop().poll(timeout)
+    }
+
+    /// Deallocates the underlying `DNSServiceRef`, which stops the browse operation.
+    fn pause(&mut self) {
+        if self.browsing {
+            self.domain_browsers.clear();
+            *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+            self.browsing = false;
+            self.paused = true;
+        }
+    }
+
+    /// Starts a new browse operation, which reports every service that is still present again.
+    fn resume(&mut self) -> Result<EventLoop<'_>> {
+        if !self.paused {
+            return Err("browser is not paused".into());
+        }
+
+        unsafe {
+            (*self.context).stopped.store(false, Ordering::SeqCst);
+            (*self.context).next_refresh_at = None;
+        }
+
+        self.start_browse()?;
+        self.paused = false;
+
+        Ok(self.event_loop())
+    }
+
+    /// Deallocates the underlying `DNSServiceRef`, which also closes its connection to the
+    /// daemon.
+    fn close(&mut self) -> Result<()> {
+        self.domain_browsers.clear();
+        *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+        self.browsing = false;
+        self.paused = false;
+
+        let ctx = unsafe { &mut *self.context };
+        ctx.stopped.store(true, Ordering::SeqCst);
+        ctx.services.clear();
+        ctx.stats.clear();
+
+        Ok(())
+    }
+
+    /// Re-resolves the previously discovered service with the specified `name`, on every
+    /// interface and domain it was found on.
+    ///
+    /// Like the initial resolution, this is performed synchronously; the callback is invoked
+    /// before this function returns.
+    fn refresh(&mut self, name: &str) -> Result<()> {
+        let ctx = unsafe { &mut *self.context };
+
+        let services = ctx
+            .services
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no discovered service named `{}`", name))?;
+
+        ctx.more_coming = false;
+
+        for service in services {
+            unsafe {
+                resolve(
+                    ctx,
+                    service.interface_index,
+                    service.name.as_ptr(),
+                    service.kind.as_ptr(),
+                    service.domain.as_ptr(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn instance_stats(&self, name: &str) -> Option<InstanceStats> {
+        unsafe { (*self.context).stats.get(name) }
+    }
+
+    fn resolve(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+    ) -> Result<ServiceDiscovery> {
+        browser::resolve_blocking::<Self>(name, service_type, timeout)
+    }
+
+    /// Starts `DNSServiceResolve()` on its own connection to the daemon, which is deallocated
+    /// once the resolve completes or is cancelled. The host's address is looked up while
+    /// processing the resolve reply, within the same `timeout`.
+    fn resolve_start(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+        mut callback: Box<ServiceDiscoveredCallback>,
+    ) -> Result<BonjourResolveHandle> {
+        let deadline = Instant::now() + timeout;
+
+        let mut context = Box::new(BonjourBrowserContext::default());
+        context.deadline = Some(deadline);
+        // the resolve is complete once the callback has been invoked
+        context.service_discovered_callback = Some(Box::new(move |r, c| {
+            let _ = callback(r, c);
+            ControlFlow::Break(())
+        }));
+
+        let c_name = c_string!(name);
+        let kind = c_string!(service_type.as_registration_string());
+        let domain = c_string!(service_type.explicit_domain().unwrap_or("local."));
+
+        let service = unsafe {
+            start_resolve(
+                &mut context,
+                constants::BONJOUR_IF_UNSPEC,
+                c_name.as_ptr(),
+                kind.as_ptr(),
+                domain.as_ptr(),
+            )?
+        };
+
+        Ok(BonjourResolveHandle {
+            service: Some(service),
+            context,
+            deadline,
+            name: name.to_string(),
+        })
+    }
+
+    /// Replies are processed until one arrives without `kDNSServiceFlagsMoreComing`, which
+    /// indicates the daemon has reported every domain it currently knows of.
+    fn browse_domains(timeout: Duration) -> Result<Vec<String>> {
+        let deadline = Instant::now() + timeout;
+        let mut ctx = Box::new(BonjourDomainContext::default());
+        let mut service = ManagedDNSServiceRef::default();
+
+        service.enumerate_domains(
+            EnumerateDomainsParams::builder()
+                .flags(bonjour_sys::kDNSServiceFlagsBrowseDomains)
+                .interface_index(constants::BONJOUR_IF_UNSPEC)
+                .callback(Some(enumerate_domains_callback))
+                .context(ctx.as_raw())
                 .build()?,
         )?;
 
-        Ok(EventLoop::new(self.service.clone()))
+        // `done` is set by the callback, through the pointer passed as context
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if ctx.done || unsafe { ffi::macos::read_select(service.sock_fd(), remaining)? } == 0 {
+                break;
+            }
+
+            service.process_result()?;
+        }
+
+        match ctx.error.take() {
+            Some(e) => Err(e),
+            None => Ok(ctx.domains),
+        }
+    }
+}
+
+impl BonjourMdnsBrowser {
+    /// Browses the default domain or a single configured domain directly on `self.service`.
+    /// Several domains are browsed on a shared connection held by `self.service`, so that the
+    /// `EventLoop` handles the replies of every domain.
+    fn start_browse(&mut self) -> Result<()> {
+        let mut service = self.service.lock().unwrap();
+
+        self.domain_browsers.clear();
+        *service = ManagedDNSServiceRef::default();
+
+        if self.domains.len() <= 1 {
+            service.browse_services(self.browse_params(self.domains.first())?)?;
+        } else {
+            service.create_connection()?;
+
+            for domain in &self.domains {
+                self.domain_browsers
+                    .push(ManagedDNSServiceRef::browse_services_shared(
+                        &service,
+                        self.browse_params(Some(domain))?,
+                    )?);
+            }
+        }
+
+        self.browsing = true;
+
+        Ok(())
+    }
+
+    /// Returns an `EventLoop` on `self.service` that also ends the browse once the maximum
+    /// duration has elapsed, and refreshes discovered services at the refresh interval.
+    fn event_loop(&self) -> EventLoop<'_> {
+        let ctx = self.context;
+        EventLoop::new(self.service.clone(), unsafe { (*ctx).stopped.clone() }).with_after_poll(
+            move || unsafe {
+                (*ctx).expire_if_due();
+                (*ctx).refresh_if_due();
+            },
+        )
+    }
+
+    /// Resolves discovered services via multicast unless other domains are browsed, in which
+    /// case they may need to be resolved via unicast DNS, or multicast is forced.
+    fn update_lookup_flags(&mut self) {
+        let force_multicast = self.force_multicast || self.domains.is_empty();
+
+        unsafe {
+            (*self.context).lookup_flags = if force_multicast {
+                bonjour_sys::kDNSServiceFlagsForceMulticast
+            } else {
+                0
+            }
+        };
+    }
+
+    fn browse_params(&self, domain: Option<&CString>) -> Result<BrowseServicesParams> {
+        Ok(BrowseServicesParams::builder()
+            .flags(if self.force_multicast {
+                bonjour_sys::kDNSServiceFlagsForceMulticast
+            } else {
+                0
+            })
+            .interface_index(if self.local_only {
+                constants::BONJOUR_IF_LOCAL_ONLY
+            } else {
+                self.interface_index
+            })
+            .regtype(self.kind.as_ptr())
+            .domain(domain.map(|d| d.as_ptr()).unwrap_or_null())
+            .callback(Some(browse_callback))
+            .context(self.context as *mut c_void)
+            .build()?)
+    }
+}
+
+/// Bonjour implementation of [`TResolveHandle`], returned by
+/// `BonjourMdnsBrowser::resolve_start()`.
+///
+/// [`TResolveHandle`]: ../../browser/trait.TResolveHandle.html
+#[derive(Debug)]
+pub struct BonjourResolveHandle {
+    // the service must be deallocated before the context its callback refers to
+    service: Option<ManagedDNSServiceRef>,
+    context: Box<BonjourBrowserContext>,
+    deadline: Instant,
+    name: String,
+}
+
+impl TResolveHandle for BonjourResolveHandle {
+    fn poll(&mut self, timeout: Duration) -> Result<()> {
+        let service = match &self.service {
+            Some(service) => service,
+            None => return Ok(()),
+        };
+
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            self.context.deliver(Err(Error::with_kind(
+                ErrorKind::TimedOut,
+                format!("timed out resolving service `{}`", self.name),
+            )));
+        } else if unsafe { ffi::macos::read_select(service.sock_fd(), timeout.min(remaining))? } > 0
+        {
+            if let Err(e) = service.process_result() {
+                self.context.invoke_callback(Err(e));
+            }
+        }
+
+        if self.context.stopped.load(Ordering::SeqCst) {
+            self.service = None;
+        }
+
+        Ok(())
+    }
+
+    /// Deallocates the `DNSServiceRef`, which stops the resolve.
+    fn cancel(&mut self) {
+        self.service = None;
+    }
+
+    fn is_done(&self) -> bool {
+        self.service.is_none()
+    }
+}
+
+impl IntoIterator for BonjourMdnsBrowser {
+    type Item = Result<ServiceEvent>;
+    type IntoIter = ServiceIter<Self>;
+
+    /// Starts browsing and returns a blocking iterator over the discovered services.
+    fn into_iter(self) -> ServiceIter<Self> {
+        ServiceIter::new(self)
     }
 }
 
 impl Drop for BonjourMdnsBrowser {
+    /// Deallocates every `DNSServiceRef` before freeing the context passed to their callbacks.
     fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
+        // operations sharing the connection must be freed before it
+        self.domain_browsers.clear();
+        *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+        unsafe { drop(Box::from_raw(self.context)) };
     }
 }
 
-#[derive(Default, FromRaw, AsRaw)]
+#[derive(FromRaw, AsRaw)]
 struct BonjourBrowserContext {
+    lookup_flags: DNSServiceFlags,
+    address_protocol: DNSServiceProtocol,
+    resolve_address: bool,
+    ignore_local: bool,
+    name_prefix: Option<String>,
+    stats: InstanceStatsTable,
+    // Bonjour reports an instance once for every interface and domain it is found on
+    services: HashMap<String, Vec<BrowsedService>>,
     service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    service_removed_callback: Option<Box<ServiceRemovedCallback>>,
     resolved_name: Option<String>,
     resolved_kind: Option<String>,
     resolved_domain: Option<String>,
     resolved_port: u16,
     resolved_txt: Option<TxtRecord>,
+    resolved_interface_index: u32,
+    resolved_records: Vec<ResolvedRecord>,
+    resolved_addresses: Vec<IpAddr>,
+    more_coming: bool,
+    deadline: Option<Instant>,
+    max_duration: Option<Duration>,
+    expires_at: Option<Instant>,
+    expired: bool,
+    refresh_interval: Option<Duration>,
+    next_refresh_at: Option<Instant>,
     user_context: Option<Arc<dyn Any>>,
+    stopped: Arc<AtomicBool>,
 }
 
 impl BonjourBrowserContext {
-    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
-        if let Some(f) = &self.service_discovered_callback {
-            f(result, self.user_context.clone());
+    fn invoke_callback(&mut self, result: Result<ServiceDiscovery>) {
+        if let Ok(service) = &result {
+            self.stats.record_resolved(service.name());
+        }
+
+        if self.ignore_local && matches!(&result, Ok(s) if s.is_registered_by_this_process()) {
+            debug!("Ignoring service registered by this process");
+            return;
+        }
+
+        if !self.expire_if_due() {
+            self.deliver(result);
+        }
+    }
+
+    /// Ends the browse if the maximum duration has elapsed, notifying the callback the first
+    /// time. Returns `true` if the browse has ended.
+    fn expire_if_due(&mut self) -> bool {
+        if !self.expired && matches!(self.expires_at, Some(at) if Instant::now() >= at) {
+            self.expired = true;
+            self.deliver(Err(Error::with_kind(
+                ErrorKind::TimedOut,
+                "browser reached its maximum duration",
+            )));
+        }
+
+        self.expired
+    }
+
+    /// Resolves every discovered service again if the refresh interval has elapsed since the
+    /// previous refresh, or since browsing started. Like the initial resolution, each service is
+    /// resolved synchronously.
+    unsafe fn refresh_if_due(&mut self) {
+        let interval = match self.refresh_interval {
+            Some(interval) if !self.expired && !self.stopped.load(Ordering::SeqCst) => interval,
+            _ => return,
+        };
+
+        let now = Instant::now();
+        match self.next_refresh_at {
+            Some(at) if now < at => return,
+            Some(_) => {}
+            None => {
+                self.next_refresh_at = Some(now + interval);
+                return;
+            }
+        }
+
+        self.next_refresh_at = Some(now + interval);
+        self.more_coming = false;
+
+        let services: Vec<BrowsedService> = self.services.values().flatten().cloned().collect();
+        for service in services {
+            let result = resolve(
+                self,
+                service.interface_index,
+                service.name.as_ptr(),
+                service.kind.as_ptr(),
+                service.domain.as_ptr(),
+            );
+
+            if let Err(e) = result {
+                self.invoke_callback(Err(e));
+            }
+        }
+    }
+
+    fn deliver(&mut self, result: Result<ServiceDiscovery>) {
+        if let Some(f) = &mut self.service_discovered_callback {
+            if let ControlFlow::Break(()) = f(result, self.user_context.clone()) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
         } else {
             warn!("attempted to invoke callback but none was set");
         }
     }
+
+    /// Records a copy of a service reported by a browser. Returns `true` if it is the first copy
+    /// of the instance, i.e. the instance has just been announced.
+    fn add_service(&mut self, service: BrowsedService) -> bool {
+        let name = service.name.to_string_lossy().into_owned();
+        let copies = self.services.entry(name).or_default();
+        let is_new = copies.is_empty();
+
+        if !copies.contains(&service) {
+            copies.push(service);
+        }
+
+        is_new
+    }
+
+    /// Forgets a copy of a service reported as removed by a browser, and reports the removal of
+    /// the instance once its last copy in that domain is gone.
+    fn remove_service(&mut self, service: &BrowsedService) -> Result<()> {
+        // the names were checked to be UTF-8 when the service was added
+        let name = service.name.to_string_lossy();
+
+        let copies = match self.services.get_mut(name.as_ref()) {
+            Some(copies) if copies.contains(service) => copies,
+            _ => return Ok(()),
+        };
+
+        copies.retain(|s| s != service);
+        let in_domain = copies.iter().any(|s| s.domain == service.domain);
+
+        if copies.is_empty() {
+            self.services.remove(name.as_ref());
+            self.stats.record_removed(&name);
+        }
+
+        if in_domain {
+            Ok(())
+        } else {
+            self.invoke_removed(service)
+        }
+    }
+
+    /// Passes the removal of a previously discovered service to the removed callback, if set.
+    fn invoke_removed(&mut self, service: &BrowsedService) -> Result<()> {
+        // the names were checked to be UTF-8 when the service was added
+        let kind = bonjour_util::normalize_domain(&service.kind.to_string_lossy());
+
+        let removal = ServiceRemoval::builder()
+            .name(service.name.to_string_lossy().into_owned())
+            .service_type(ServiceType::from_str(&kind)?)
+            .domain(bonjour_util::normalize_domain(
+                &service.domain.to_string_lossy(),
+            ))
+            .build()?;
+
+        if let Some(f) = &mut self.service_removed_callback {
+            if let ControlFlow::Break(()) = f(removal, self.user_context.clone()) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for BonjourBrowserContext {
+    fn default() -> Self {
+        Self {
+            lookup_flags: bonjour_sys::kDNSServiceFlagsForceMulticast,
+            address_protocol: bonjour_util::address_protocol(AddressFamily::Any),
+            resolve_address: true,
+            ignore_local: false,
+            name_prefix: None,
+            stats: InstanceStatsTable::default(),
+            services: HashMap::new(),
+            service_discovered_callback: None,
+            service_removed_callback: None,
+            resolved_name: None,
+            resolved_kind: None,
+            resolved_domain: None,
+            resolved_port: 0,
+            resolved_txt: None,
+            resolved_interface_index: constants::BONJOUR_IF_UNSPEC,
+            resolved_records: vec![],
+            resolved_addresses: vec![],
+            more_coming: false,
+            deadline: None,
+            max_duration: None,
+            expires_at: None,
+            expired: false,
+            refresh_interval: None,
+            next_refresh_at: None,
+            user_context: None,
+            stopped: Arc::default(),
+        }
+    }
+}
+
+/// A service instance reported by the browser on one interface and domain, kept so it can be
+/// resolved again later.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BrowsedService {
+    interface_index: u32,
+    name: CString,
+    kind: CString,
+    domain: CString,
 }
 
 impl fmt::Debug for BonjourBrowserContext {
@@ -104,13 +701,14 @@ impl fmt::Debug for BonjourBrowserContext {
             .field("resolved_kind", &self.resolved_kind)
             .field("resolved_domain", &self.resolved_domain)
             .field("resolved_port", &self.resolved_port)
+            .field("services", &self.services)
             .finish()
     }
 }
 
 unsafe extern "C" fn browse_callback(
     _sd_ref: DNSServiceRef,
-    _flags: DNSServiceFlags,
+    flags: DNSServiceFlags,
     interface_index: u32,
     error: DNSServiceErrorType,
     name: *const c_char,
@@ -119,13 +717,67 @@ unsafe extern "C" fn browse_callback(
     context: *mut c_void,
 ) {
     let ctx = BonjourBrowserContext::from_raw(context);
-    if let Err(e) = handle_browse(ctx, error, name, regtype, domain, interface_index) {
+    if ctx.expire_if_due() {
+        return;
+    }
+
+    if let Err(e) = handle_browse(ctx, flags, error, name, regtype, domain, interface_index) {
         ctx.invoke_callback(Err(e));
     }
 }
 
+#[derive(Default, FromRaw, AsRaw)]
+struct BonjourDomainContext {
+    domains: Vec<String>,
+    error: Option<Error>,
+    done: bool,
+}
+
+unsafe extern "C" fn enumerate_domains_callback(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    domain: *const c_char,
+    context: *mut c_void,
+) {
+    let ctx = BonjourDomainContext::from_raw(context);
+
+    if error != 0 {
+        ctx.error = Some(
+            Error::from(format!(
+                "enumerate_domains_callback() reported error (code: {})",
+                error
+            ))
+            .with_raw_code(error),
+        );
+        ctx.done = true;
+        return;
+    }
+
+    let domain = match c_str::try_raw_to_str(domain) {
+        Ok(domain) => domain,
+        Err(e) => {
+            ctx.error = Some(e);
+            ctx.done = true;
+            return;
+        }
+    };
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd != 0 {
+        if !ctx.domains.iter().any(|d| d == domain) {
+            ctx.domains.push(domain.to_string());
+        }
+    } else {
+        ctx.domains.retain(|d| d != domain);
+    }
+
+    ctx.done = flags & bonjour_sys::kDNSServiceFlagsMoreComing == 0;
+}
+
 unsafe fn handle_browse(
     ctx: &mut BonjourBrowserContext,
+    flags: DNSServiceFlags,
     error: DNSServiceErrorType,
     name: *const c_char,
     regtype: *const c_char,
@@ -133,16 +785,87 @@ unsafe fn handle_browse(
     interface_index: u32,
 ) -> Result<()> {
     if error != 0 {
-        return Err(format!("browse_callback() reported error (code: {})", error).into());
+        return Err(Error::from(format!(
+            "browse_callback() reported error (code: {})",
+            error
+        ))
+        .with_raw_code(error));
+    }
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd == 0 {
+        // services with malformed names were never added
+        return match browsed_service(interface_index, name, regtype, domain) {
+            Ok(service) => ctx.remove_service(&service),
+            Err(_) => Ok(()),
+        };
+    }
+
+    ctx.more_coming = flags & bonjour_sys::kDNSServiceFlagsMoreComing != 0;
+
+    let instance = c_str::try_raw_to_str(name)?;
+    if !browser::matches_name_prefix(instance, ctx.name_prefix.as_deref()) {
+        debug!(
+            "Skipping service `{}` not matching the name prefix",
+            instance
+        );
+        return Ok(());
+    }
+
+    let service = browsed_service(interface_index, name, regtype, domain)?;
+
+    if ctx.add_service(service) {
+        ctx.stats.record_seen(instance);
     }
 
+    resolve(ctx, interface_index, name, regtype, domain)
+}
+
+unsafe fn browsed_service(
+    interface_index: u32,
+    name: *const c_char,
+    regtype: *const c_char,
+    domain: *const c_char,
+) -> Result<BrowsedService> {
+    Ok(BrowsedService {
+        interface_index,
+        name: c_string!(c_str::try_raw_to_str(name)?),
+        kind: c_string!(c_str::try_raw_to_str(regtype)?),
+        domain: c_string!(c_str::try_raw_to_str(domain)?),
+    })
+}
+
+unsafe fn resolve(
+    ctx: &mut BonjourBrowserContext,
+    interface_index: u32,
+    name: *const c_char,
+    regtype: *const c_char,
+    domain: *const c_char,
+) -> Result<()> {
+    let service = start_resolve(ctx, interface_index, name, regtype, domain)?;
+
+    process_result(&service, ctx.deadline, || {
+        format!("timed out resolving service `{}`", c_str::raw_to_str(name))
+    })
+}
+
+/// Starts resolving the specified service, returning the `DNSServiceRef` to process the reply on.
+unsafe fn start_resolve(
+    ctx: &mut BonjourBrowserContext,
+    interface_index: u32,
+    name: *const c_char,
+    regtype: *const c_char,
+    domain: *const c_char,
+) -> Result<ManagedDNSServiceRef> {
     ctx.resolved_name = Some(c_str::copy_raw(name));
     ctx.resolved_kind = Some(c_str::copy_raw(regtype));
     ctx.resolved_domain = Some(c_str::copy_raw(domain));
+    ctx.resolved_interface_index = interface_index;
 
-    ManagedDNSServiceRef::default().resolve_service(
+    let mut service = ManagedDNSServiceRef::default();
+
+    service.resolve_service(
         ServiceResolveParams::builder()
-            .flags(bonjour_sys::kDNSServiceFlagsForceMulticast)
+            .flags(ctx.lookup_flags)
             .interface_index(interface_index)
             .name(name)
             .regtype(regtype)
@@ -150,7 +873,26 @@ unsafe fn handle_browse(
             .callback(Some(resolve_callback))
             .context(ctx.as_raw())
             .build()?,
-    )
+    )?;
+
+    Ok(service)
+}
+
+/// Processes the next result of `service`. If a `deadline` is set and passes before a result is
+/// available, returns the error produced by `timeout_err` instead.
+unsafe fn process_result(
+    service: &ManagedDNSServiceRef,
+    deadline: Option<Instant>,
+    timeout_err: impl FnOnce() -> String,
+) -> Result<()> {
+    if let Some(deadline) = deadline {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if ffi::macos::read_select(service.sock_fd(), remaining)? == 0 {
+            return Err(timeout_err().into());
+        }
+    }
+
+    service.process_result()
 }
 
 unsafe extern "C" fn resolve_callback(
@@ -158,7 +900,7 @@ unsafe extern "C" fn resolve_callback(
     _flags: DNSServiceFlags,
     interface_index: u32,
     error: DNSServiceErrorType,
-    _fullname: *const c_char,
+    fullname: *const c_char,
     host_target: *const c_char,
     port: u16,
     txt_len: u16,
@@ -170,6 +912,7 @@ unsafe extern "C" fn resolve_callback(
     let result = handle_resolve(
         ctx,
         error,
+        fullname,
         port,
         interface_index,
         host_target,
@@ -182,9 +925,11 @@ unsafe extern "C" fn resolve_callback(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 unsafe fn handle_resolve(
     ctx: &mut BonjourBrowserContext,
     error: DNSServiceErrorType,
+    fullname: *const c_char,
     port: u16,
     interface_index: u32,
     host_target: *const c_char,
@@ -192,11 +937,31 @@ unsafe fn handle_resolve(
     txt_record: *const c_uchar,
 ) -> Result<()> {
     if error != 0 {
-        return Err(format!("error reported by resolve_callback: (code: {})", error).into());
+        return Err(Error::from(format!(
+            "error reported by resolve_callback: (code: {})",
+            error
+        ))
+        .with_raw_code(error));
     }
 
     ctx.resolved_port = port;
 
+    let fullname = c_str::try_raw_to_str(fullname)?;
+    let host_name = c_str::try_raw_to_str(host_target)?;
+    let txt = if txt_record.is_null() {
+        &[][..]
+    } else {
+        slice::from_raw_parts(txt_record, txt_len as usize)
+    };
+
+    // the TXT record is parsed by Bonjour, which skips truncated entries rather than failing
+    txt_record::check_received(txt_record::split_wire_format(txt)?.into_iter())?;
+
+    ctx.resolved_records = vec![
+        ResolvedRecord::srv(fullname, u16::from_be(port), host_name, None),
+        ResolvedRecord::txt(fullname, txt, None),
+    ];
+
     ctx.resolved_txt = if txt_len > 1 {
         Some(TxtRecord::from(ManagedTXTRecordRef::clone_raw(
             txt_record, txt_len,
@@ -205,66 +970,104 @@ unsafe fn handle_resolve(
         None
     };
 
-    ManagedDNSServiceRef::default().get_address_info(
+    if !ctx.resolve_address {
+        return deliver_resolved(ctx, host_name.to_string());
+    }
+
+    let deadline = ctx.deadline;
+    let mut service = ManagedDNSServiceRef::default();
+
+    service.get_address_info(
         GetAddressInfoParams::builder()
-            .flags(bonjour_sys::kDNSServiceFlagsForceMulticast)
+            .flags(ctx.lookup_flags)
             .interface_index(interface_index)
-            .protocol(1)
+            .protocol(ctx.address_protocol)
             .hostname(host_target)
             .callback(Some(get_address_info_callback))
             .context(ctx.as_raw())
             .build()?,
-    )
+    )?;
+
+    ctx.resolved_addresses.clear();
+
+    // every address of the host is collected before the service is delivered
+    while ctx.resolved_name.is_some() {
+        process_result(&service, deadline, || {
+            format!("timed out looking up address of `{}`", host_name)
+        })?;
+    }
+
+    Ok(())
 }
 
 unsafe extern "C" fn get_address_info_callback(
     _sd_ref: DNSServiceRef,
-    _flags: DNSServiceFlags,
+    flags: DNSServiceFlags,
     _interface_index: u32,
     error: DNSServiceErrorType,
     hostname: *const c_char,
     address: *const bonjour_sys::sockaddr,
-    _ttl: u32,
+    ttl: u32,
     context: *mut c_void,
 ) {
     let ctx = BonjourBrowserContext::from_raw(context);
-    if let Err(e) = handle_get_address_info(ctx, error, address, hostname) {
+    if let Err(e) = handle_get_address_info(ctx, flags, error, address, hostname, ttl) {
+        // the service is not delivered, so stop waiting for more addresses
+        ctx.resolved_name = None;
         ctx.invoke_callback(Err(e));
     }
 }
 
 unsafe fn handle_get_address_info(
     ctx: &mut BonjourBrowserContext,
+    flags: DNSServiceFlags,
     error: DNSServiceErrorType,
     address: *const bonjour_sys::sockaddr,
     hostname: *const c_char,
+    ttl: u32,
 ) -> Result<()> {
-    // this callback runs multiple times for some reason
+    // replies may still arrive after the service has been delivered
     if ctx.resolved_name.is_none() {
         return Ok(());
     }
 
     if error != 0 {
-        return Err(format!(
+        return Err(Error::from(format!(
             "get_address_info_callback() reported error (code: {})",
             error
-        )
-        .into());
+        ))
+        .with_raw_code(error));
+    }
+
+    let hostname = c_str::try_raw_to_str(hostname)?;
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd != 0 {
+        let ip = bonjour_util::sockaddr_to_ip(address);
+        ctx.resolved_records
+            .push(ResolvedRecord::address(hostname, ip, Some(ttl)));
+        if !ctx.resolved_addresses.contains(&ip) {
+            ctx.resolved_addresses.push(ip);
+        }
     }
 
+    // the replies for the other addresses already known to the daemon follow immediately
+    if flags & bonjour_sys::kDNSServiceFlagsMoreComing != 0 {
+        return Ok(());
+    }
+
+    deliver_resolved(ctx, hostname.to_string())
+}
+
+/// Invokes the callback with the service resolved so far, at the specified `hostname` and the
+/// addresses collected for it.
+unsafe fn deliver_resolved(ctx: &mut BonjourBrowserContext, hostname: String) -> Result<()> {
     // on macOS the bytes are swapped for the port
     let port: u16 = ctx.resolved_port.to_be();
 
-    // on macOS the bytes are swapped for the ip
-    let ip = {
-        let address = address as *const sockaddr_in;
-        assert_not_null!(address);
-        let s_addr = (*address).sin_addr.s_addr.to_le_bytes();
-        IpAddr::from(s_addr).to_string()
-    };
-
-    let hostname = c_str::copy_raw(hostname);
+    let is_local = ctx.resolved_interface_index == constants::BONJOUR_IF_LOCAL_ONLY
+        || bonjour_util::is_local_host_name(&hostname);
     let domain = bonjour_util::normalize_domain(&ctx.resolved_domain.take().unwrap());
+    let is_wide_area = !domain.eq_ignore_ascii_case("local");
     let kind = bonjour_util::normalize_domain(&ctx.resolved_kind.take().unwrap());
 
     let result = ServiceDiscovery::builder()
@@ -272,9 +1075,20 @@ unsafe fn handle_get_address_info(
         .service_type(ServiceType::from_str(&kind)?)
         .domain(domain)
         .host_name(hostname)
-        .address(ip)
+        .address(
+            ctx.resolved_addresses
+                .first()
+                .map(|ip| ip.to_string())
+                .unwrap_or_default(),
+        )
+        .addresses(std::mem::take(&mut ctx.resolved_addresses))
         .port(port)
         .txt(ctx.resolved_txt.take())
+        .is_local(is_local)
+        .is_wide_area(is_wide_area)
+        .interface_index(ctx.resolved_interface_index)
+        .more_coming(ctx.more_coming)
+        .records(std::mem::take(&mut ctx.resolved_records))
         .build()
         .expect("could not build ServiceResolution");
 
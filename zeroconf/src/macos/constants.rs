@@ -1,4 +1,8 @@
 use bonjour_sys::DNSServiceFlags;
 
 pub const BONJOUR_IF_UNSPEC: u32 = 0;
+/// `kDNSServiceInterfaceIndexLocalOnly`, which is a macro and absent from the bindings
+pub const BONJOUR_IF_LOCAL_ONLY: u32 = u32::MAX;
 pub const BONJOUR_RENAME_FLAGS: DNSServiceFlags = 0;
+/// `kDNSServiceProperty_DaemonVersion`, which is a macro and absent from the bindings
+pub const BONJOUR_PROPERTY_DAEMON_VERSION: &[u8] = b"DaemonVersion\0";
@@ -1,18 +1,64 @@
 //! Event loop for running a `MdnsService` or `MdnsBrowser`.
 
 use super::service_ref::ManagedDNSServiceRef;
+use crate::error::{Error, ErrorKind};
 use crate::event_loop::TEventLoop;
-use crate::{ffi, Result};
+use crate::service::RegistrationSlot;
+use crate::{ffi, Result, ServiceRegistration};
+use std::cell::RefCell;
+use std::io;
 use std::marker::PhantomData;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(new)]
 pub struct BonjourEventLoop<'a> {
     service: Arc<Mutex<ManagedDNSServiceRef>>,
+    stopped: Arc<AtomicBool>,
+    #[new(default)]
+    registration: Option<RegistrationSlot>,
+    #[new(default)]
+    last_error: RefCell<Option<Error>>,
+    #[new(default)]
+    after_poll: Option<Box<dyn Fn()>>,
     phantom: PhantomData<&'a ManagedDNSServiceRef>,
 }
 
+impl<'a> BonjourEventLoop<'a> {
+    pub(crate) fn with_registration(mut self, registration: RegistrationSlot) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    /// Sets a function to run at the end of every `poll()` and `try_poll()`, e.g. to check a
+    /// deadline while no events arrive.
+    pub(crate) fn with_after_poll(mut self, after_poll: impl Fn() + 'static) -> Self {
+        self.after_poll = Some(Box::new(after_poll));
+        self
+    }
+
+    /// Processes a single reply, recording transient errors in `last_error` instead of
+    /// returning them.
+    fn process_result(&self, service: &ManagedDNSServiceRef) -> Result<()> {
+        match service.process_result() {
+            Err(e) if e.kind() == ErrorKind::Transient => {
+                debug!("ignoring transient error: {}", e);
+                *self.last_error.borrow_mut() = Some(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn run_after_poll(&self) {
+        if let Some(f) = &self.after_poll {
+            f();
+        }
+    }
+}
+
 impl<'a> TEventLoop for BonjourEventLoop<'a> {
     /// Polls for new events.
     ///
@@ -20,12 +66,74 @@ impl<'a> TEventLoop for BonjourEventLoop<'a> {
     /// `select()` on the underlying socket with the specified timeout. If the socket contains no
     /// new data, the blocking call is not made.
     fn poll(&self, timeout: Duration) -> Result<()> {
+        if self.is_stopped() {
+            return Ok(());
+        }
+
         let service = self.service.lock().unwrap();
         let select = unsafe { ffi::macos::read_select(service.sock_fd(), timeout)? };
         if select > 0 {
-            service.process_result()
-        } else {
-            Ok(())
+            self.process_result(&service)?;
         }
+
+        self.run_after_poll();
+
+        Ok(())
+    }
+
+    /// Calls `ManagedDNSServiceRef::process_result()` for as long as the socket has data, each
+    /// call handling a single reply from the daemon.
+    fn try_poll(&self) -> Result<usize> {
+        let service = self.service.lock().unwrap();
+        let mut handled = 0;
+
+        while !self.is_stopped()
+            && unsafe { ffi::macos::read_select(service.sock_fd(), Duration::ZERO)? } > 0
+        {
+            self.process_result(&service)?;
+            handled += 1;
+        }
+
+        self.run_after_poll();
+
+        Ok(handled)
+    }
+
+    fn wait_for_registration(&self, timeout: Duration) -> Result<ServiceRegistration> {
+        let registration = self
+            .registration
+            .as_ref()
+            .ok_or("event loop is not driving a service")?;
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(result) = registration.lock().unwrap().clone() {
+                return result;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err("timed out waiting for registration".into());
+            } else if self.is_stopped() {
+                return Err("event loop stopped before registration".into());
+            }
+
+            self.poll(remaining)?;
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    fn last_error(&self) -> Option<Error> {
+        self.last_error.borrow().clone()
+    }
+
+    /// Passes the `DNSServiceRef`'s connection to mDNSResponder to `configure`.
+    fn configure_socket(&self, configure: impl FnOnce(RawFd) -> io::Result<()>) -> Result<()> {
+        let sock_fd = self.service.lock().unwrap().sock_fd();
+        configure(sock_fd).map_err(|e| format!("could not configure socket: {}", e).into())
     }
 }
@@ -1,19 +1,23 @@
 //! Bonjour implementation for cross-platform service.
 
-use super::service_ref::{ManagedDNSServiceRef, RegisterServiceParams};
+use super::service_ref::{AddRecordParams, ManagedDNSServiceRef, RegisterServiceParams};
 use super::{bonjour_util, constants};
+use crate::error::{Error, ErrorKind};
 use crate::ffi::c_str::{self, AsCChars};
 use crate::ffi::{FromRaw, UnwrapOrNull};
 use crate::prelude::*;
+use crate::service::{self, RegistrationClaim, RegistrationSlot};
 use crate::{
-    EventLoop, NetworkInterface, Result, ServiceRegisteredCallback, ServiceRegistration,
-    ServiceType, TxtRecord,
+    EventLoop, NetworkInterface, PublishFlags, Result, ServiceRegisteredCallback,
+    ServiceRegistration, ServiceType, TxtRecord,
 };
 use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
 use libc::{c_char, c_void};
 use std::any::Any;
 use std::ffi::CString;
+use std::ops::ControlFlow;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
@@ -25,23 +29,39 @@ pub struct BonjourMdnsService {
     domain: Option<CString>,
     host: Option<CString>,
     interface_index: u32,
+    local_only: bool,
     txt_record: Option<TxtRecord>,
+    additional_txt_records: Vec<TxtRecord>,
+    additional_record_ttl: u32,
     context: *mut BonjourServiceContext,
 }
 
 impl TMdnsService for BonjourMdnsService {
+    type EventLoop<'a> = EventLoop<'a>;
+
     fn new(service_type: ServiceType, port: u16) -> Self {
-        Self {
+        let domain = service_type.explicit_domain().map(|d| d.to_string());
+
+        let mut service = Self {
             service: Arc::default(),
-            kind: c_string!(service_type.to_string()),
+            kind: c_string!(service_type.as_registration_string()),
             port,
             name: None,
             domain: None,
             host: None,
             interface_index: constants::BONJOUR_IF_UNSPEC,
+            local_only: false,
             txt_record: None,
+            additional_txt_records: vec![],
+            additional_record_ttl: 0,
             context: Box::into_raw(Box::default()),
+        };
+
+        if let Some(domain) = domain {
+            service.set_domain(&domain);
         }
+
+        service
     }
 
     /// Sets the name to register this service under. If no name is set, Bonjour will
@@ -54,6 +74,10 @@ impl TMdnsService for BonjourMdnsService {
         self.interface_index = bonjour_util::interface_index(interface);
     }
 
+    fn set_local_only(&mut self, local_only: bool) {
+        self.local_only = local_only;
+    }
+
     fn set_domain(&mut self, domain: &str) {
         self.domain = Some(c_string!(domain));
     }
@@ -66,10 +90,28 @@ impl TMdnsService for BonjourMdnsService {
         self.txt_record = Some(txt_record);
     }
 
+    fn set_txt_records(&mut self, txt_records: Vec<TxtRecord>) {
+        let mut txt_records = txt_records.into_iter();
+        self.txt_record = txt_records.next();
+        self.additional_txt_records = txt_records.collect();
+    }
+
     fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
         unsafe { (*self.context).registered_callback = Some(registered_callback) };
     }
 
+    fn set_additional_record_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.additional_record_ttl = ttl;
+        Ok(())
+    }
+
+    fn set_publish_flags(&mut self, _flags: PublishFlags) -> Result<()> {
+        Err(Error::with_kind(
+            ErrorKind::Unsupported,
+            "publish flags are only supported by Avahi",
+        ))
+    }
+
     fn set_context(&mut self, context: Box<dyn Any>) {
         unsafe { (*self.context).user_context = Some(Arc::from(context)) };
     }
@@ -77,6 +119,98 @@ impl TMdnsService for BonjourMdnsService {
     fn register(&mut self) -> Result<EventLoop> {
         debug!("Registering service: {:?}", self);
 
+        if unsafe { (*self.context).claim.is_some() } {
+            return Err(Error::with_kind(
+                ErrorKind::AlreadyRegistered,
+                "service is already registered",
+            ));
+        }
+
+        service::validate_settings(
+            self.host.as_ref().map(|h| h.to_str().unwrap()),
+            self.domain.as_ref().map(|d| d.to_str().unwrap()),
+        )?;
+
+        let claim = RegistrationClaim::new(
+            self.kind.to_str().unwrap(),
+            self.name.as_ref().map(|n| n.to_str().unwrap()),
+            self.domain.as_ref().map(|d| d.to_str().unwrap()),
+            self.port,
+        )?;
+
+        unsafe {
+            (*self.context).stopped.store(false, Ordering::SeqCst);
+            (*self.context).port = self.port;
+            (*self.context).registrations.clear();
+            (*self.context).registration.lock().unwrap().take();
+        }
+
+        self.publish()?;
+
+        unsafe { (*self.context).claim = Some(claim) };
+
+        Ok(EventLoop::new(self.service.clone(), unsafe {
+            (*self.context).stopped.clone()
+        })
+        .with_registration(unsafe { (*self.context).registration.clone() }))
+    }
+
+    /// Deallocates the `DNSServiceRef`, which deregisters the service and causes mDNSResponder to
+    /// send goodbye packets for it.
+    fn unregister(&mut self) -> Result<()> {
+        if unsafe { (*self.context).claim.take() }.is_none() {
+            return Ok(());
+        }
+
+        debug!("Unregistering service: {:?}", self);
+
+        *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+
+        unsafe {
+            (*self.context).registrations.clear();
+            (*self.context).registration.lock().unwrap().take();
+            (*self.context).stopped.store(true, Ordering::SeqCst);
+        }
+
+        Ok(())
+    }
+
+    /// Deallocates the `DNSServiceRef` and registers the service again under the name it was
+    /// registered with, so that mDNSResponder probes and announces it anew.
+    fn reannounce(&mut self) -> Result<EventLoop<'_>> {
+        if unsafe { (*self.context).claim.is_none() } {
+            return Err("service is not registered".into());
+        }
+
+        debug!("Reannouncing service: {:?}", self);
+
+        unsafe {
+            if let Some(registration) = (*self.context).registrations.first() {
+                self.name = Some(c_string!(registration.name().as_str()));
+            }
+
+            (*self.context).stopped.store(false, Ordering::SeqCst);
+            (*self.context).registrations.clear();
+            (*self.context).registration.lock().unwrap().take();
+        }
+
+        *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+
+        self.publish()?;
+
+        Ok(EventLoop::new(self.service.clone(), unsafe {
+            (*self.context).stopped.clone()
+        })
+        .with_registration(unsafe { (*self.context).registration.clone() }))
+    }
+
+    fn registrations(&self) -> Vec<ServiceRegistration> {
+        unsafe { (*self.context).registrations.clone() }
+    }
+}
+
+impl BonjourMdnsService {
+    fn publish(&self) -> Result<()> {
         let txt_len = self
             .txt_record
             .as_ref()
@@ -89,10 +223,16 @@ impl TMdnsService for BonjourMdnsService {
             .map(|t| t.inner().get_bytes_ptr())
             .unwrap_or_null();
 
-        self.service.lock().unwrap().register_service(
+        let mut service = self.service.lock().unwrap();
+
+        service.register_service(
             RegisterServiceParams::builder()
                 .flags(constants::BONJOUR_RENAME_FLAGS)
-                .interface_index(self.interface_index)
+                .interface_index(if self.local_only {
+                    constants::BONJOUR_IF_LOCAL_ONLY
+                } else {
+                    self.interface_index
+                })
                 .name(self.name.as_ref().as_c_chars().unwrap_or_null())
                 .regtype(self.kind.as_ptr())
                 .domain(self.domain.as_ref().as_c_chars().unwrap_or_null())
@@ -105,26 +245,52 @@ impl TMdnsService for BonjourMdnsService {
                 .build()?,
         )?;
 
-        Ok(EventLoop::new(self.service.clone()))
+        // an empty TXT record has no data to add
+        for txt_record in self.additional_txt_records.iter().filter(|t| !t.is_empty()) {
+            service.add_record(
+                AddRecordParams::builder()
+                    .flags(0)
+                    .rrtype(bonjour_sys::kDNSServiceType_TXT as u16)
+                    .rdlen(txt_record.inner().get_length())
+                    .rdata(txt_record.inner().get_bytes_ptr())
+                    .ttl(self.additional_record_ttl)
+                    .build()?,
+            )?;
+        }
+
+        Ok(())
     }
 }
 
 impl Drop for BonjourMdnsService {
+    /// Deallocates the `DNSServiceRef`, which deregisters the service and causes mDNSResponder to
+    /// send goodbye packets for it. This happens before the context is freed, as no callback
+    /// can be dispatched for a deallocated ref.
     fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
+        *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+        unsafe { drop(Box::from_raw(self.context)) };
     }
 }
 
 #[derive(Default, FromRaw)]
 struct BonjourServiceContext {
+    port: u16,
+    registrations: Vec<ServiceRegistration>,
     registered_callback: Option<Box<ServiceRegisteredCallback>>,
     user_context: Option<Arc<dyn Any>>,
+    registration: RegistrationSlot,
+    stopped: Arc<AtomicBool>,
+    claim: Option<RegistrationClaim>,
 }
 
 impl BonjourServiceContext {
-    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
-        if let Some(f) = &self.registered_callback {
-            f(result, self.user_context.clone());
+    fn invoke_callback(&mut self, result: Result<ServiceRegistration>) {
+        *self.registration.lock().unwrap() = Some(result.clone());
+
+        if let Some(f) = &mut self.registered_callback {
+            if let ControlFlow::Break(()) = f(result, self.user_context.clone()) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
         } else {
             warn!("attempted to invoke callback but none was set");
         }
@@ -147,26 +313,38 @@ unsafe extern "C" fn register_callback(
 }
 
 unsafe fn handle_register(
-    context: &BonjourServiceContext,
+    context: &mut BonjourServiceContext,
     error: DNSServiceErrorType,
     domain: *const c_char,
     name: *const c_char,
     regtype: *const c_char,
 ) -> Result<()> {
     if error != 0 {
-        return Err(format!("register_callback() reported error (code: {0})", error).into());
+        return Err(Error::from(format!(
+            "register_callback() reported error (code: {0})",
+            error
+        ))
+        .with_raw_code(error));
     }
 
     let domain = bonjour_util::normalize_domain(c_str::raw_to_str(domain));
     let kind = bonjour_util::normalize_domain(c_str::raw_to_str(regtype));
 
+    // mDNSResponder reports the name it renamed the service to after a name collision
+    let name = c_str::copy_raw(name);
+    if let Some(claim) = &mut context.claim {
+        claim.rename(&name);
+    }
+
     let result = ServiceRegistration::builder()
-        .name(c_str::copy_raw(name))
+        .name(name)
         .service_type(ServiceType::from_str(&kind)?)
+        .port(context.port)
         .domain(domain)
         .build()
         .expect("could not build ServiceRegistration");
 
+    context.registrations = vec![result.clone()];
     context.invoke_callback(Ok(result));
 
     Ok(())
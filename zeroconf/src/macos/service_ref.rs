@@ -1,11 +1,15 @@
 //! Low level interface for interacting with `DNSserviceRef`
 
+use super::bonjour_util;
+use crate::error::{Error, ErrorKind};
 use crate::Result;
 use bonjour_sys::{
-    DNSServiceBrowse, DNSServiceBrowseReply, DNSServiceFlags, DNSServiceGetAddrInfo,
-    DNSServiceGetAddrInfoReply, DNSServiceProcessResult, DNSServiceProtocol, DNSServiceRef,
-    DNSServiceRefDeallocate, DNSServiceRefSockFD, DNSServiceRegister, DNSServiceRegisterReply,
-    DNSServiceResolve, DNSServiceResolveReply,
+    kDNSServiceFlagsShareConnection, DNSRecordRef, DNSServiceAddRecord, DNSServiceBrowse,
+    DNSServiceBrowseReply, DNSServiceCreateConnection, DNSServiceDomainEnumReply,
+    DNSServiceEnumerateDomains, DNSServiceFlags, DNSServiceGetAddrInfo, DNSServiceGetAddrInfoReply,
+    DNSServiceProcessResult, DNSServiceProtocol, DNSServiceRef, DNSServiceRefDeallocate,
+    DNSServiceRefSockFD, DNSServiceRegister, DNSServiceRegisterReply, DNSServiceResolve,
+    DNSServiceResolveReply,
 };
 use libc::{c_char, c_void};
 use std::ptr;
@@ -66,6 +70,31 @@ impl ManagedDNSServiceRef {
         )
     }
 
+    /// Delegate function for [`DNSServiceAddRecord`], adding a record to the registration made
+    /// with `register_service()`.
+    ///
+    /// The `DNSRecordRef` of the new record is not kept; the record is removed along with the
+    /// registration once this instance is dropped.
+    ///
+    /// [`DNSServiceAddRecord`]: https://developer.apple.com/documentation/dnssd/1804730-dnsserviceaddrecord?language=objc
+    pub fn add_record(
+        &mut self,
+        AddRecordParams {
+            flags,
+            rrtype,
+            rdlen,
+            rdata,
+            ttl,
+        }: AddRecordParams,
+    ) -> Result<()> {
+        let mut record: DNSRecordRef = ptr::null_mut();
+
+        bonjour!(
+            DNSServiceAddRecord(self.0, &mut record, flags, rrtype, rdlen, rdata, ttl),
+            "could not add record"
+        )
+    }
+
     /// Delegate function for [`DNSServiceBrowse`].
     ///
     /// [`DNSServiceBrowse`]: https://developer.apple.com/documentation/dnssd/1804742-dnsservicebrowse?language=objc
@@ -94,6 +123,67 @@ impl ManagedDNSServiceRef {
         )
     }
 
+    /// Delegate function for [`DNSServiceEnumerateDomains`].
+    ///
+    /// [`DNSServiceEnumerateDomains`]: https://developer.apple.com/documentation/dnssd/1804754-dnsserviceenumeratedomains?language=objc
+    pub fn enumerate_domains(
+        &mut self,
+        EnumerateDomainsParams {
+            flags,
+            interface_index,
+            callback,
+            context,
+        }: EnumerateDomainsParams,
+    ) -> Result<()> {
+        bonjour!(
+            DNSServiceEnumerateDomains(
+                &mut self.0 as *mut DNSServiceRef,
+                flags,
+                interface_index,
+                callback,
+                context,
+            ),
+            "could not enumerate domains"
+        )
+    }
+
+    /// Delegate function for [`DNSServiceCreateConnection`].
+    ///
+    /// The resulting connection can be shared by several operations started with
+    /// `browse_services_shared()`, whose replies are all handled by calling `process_result()`
+    /// on this instance.
+    ///
+    /// [`DNSServiceCreateConnection`]: https://developer.apple.com/documentation/dnssd/1804724-dnsservicecreateconnection?language=objc
+    pub fn create_connection(&mut self) -> Result<()> {
+        bonjour!(
+            DNSServiceCreateConnection(&mut self.0 as *mut DNSServiceRef),
+            "could not create connection"
+        )
+    }
+
+    /// Starts a [`DNSServiceBrowse`] operation on the shared `connection` created with
+    /// `create_connection()`, returning a new instance for the operation.
+    ///
+    /// Dropping the returned instance only cancels this operation. It must be dropped before
+    /// `connection`, since deallocating the connection invalidates every operation sharing it.
+    ///
+    /// [`DNSServiceBrowse`]: https://developer.apple.com/documentation/dnssd/1804742-dnsservicebrowse?language=objc
+    pub fn browse_services_shared(
+        connection: &ManagedDNSServiceRef,
+        mut params: BrowseServicesParams,
+    ) -> Result<Self> {
+        params.flags |= kDNSServiceFlagsShareConnection;
+
+        let mut operation = Self(connection.0);
+        if let Err(e) = operation.browse_services(params) {
+            // the connection is still owned by `connection`
+            operation.0 = ptr::null_mut();
+            return Err(e);
+        }
+
+        Ok(operation)
+    }
+
     /// Delegate function fro [`DNSServiceResolve`].
     ///
     /// [`DNSServiceResolve`]: https://developer.apple.com/documentation/dnssd/1804744-dnsserviceresolve?language=objc
@@ -121,9 +211,7 @@ impl ManagedDNSServiceRef {
                 context,
             ),
             "DNSServiceResolve() reported error"
-        )?;
-
-        self.process_result()
+        )
     }
 
     /// Delegate function for [`DNSServiceGetAddrInfo`].
@@ -151,19 +239,27 @@ impl ManagedDNSServiceRef {
                 context,
             ),
             "DNSServiceGetAddrInfo() reported error"
-        )?;
-
-        self.process_result()
+        )
     }
 
     /// Delegate function for [`DNSServiceProcessResult`].
     ///
+    /// Errors that [`bonjour_util::is_transient_error()`] considers recoverable are returned with
+    /// `ErrorKind::Transient`.
+    ///
     /// [`DNSServiceProcessResult`]: https://developer.apple.com/documentation/dnssd/1804696-dnsserviceprocessresult?language=objc
+    /// [`bonjour_util::is_transient_error()`]: ../bonjour_util/fn.is_transient_error.html
     pub fn process_result(&self) -> Result<()> {
-        bonjour!(
-            DNSServiceProcessResult(self.0),
-            "could not process service result"
-        )
+        let err = unsafe { DNSServiceProcessResult(self.0) };
+        let message = format!("could not process service result (code: {})", err);
+
+        if err == 0 {
+            Ok(())
+        } else if bonjour_util::is_transient_error(err) {
+            Err(Error::with_kind(ErrorKind::Transient, message).with_raw_code(err))
+        } else {
+            Err(Error::from(message).with_raw_code(err))
+        }
     }
 
     /// Delegate function for [`DNSServiceRefSockFD`].
@@ -206,6 +302,25 @@ pub struct RegisterServiceParams {
     context: *mut c_void,
 }
 
+/// Holds parameters for `ManagedDNSServiceRef::add_record()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct AddRecordParams {
+    flags: DNSServiceFlags,
+    rrtype: u16,
+    rdlen: u16,
+    rdata: *const c_void,
+    ttl: u32,
+}
+
+/// Holds parameters for `ManagedDNSServiceRef::enumerate_domains()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct EnumerateDomainsParams {
+    flags: DNSServiceFlags,
+    interface_index: u32,
+    callback: DNSServiceDomainEnumReply,
+    context: *mut c_void,
+}
+
 /// Holds parameters for `ManagedDNSServiceRef::browse_services()`.
 #[derive(Builder, BuilderDelegate)]
 pub struct BrowseServicesParams {
@@ -1,12 +1,11 @@
 //! Bonjour implementation for cross-platform TXT record.
 
 use super::txt_record_ref::ManagedTXTRecordRef;
-use crate::ffi::c_str;
-use crate::txt_record::TTxtRecord;
+use crate::txt_record::{self, TTxtRecord};
 use crate::Result;
 use libc::{c_char, c_void};
 use std::ffi::CString;
-use std::{mem, ptr};
+use std::{ptr, slice};
 
 /// Interface for interfacting with Bonjour's TXT record capabilities.
 #[derive(Clone)]
@@ -18,9 +17,11 @@ impl TTxtRecord for BonjourTxtRecord {
     }
 
     fn insert(&mut self, key: &str, value: &str) -> Result<()> {
+        txt_record::validate_entry(key, value.as_bytes())?;
+
         let key = c_string!(key);
         let value = c_string!(value);
-        let value_size = mem::size_of_val(&value) as u8;
+        let value_size = value.as_bytes().len() as u8;
         unsafe {
             self.0.set_value(
                 key.as_ptr() as *const c_char,
@@ -41,7 +42,9 @@ impl TTxtRecord for BonjourTxtRecord {
         if value_raw.is_null() {
             None
         } else {
-            Some(unsafe { c_str::raw_to_str(value_raw as *const c_char).to_string() })
+            let bytes =
+                unsafe { slice::from_raw_parts(value_raw as *const u8, value_len as usize) };
+            Some(String::from_utf8_lossy(bytes).into_owned())
         }
     }
 
@@ -67,6 +70,19 @@ impl TTxtRecord for BonjourTxtRecord {
         Box::new(Iter::new(self))
     }
 
+    /// Walks the length-prefixed entries in the buffer maintained by the underlying
+    /// `TXTRecordRef`.
+    fn iter_raw<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a [u8], &'a [u8])> + 'a> {
+        let length = self.0.get_length() as usize;
+        let bytes: &'a [u8] = if length == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.0.get_bytes_ptr() as *const u8, length) }
+        };
+
+        Box::new(RawIter(bytes))
+    }
+
     fn keys<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a> {
         Box::new(Keys(Iter::new(self)))
     }
@@ -74,6 +90,21 @@ impl TTxtRecord for BonjourTxtRecord {
     fn values<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a> {
         Box::new(Values(Iter::new(self)))
     }
+
+    /// Copies the buffer maintained by the underlying `TXTRecordRef`.
+    fn to_bytes(&self) -> Vec<u8> {
+        let length = self.0.get_length() as usize;
+        if length == 0 {
+            return vec![];
+        }
+
+        unsafe { slice::from_raw_parts(self.0.get_bytes_ptr() as *const u8, length).to_vec() }
+    }
+
+    /// Returns the length of the buffer copied by `to_bytes()`.
+    fn byte_len(&self) -> usize {
+        self.0.get_length() as usize
+    }
 }
 
 impl BonjourTxtRecord {
@@ -131,13 +162,17 @@ impl Iterator for Iter<'_> {
                 .unwrap();
         }
 
-        assert_not_null!(value);
-
         let key = String::from(raw_key.to_str().unwrap())
             .trim_matches(char::from(0))
             .to_string();
 
-        let value = unsafe { c_str::raw_to_str(value as *const c_char).to_string() };
+        // a key without `=` has no value, and values are not NUL-terminated
+        let value = if value.is_null() {
+            String::new()
+        } else {
+            let bytes = unsafe { slice::from_raw_parts(value as *const u8, value_len as usize) };
+            String::from_utf8_lossy(bytes).into_owned()
+        };
 
         self.index += 1;
 
@@ -145,6 +180,27 @@ impl Iterator for Iter<'_> {
     }
 }
 
+/// An `Iterator` over the raw entries of a TXT record buffer in wire format.
+pub struct RawIter<'a>(&'a [u8]);
+
+impl<'a> Iterator for RawIter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    /// Zero-length entries, such as the one an empty TXT record consists of, are skipped.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (len, rest) = self.0.split_first()?;
+            let len = (*len as usize).min(rest.len());
+            let (entry, rest) = rest.split_at(len);
+            self.0 = rest;
+
+            if !entry.is_empty() {
+                return Some(txt_record::split_entry(entry));
+            }
+        }
+    }
+}
+
 /// An `Iterator` that allows iteration over a [`BonjourTxtRecord`]'s keys.
 pub struct Keys<'a>(Iter<'a>);
 
@@ -7,8 +7,7 @@ use bonjour_sys::{
     TXTRecordRef, TXTRecordRemoveValue, TXTRecordSetValue,
 };
 use libc::{c_char, c_uchar, c_void};
-use std::ffi::CString;
-use std::{fmt, mem, ptr};
+use std::{fmt, mem, ptr, slice};
 
 /// Wraps the `ManagedTXTRecordRef` type from the raw Bonjour bindings.
 ///
@@ -129,10 +128,11 @@ impl ManagedTXTRecordRef {
         TXTRecordGetValuePtr(self.get_length(), self.get_bytes_ptr(), key, value_len)
     }
 
+    /// Creates a new `ManagedTXTRecordRef` holding a copy of the items in the specified raw TXT
+    /// record. The new record owns its own buffer and does not reference `raw` after returning.
     pub(crate) unsafe fn clone_raw(raw: *const c_uchar, size: u16) -> Result<Self> {
-        let chars = c_string!(alloc(size as usize)).into_raw() as *mut c_uchar;
-        ptr::copy(raw, chars, size as usize);
-        let chars = CString::from_raw(chars as *mut c_char);
+        // the TXT record may contain NUL bytes, so it is copied as bytes rather than as a C string
+        let chars = slice::from_raw_parts(raw, size as usize).to_vec();
 
         let mut record = Self::new();
 
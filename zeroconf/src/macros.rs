@@ -19,7 +19,9 @@ macro_rules! bonjour {
         #[allow(unused_unsafe)]
         let err = unsafe { $call };
         if err != 0 {
-            crate::Result::Err(format!("{}", format!("{} (code: {})", $msg, err)).into())
+            crate::Result::Err(
+                crate::error::Error::from(format!("{} (code: {})", $msg, err)).with_raw_code(err),
+            )
         } else {
             crate::Result::Ok(())
         }
@@ -33,11 +35,12 @@ macro_rules! avahi {
         let err = unsafe { $call };
         if err < 0 {
             crate::Result::Err(
-                format!(
-                    "{}",
-                    format!("{}: `{}`", $msg, crate::linux::avahi_util::get_error(err))
-                )
-                .into(),
+                crate::error::Error::from(format!(
+                    "{}: `{}`",
+                    $msg,
+                    crate::linux::avahi_util::get_error(err)
+                ))
+                .with_raw_code(err),
             )
         } else {
             crate::Result::Ok(())
@@ -66,4 +69,39 @@ mod tests {
     fn c_string_success() {
         assert_eq!(c_string!("foo"), CString::new("foo").unwrap());
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn avahi_error_preserves_raw_code() {
+        let err = avahi!(
+            avahi_sys::AVAHI_ERR_INVALID_SERVICE_TYPE,
+            "could not add service"
+        )
+        .unwrap_err();
+
+        assert_eq!(crate::error::ErrorKind::Other, err.kind());
+        assert_eq!(
+            Some(avahi_sys::AVAHI_ERR_INVALID_SERVICE_TYPE),
+            err.raw_code()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn avahi_success_has_no_error() {
+        assert!(avahi!(0, "could not add service").is_ok());
+    }
+
+    #[cfg(target_vendor = "apple")]
+    #[test]
+    fn bonjour_error_preserves_raw_code() {
+        let err = bonjour!(
+            bonjour_sys::kDNSServiceErr_BadParam,
+            "could not register service"
+        )
+        .unwrap_err();
+
+        assert_eq!(crate::error::ErrorKind::Other, err.kind());
+        assert_eq!(Some(bonjour_sys::kDNSServiceErr_BadParam), err.raw_code());
+    }
 }
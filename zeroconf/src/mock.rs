@@ -0,0 +1,1264 @@
+//! In-memory backend for unit testing code that uses this crate, enabled with the `mock` feature.
+//!
+//! [`MockMdnsService`] and [`MockMdnsBrowser`] implement the same traits as the platform
+//! implementations, so code written against [`TMdnsService`] and [`TMdnsBrowser`] can be tested
+//! without a running daemon or a network.
+//!
+//! Instead of the network, the mocks share a per-thread registry: services registered with
+//! `MockMdnsService` are discovered by `MockMdnsBrowser`s browsing for the same type on the same
+//! thread, and tests may inject additional discoveries, removals or errors with
+//! [`inject_discovery()`], [`inject_removal()`], [`inject_removal_on()`] and [`inject_error()`].
+//! Discoveries carrying
+//! malformed TXT data, as a buggy peer might advertise, can be injected with
+//! [`inject_raw_txt()`]. Since every test runs on its own thread, tests are isolated from each
+//! other.
+//!
+//! Events are delivered when the returned event loop is polled, as with the platform
+//! implementations.
+//!
+//! [`MockMdnsService`]: struct.MockMdnsService.html
+//! [`MockMdnsBrowser`]: struct.MockMdnsBrowser.html
+//! [`TMdnsService`]: ../service/trait.TMdnsService.html
+//! [`TMdnsBrowser`]: ../browser/trait.TMdnsBrowser.html
+//! [`inject_discovery()`]: fn.inject_discovery.html
+//! [`inject_removal()`]: fn.inject_removal.html
+//! [`inject_removal_on()`]: fn.inject_removal_on.html
+//! [`inject_error()`]: fn.inject_error.html
+//! [`inject_raw_txt()`]: fn.inject_raw_txt.html
+
+use crate::browser::{self, InstanceStatsTable};
+use crate::error::{Error, ErrorKind};
+use crate::event_loop::TEventLoop;
+use crate::prelude::*;
+use crate::service;
+use crate::txt_record;
+use crate::{
+    AddressFamily, InstanceStats, NetworkInterface, PublishFlags, Result,
+    ServiceDiscoveredCallback, ServiceDiscovery, ServiceEvent, ServiceIter,
+    ServiceRegisteredCallback, ServiceRegistration, ServiceRemoval, ServiceRemovedCallback,
+    ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static NETWORK: RefCell<MockNetwork> = RefCell::default();
+}
+
+#[allow(clippy::large_enum_variant)]
+enum MockEvent {
+    Found(Result<ServiceDiscovery>),
+    /// The removal of a service on a single interface and domain, or everywhere if `None`
+    Removed(String, Option<(u32, String)>),
+}
+
+#[derive(Default)]
+struct MockNetwork {
+    events: Vec<(ServiceType, MockEvent)>,
+    registrations: Vec<(u64, ServiceRegistration)>,
+    next_id: u64,
+}
+
+/// Makes `discovery` visible to every `MockMdnsBrowser` on this thread that browses for its type.
+pub fn inject_discovery(discovery: ServiceDiscovery) {
+    let service_type = discovery.service_type().clone();
+    NETWORK.with(|n| {
+        n.borrow_mut()
+            .events
+            .push((service_type, MockEvent::Found(Ok(discovery))))
+    });
+}
+
+/// Reports the service `name` of `service_type` as removed to every `MockMdnsBrowser` on this
+/// thread that browses for it. The service can no longer be resolved until it is discovered
+/// again.
+pub fn inject_removal(service_type: ServiceType, name: &str) {
+    NETWORK.with(|n| {
+        n.borrow_mut()
+            .events
+            .push((service_type, MockEvent::Removed(name.to_string(), None)))
+    });
+}
+
+/// Reports the service `name` of `service_type` as removed from the interface `interface_index`
+/// and `domain` only, as the daemons do when an instance disappears from one of the interfaces,
+/// address families or domains it was discovered on.
+///
+/// As with the platform implementations, browsers only report the service as removed once it is
+/// gone from every interface it was discovered on in `domain`.
+pub fn inject_removal_on(
+    service_type: ServiceType,
+    name: &str,
+    interface_index: u32,
+    domain: &str,
+) {
+    let copy = (interface_index, normalize_domain(domain));
+    NETWORK.with(|n| {
+        n.borrow_mut().events.push((
+            service_type,
+            MockEvent::Removed(name.to_string(), Some(copy)),
+        ))
+    });
+}
+
+/// Makes `discovery` visible like `inject_discovery()`, with its TXT record replaced by one
+/// received as the raw `txt` bytes in wire format.
+///
+/// The bytes are checked as the platform implementations check TXT records received from the
+/// network: if they are malformed, browsers are passed an `Err` with [`ErrorKind::Malformed`]
+/// in place of the discovery. Keys must additionally be accepted by `TxtRecord::insert()`.
+///
+/// [`ErrorKind::Malformed`]: ../error/enum.ErrorKind.html#variant.Malformed
+pub fn inject_raw_txt(mut discovery: ServiceDiscovery, txt: &[u8]) {
+    let service_type = discovery.service_type().clone();
+
+    let event = parse_raw_txt(txt).map(|txt| {
+        discovery.set_txt(txt);
+        discovery
+    });
+
+    NETWORK.with(|n| {
+        n.borrow_mut()
+            .events
+            .push((service_type, MockEvent::Found(event)))
+    });
+}
+
+fn parse_raw_txt(bytes: &[u8]) -> Result<Option<TxtRecord>> {
+    let entries = txt_record::split_wire_format(bytes)?;
+    txt_record::check_received(entries.iter().copied())?;
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let mut txt = TxtRecord::new();
+    for (key, value) in entries {
+        // both were checked to be valid UTF-8 above
+        let key = std::str::from_utf8(key).unwrap();
+        let value = std::str::from_utf8(value).unwrap();
+        txt.insert(key, value)
+            .map_err(|e| Error::with_kind(ErrorKind::Malformed, e.to_string()))?;
+    }
+
+    Ok(Some(txt))
+}
+
+/// Reports `error` to every `MockMdnsBrowser` on this thread that browses for `service_type`.
+pub fn inject_error(service_type: ServiceType, error: Error) {
+    NETWORK.with(|n| {
+        n.borrow_mut()
+            .events
+            .push((service_type, MockEvent::Found(Err(error))))
+    });
+}
+
+/// Returns the registrations of all `MockMdnsService`s on this thread that are currently
+/// registered, in the order they were registered.
+pub fn registrations() -> Vec<ServiceRegistration> {
+    NETWORK.with(|n| {
+        n.borrow()
+            .registrations
+            .iter()
+            .map(|(_, r)| r.clone())
+            .collect()
+    })
+}
+
+/// Forgets all registrations and injected events on this thread.
+pub fn reset() {
+    NETWORK.with(|n| *n.borrow_mut() = MockNetwork::default());
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim_end_matches('.').to_string()
+}
+
+/// Returns the interface and domain that identify the copy of a service `discovery` was found as.
+fn copy_of(discovery: &ServiceDiscovery) -> (u32, String) {
+    (
+        *discovery.interface_index(),
+        normalize_domain(discovery.domain()),
+    )
+}
+
+fn is_same_type(a: &ServiceType, b: &ServiceType) -> bool {
+    a.name() == b.name() && a.protocol() == b.protocol()
+}
+
+fn is_registered(
+    event: &Result<ServiceDiscovery>,
+    registrations: &[(u64, ServiceRegistration)],
+) -> bool {
+    match event {
+        Ok(discovery) => registrations.iter().any(|(_, r)| {
+            r.name() == discovery.name() && is_same_type(r.service_type(), discovery.service_type())
+        }),
+        Err(_) => false,
+    }
+}
+
+fn is_in_domains(event: &Result<ServiceDiscovery>, domains: &[String]) -> bool {
+    match event {
+        Ok(discovery) if !domains.is_empty() => {
+            let domain = discovery.domain().trim_end_matches('.');
+            domains.iter().any(|d| d.trim_end_matches('.') == domain)
+        }
+        _ => true,
+    }
+}
+
+fn has_name_prefix(event: &Result<ServiceDiscovery>, prefix: Option<&str>) -> bool {
+    match event {
+        Ok(discovery) => browser::matches_name_prefix(discovery.name(), prefix),
+        Err(_) => true,
+    }
+}
+
+/// Mock implementation of [`TMdnsService`].
+///
+/// [`TMdnsService`]: ../service/trait.TMdnsService.html
+#[derive(Debug)]
+pub struct MockMdnsService {
+    service_type: ServiceType,
+    port: u16,
+    name: Option<String>,
+    domain: Option<String>,
+    host: Option<String>,
+    interface: NetworkInterface,
+    local_only: bool,
+    txt_record: Option<TxtRecord>,
+    additional_txt_records: Vec<TxtRecord>,
+    additional_record_ttl: u32,
+    publish_flags: PublishFlags,
+    state: Rc<RefCell<MockServiceState>>,
+}
+
+#[derive(Default)]
+struct MockServiceState {
+    id: Option<u64>,
+    pending: Option<Result<ServiceRegistration>>,
+    registration: Option<Result<ServiceRegistration>>,
+    announcement: Option<ServiceDiscovery>,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    user_context: Option<Arc<dyn Any>>,
+    stopped: bool,
+}
+
+impl std::fmt::Debug for MockServiceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServiceState")
+            .field("id", &self.id)
+            .field("registration", &self.registration)
+            .finish()
+    }
+}
+
+impl MockMdnsService {
+    /// Returns the network interface that was set with `set_network_interface()`.
+    pub fn network_interface(&self) -> NetworkInterface {
+        self.interface
+    }
+
+    /// Returns `true` if `set_local_only(true)` was called.
+    pub fn is_local_only(&self) -> bool {
+        self.local_only
+    }
+
+    /// Returns the flags that were set with `set_publish_flags()`.
+    pub fn publish_flags(&self) -> PublishFlags {
+        self.publish_flags
+    }
+
+    /// Returns the TTL that was set with `set_additional_record_ttl()`, or `0` if none was set.
+    pub fn additional_record_ttl(&self) -> u32 {
+        self.additional_record_ttl
+    }
+
+    /// Returns every TXT record this service is registered with: the one set with
+    /// `set_txt_record()`, followed by the additional ones set with `set_txt_records()`.
+    ///
+    /// Browsers only discover the first one, as with the platform implementations.
+    pub fn txt_records(&self) -> Vec<TxtRecord> {
+        self.txt_record
+            .iter()
+            .chain(&self.additional_txt_records)
+            .cloned()
+            .collect()
+    }
+
+    fn withdraw(&mut self) {
+        if let Some(id) = self.state.borrow_mut().id.take() {
+            NETWORK.with(|n| n.borrow_mut().registrations.retain(|(i, _)| *i != id));
+        }
+    }
+}
+
+impl TMdnsService for MockMdnsService {
+    type EventLoop<'a> = MockEventLoop<'a>;
+
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        let domain = service_type.explicit_domain().map(|d| d.to_string());
+
+        let mut service = Self {
+            service_type,
+            port,
+            name: None,
+            domain: None,
+            host: None,
+            interface: NetworkInterface::Unspec,
+            local_only: false,
+            txt_record: None,
+            additional_txt_records: vec![],
+            additional_record_ttl: 0,
+            publish_flags: PublishFlags::empty(),
+            state: Rc::default(),
+        };
+
+        if let Some(domain) = domain {
+            service.set_domain(&domain);
+        }
+
+        service
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface = interface;
+    }
+
+    fn set_local_only(&mut self, local_only: bool) {
+        self.local_only = local_only;
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(domain.to_string());
+    }
+
+    fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_string());
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.txt_record = Some(txt_record);
+    }
+
+    fn set_txt_records(&mut self, txt_records: Vec<TxtRecord>) {
+        let mut txt_records = txt_records.into_iter();
+        self.txt_record = txt_records.next();
+        self.additional_txt_records = txt_records.collect();
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        self.state.borrow_mut().registered_callback = Some(registered_callback);
+    }
+
+    /// Accepts any TTL, as Bonjour does.
+    fn set_additional_record_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.additional_record_ttl = ttl;
+        Ok(())
+    }
+
+    fn set_publish_flags(&mut self, flags: PublishFlags) -> Result<()> {
+        self.publish_flags = flags;
+        Ok(())
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any>) {
+        self.state.borrow_mut().user_context = Some(Arc::from(context));
+    }
+
+    /// Registers the service in this thread's mock registry, making it discoverable by
+    /// `MockMdnsBrowser`s. The registered callback is invoked on the next poll.
+    ///
+    /// As with the platform implementations, registering the same type, name, domain and port
+    /// as another service in the registry fails with `ErrorKind::NameConflict`.
+    ///
+    /// If no name is set, the service is registered as `mock`.
+    fn register(&mut self) -> Result<MockEventLoop<'_>> {
+        if self.state.borrow().id.is_some() {
+            return Err(Error::with_kind(
+                ErrorKind::AlreadyRegistered,
+                "service is already registered",
+            ));
+        }
+
+        service::validate_settings(self.host.as_deref(), self.domain.as_deref())?;
+
+        let name = self.name.clone().unwrap_or_else(|| "mock".to_string());
+        let domain = self.domain.clone().unwrap_or_else(|| "local".to_string());
+
+        let conflict = NETWORK.with(|n| {
+            n.borrow().registrations.iter().any(|(_, r)| {
+                *r.name() == name
+                    && *r.domain() == domain
+                    && *r.port() == self.port
+                    && is_same_type(r.service_type(), &self.service_type)
+            })
+        });
+
+        if conflict {
+            return Err(Error::with_kind(
+                ErrorKind::NameConflict,
+                format!(
+                    "service `{}` of type `{}` on port {} is already registered on this thread",
+                    name,
+                    self.service_type.as_registration_string(),
+                    self.port
+                ),
+            ));
+        }
+        let host_name = self
+            .host
+            .clone()
+            .unwrap_or_else(|| "localhost.local".to_string());
+
+        let registration = ServiceRegistration::builder()
+            .name(name.clone())
+            .service_type(self.service_type.clone())
+            .port(self.port)
+            .domain(domain.clone())
+            .build()?;
+
+        // as with Bonjour, anything outside the multicast `local` domain is wide-area
+        let is_wide_area = !domain.trim_end_matches('.').eq_ignore_ascii_case("local");
+
+        let discovery = ServiceDiscovery::builder()
+            .name(name)
+            .service_type(self.service_type.clone())
+            .domain(domain)
+            .host_name(host_name)
+            .address("127.0.0.1".to_string())
+            .port(self.port)
+            .txt(self.txt_record.clone().filter(|t| !t.is_empty()))
+            .is_local(true)
+            .is_wide_area(is_wide_area)
+            .build()?;
+
+        let id = NETWORK.with(|n| {
+            let mut network = n.borrow_mut();
+            let id = network.next_id;
+            network.next_id += 1;
+            network.registrations.push((id, registration.clone()));
+            id
+        });
+
+        inject_discovery(discovery.clone());
+
+        let mut state = self.state.borrow_mut();
+        state.id = Some(id);
+        state.announcement = Some(discovery);
+        state.pending = Some(Ok(registration));
+        state.registration = None;
+        state.stopped = false;
+
+        Ok(MockEventLoop::new(MockTarget::Service(self.state.clone())))
+    }
+
+    fn unregister(&mut self) -> Result<()> {
+        self.withdraw();
+
+        let mut state = self.state.borrow_mut();
+        state.pending = None;
+        state.registration = None;
+        state.stopped = true;
+
+        Ok(())
+    }
+
+    /// Injects the service's discovery again, so that browsers on this thread that already
+    /// discovered it are passed it a second time. The registered callback is invoked again on the
+    /// next poll.
+    fn reannounce(&mut self) -> Result<MockEventLoop<'_>> {
+        let mut state = self.state.borrow_mut();
+
+        let registration = state.id.and_then(|id| {
+            NETWORK.with(|n| {
+                n.borrow()
+                    .registrations
+                    .iter()
+                    .find(|(i, _)| *i == id)
+                    .map(|(_, r)| r.clone())
+            })
+        });
+
+        let registration = match registration {
+            Some(registration) => registration,
+            None => return Err("service is not registered".into()),
+        };
+
+        inject_discovery(state.announcement.clone().unwrap());
+
+        state.pending = Some(Ok(registration));
+        state.registration = None;
+        state.stopped = false;
+
+        drop(state);
+
+        Ok(MockEventLoop::new(MockTarget::Service(self.state.clone())))
+    }
+
+    fn registrations(&self) -> Vec<ServiceRegistration> {
+        match &self.state.borrow().registration {
+            Some(Ok(registration)) => vec![registration.clone()],
+            _ => vec![],
+        }
+    }
+}
+
+impl Drop for MockMdnsService {
+    /// Removes the service from this thread's mock registry. Browsers that already discovered it
+    /// are not notified, as with the platform implementations.
+    fn drop(&mut self) {
+        self.withdraw();
+    }
+}
+
+/// Mock implementation of [`TMdnsBrowser`].
+///
+/// [`TMdnsBrowser`]: ../browser/trait.TMdnsBrowser.html
+#[derive(Debug)]
+pub struct MockMdnsBrowser {
+    interface: NetworkInterface,
+    local_only: bool,
+    address_family: AddressFamily,
+    wide_area_domain: Option<String>,
+    force_multicast: bool,
+    browsing: bool,
+    paused: bool,
+    state: Rc<RefCell<MockBrowserState>>,
+}
+
+struct MockBrowserState {
+    service_type: ServiceType,
+    domains: Vec<String>,
+    resolve_address: bool,
+    ignore_local: bool,
+    max_duration: Option<Duration>,
+    expires_at: Option<Instant>,
+    expired: bool,
+    max_concurrent_resolves: Option<usize>,
+    name_prefix: Option<String>,
+    stats: InstanceStatsTable,
+    refresh_interval: Option<Duration>,
+    next_refresh_at: Option<Instant>,
+    // the name of each discovered service with the interface and domain it was found on
+    discovered: Vec<(String, (u32, String))>,
+    cursor: usize,
+    refreshed: Vec<Result<ServiceDiscovery>>,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    removals: VecDeque<ServiceRemoval>,
+    service_removed_callback: Option<Box<ServiceRemovedCallback>>,
+    user_context: Option<Arc<dyn Any>>,
+    stopped: bool,
+}
+
+impl std::fmt::Debug for MockBrowserState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockBrowserState")
+            .field("service_type", &self.service_type)
+            .field("cursor", &self.cursor)
+            .finish()
+    }
+}
+
+impl MockMdnsBrowser {
+    /// Returns the network interface that was set with `set_network_interface()`.
+    pub fn network_interface(&self) -> NetworkInterface {
+        self.interface
+    }
+
+    /// Returns `true` if `set_local_only(true)` was called.
+    pub fn is_local_only(&self) -> bool {
+        self.local_only
+    }
+
+    /// Returns the address family that was set with `set_address_family()`.
+    pub fn address_family(&self) -> AddressFamily {
+        self.address_family
+    }
+
+    /// Returns the domain that was set with `set_wide_area()`, if any.
+    pub fn wide_area_domain(&self) -> Option<&str> {
+        self.wide_area_domain.as_deref()
+    }
+
+    /// Returns the value that was set with `set_force_multicast()`.
+    pub fn force_multicast(&self) -> bool {
+        self.force_multicast
+    }
+
+    /// Returns the domains that were set with `set_domains()`.
+    pub fn domains(&self) -> Vec<String> {
+        self.state.borrow().domains.clone()
+    }
+
+    /// Starts delivering the registry from the beginning without moving the deadline set with
+    /// `set_max_duration()`.
+    fn restart(&mut self) -> Result<MockEventLoop<'_>> {
+        self.browsing = true;
+        self.paused = false;
+
+        let mut state = self.state.borrow_mut();
+        state.cursor = 0;
+        state.refreshed.clear();
+        state.next_refresh_at = None;
+        state.stopped = false;
+
+        Ok(MockEventLoop::new(MockTarget::Browser(self.state.clone())))
+    }
+}
+
+impl TMdnsBrowser for MockMdnsBrowser {
+    type EventLoop<'a> = MockEventLoop<'a>;
+    type ResolveHandle = MockResolveHandle;
+
+    fn new(service_type: ServiceType) -> Self {
+        let domain = service_type.explicit_domain().map(|d| d.to_string());
+
+        let mut browser = Self {
+            interface: NetworkInterface::Unspec,
+            local_only: false,
+            address_family: AddressFamily::Any,
+            wide_area_domain: None,
+            force_multicast: false,
+            browsing: false,
+            paused: false,
+            state: Rc::new(RefCell::new(MockBrowserState {
+                service_type,
+                domains: vec![],
+                resolve_address: true,
+                ignore_local: false,
+                max_duration: None,
+                expires_at: None,
+                expired: false,
+                max_concurrent_resolves: None,
+                name_prefix: None,
+                stats: InstanceStatsTable::default(),
+                refresh_interval: None,
+                next_refresh_at: None,
+                discovered: vec![],
+                cursor: 0,
+                refreshed: vec![],
+                service_discovered_callback: None,
+                removals: VecDeque::new(),
+                service_removed_callback: None,
+                user_context: None,
+                stopped: false,
+            })),
+        };
+
+        if let Some(domain) = domain {
+            browser.set_domains(vec![domain]);
+        }
+
+        browser
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface = interface;
+    }
+
+    fn set_local_only(&mut self, local_only: bool) {
+        self.local_only = local_only;
+    }
+
+    /// Skips discoveries of `MockMdnsService`s registered on this thread, which stand in for
+    /// the services of this process. Injected discoveries are still delivered.
+    fn set_ignore_local(&mut self, ignore_local: bool) {
+        self.state.borrow_mut().ignore_local = ignore_local;
+    }
+
+    fn set_address_family(&mut self, family: AddressFamily) {
+        self.address_family = family;
+    }
+
+    fn set_wide_area(&mut self, domain: &str) {
+        self.wide_area_domain = Some(domain.to_string());
+    }
+
+    fn set_force_multicast(&mut self, force_multicast: bool) {
+        self.force_multicast = force_multicast;
+    }
+
+    fn set_resolve_address(&mut self, resolve_address: bool) {
+        self.state.borrow_mut().resolve_address = resolve_address;
+    }
+
+    /// Only services registered or injected in one of the `domains` are delivered; trailing
+    /// dots are ignored when comparing domains.
+    fn set_domains(&mut self, domains: Vec<String>) {
+        self.wide_area_domain = None;
+        self.state.borrow_mut().domains = domains;
+    }
+
+    fn set_max_duration(&mut self, max_duration: Duration) {
+        self.state.borrow_mut().max_duration = Some(max_duration);
+    }
+
+    /// Limits how many discoveries are delivered per poll, as if each poll completed one batch
+    /// of resolves.
+    fn set_max_concurrent_resolves(&mut self, max: usize) {
+        self.state.borrow_mut().max_concurrent_resolves = Some(max.max(1));
+    }
+
+    fn set_name_prefix(&mut self, prefix: &str) {
+        let prefix = Some(prefix.to_string()).filter(|p| !p.is_empty());
+        self.state.borrow_mut().name_prefix = prefix;
+    }
+
+    /// Delivers the most recent discovery of every service delivered so far again on the first
+    /// poll after each `interval`.
+    fn set_refresh_interval(&mut self, interval: Duration) {
+        self.state.borrow_mut().refresh_interval = Some(interval);
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.state.borrow_mut().service_discovered_callback = Some(service_discovered_callback);
+    }
+
+    fn set_service_removed_callback(
+        &mut self,
+        service_removed_callback: Box<ServiceRemovedCallback>,
+    ) {
+        self.state.borrow_mut().service_removed_callback = Some(service_removed_callback);
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any>) {
+        self.state.borrow_mut().user_context = Some(Arc::from(context));
+    }
+
+    /// Starts browsing this thread's mock registry. Every matching service registered or
+    /// injected so far, and any added later, is delivered on the following polls.
+    fn browse_services(&mut self) -> Result<MockEventLoop<'_>> {
+        browser::check_force_multicast(self.wide_area_domain.is_some(), self.force_multicast)?;
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.expires_at = state.max_duration.map(|d| Instant::now() + d);
+            state.expired = false;
+        }
+
+        self.restart()
+    }
+
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        if !self.browsing {
+            return Err("browser is not browsing".into());
+        }
+
+        MockEventLoop::new(MockTarget::Browser(self.state.clone())).poll(timeout)
+    }
+
+    fn pause(&mut self) {
+        if self.browsing {
+            self.browsing = false;
+            self.paused = true;
+        }
+    }
+
+    /// Resumes browsing, delivering every matching service in the registry again as the
+    /// platform implementations do.
+    fn resume(&mut self) -> Result<MockEventLoop<'_>> {
+        if !self.paused {
+            return Err("browser is not paused".into());
+        }
+
+        self.restart()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.browsing = false;
+        self.paused = false;
+
+        let mut state = self.state.borrow_mut();
+        state.refreshed.clear();
+        state.discovered.clear();
+        state.stats.clear();
+        state.stopped = true;
+
+        Ok(())
+    }
+
+    /// Delivers the most recent discovery of the service with the specified `name` again on the
+    /// next poll.
+    fn refresh(&mut self, name: &str) -> Result<()> {
+        let service_type = self.state.borrow().service_type.clone();
+        let discovery = find_discovery(name, &service_type)
+            .ok_or_else(|| format!("no discovered service named `{}`", name))?;
+
+        self.state.borrow_mut().refreshed.push(Ok(discovery));
+
+        Ok(())
+    }
+
+    fn instance_stats(&self, name: &str) -> Option<InstanceStats> {
+        self.state.borrow().stats.get(name)
+    }
+
+    /// Returns the most recent discovery of the service with the specified `name` and
+    /// `service_type`. The `timeout` is ignored since lookups complete immediately.
+    fn resolve(
+        name: &str,
+        service_type: ServiceType,
+        _timeout: Duration,
+    ) -> Result<ServiceDiscovery> {
+        find_discovery(name, &service_type)
+            .ok_or_else(|| format!("timed out resolving service `{}`", name).into())
+    }
+
+    /// Looks the service up when the returned handle is first polled, or once `timeout` has
+    /// elapsed if it has not been registered or injected by then.
+    fn resolve_start(
+        name: &str,
+        service_type: ServiceType,
+        timeout: Duration,
+        callback: Box<ServiceDiscoveredCallback>,
+    ) -> Result<MockResolveHandle> {
+        Ok(MockResolveHandle {
+            name: name.to_string(),
+            service_type,
+            deadline: Instant::now() + timeout,
+            callback: Some(callback),
+        })
+    }
+
+    /// Returns the domains of every service registered or injected on this thread, in the order
+    /// they first appeared. The `timeout` is ignored.
+    fn browse_domains(_timeout: Duration) -> Result<Vec<String>> {
+        NETWORK.with(|n| {
+            let mut domains: Vec<String> = vec![];
+            for (_, event) in &n.borrow().events {
+                if let MockEvent::Found(Ok(discovery)) = event {
+                    if !domains.contains(discovery.domain()) {
+                        domains.push(discovery.domain().clone());
+                    }
+                }
+            }
+            Ok(domains)
+        })
+    }
+}
+
+/// Mock implementation of [`TResolveHandle`], returned by `MockMdnsBrowser::resolve_start()`.
+///
+/// [`TResolveHandle`]: ../browser/trait.TResolveHandle.html
+pub struct MockResolveHandle {
+    name: String,
+    service_type: ServiceType,
+    deadline: Instant,
+    callback: Option<Box<ServiceDiscoveredCallback>>,
+}
+
+impl std::fmt::Debug for MockResolveHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockResolveHandle")
+            .field("name", &self.name)
+            .field("service_type", &self.service_type)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl TResolveHandle for MockResolveHandle {
+    fn poll(&mut self, _timeout: Duration) -> Result<()> {
+        if self.callback.is_none() {
+            return Ok(());
+        }
+
+        let result = match find_discovery(&self.name, &self.service_type) {
+            Some(discovery) => Ok(discovery),
+            None if Instant::now() >= self.deadline => Err(Error::with_kind(
+                ErrorKind::TimedOut,
+                format!("timed out resolving service `{}`", self.name),
+            )),
+            None => return Ok(()),
+        };
+
+        if let Some(mut callback) = self.callback.take() {
+            let _ = callback(result, None);
+        }
+
+        Ok(())
+    }
+
+    fn cancel(&mut self) {
+        self.callback = None;
+    }
+
+    fn is_done(&self) -> bool {
+        self.callback.is_none()
+    }
+}
+
+/// Returns the most recent discovery of `name` that has not been removed since.
+fn find_discovery(name: &str, service_type: &ServiceType) -> Option<ServiceDiscovery> {
+    NETWORK.with(|n| {
+        let mut removed = vec![];
+
+        n.borrow()
+            .events
+            .iter()
+            .rev()
+            .filter(|(t, _)| is_same_type(t, service_type))
+            .find_map(|(_, e)| match e {
+                MockEvent::Found(Ok(d)) if d.name() == name && !removed.contains(&copy_of(d)) => {
+                    Some(Some(d.clone()))
+                }
+                MockEvent::Removed(n, None) if n == name => Some(None),
+                MockEvent::Removed(n, Some(copy)) if n == name => {
+                    removed.push(copy.clone());
+                    None
+                }
+                _ => None,
+            })
+            .flatten()
+    })
+}
+
+#[derive(Debug)]
+enum MockTarget {
+    Service(Rc<RefCell<MockServiceState>>),
+    Browser(Rc<RefCell<MockBrowserState>>),
+}
+
+impl IntoIterator for MockMdnsBrowser {
+    type Item = Result<ServiceEvent>;
+    type IntoIter = ServiceIter<Self>;
+
+    /// Starts browsing and returns a blocking iterator over the discovered services.
+    fn into_iter(self) -> ServiceIter<Self> {
+        ServiceIter::new(self)
+    }
+}
+
+/// Mock implementation of [`TEventLoop`], returned by [`MockMdnsService`] and
+/// [`MockMdnsBrowser`].
+///
+/// Polling never blocks; the `timeout` passed to `poll()` is ignored.
+///
+/// [`TEventLoop`]: ../event_loop/trait.TEventLoop.html
+/// [`MockMdnsService`]: struct.MockMdnsService.html
+/// [`MockMdnsBrowser`]: struct.MockMdnsBrowser.html
+#[derive(Debug)]
+pub struct MockEventLoop<'a> {
+    target: MockTarget,
+    phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> MockEventLoop<'a> {
+    fn new(target: MockTarget) -> Self {
+        Self {
+            target,
+            phantom: PhantomData,
+        }
+    }
+
+    fn poll_service(state: &RefCell<MockServiceState>) -> usize {
+        let (result, mut callback, user_context) = {
+            let mut state = state.borrow_mut();
+            let result = match state.pending.take() {
+                Some(result) => result,
+                None => return 0,
+            };
+            state.registration = Some(result.clone());
+            (
+                result,
+                state.registered_callback.take(),
+                state.user_context.clone(),
+            )
+        };
+
+        // the state is not borrowed while the callback runs so it may use other mocks freely
+        let flow = match &mut callback {
+            Some(f) => f(result, user_context),
+            None => ControlFlow::Continue(()),
+        };
+
+        let mut state = state.borrow_mut();
+        state.registered_callback = callback;
+        state.stopped = flow.is_break();
+
+        1
+    }
+
+    fn next_event(state: &mut MockBrowserState) -> Option<Result<ServiceDiscovery>> {
+        if state.expired {
+            return None;
+        } else if matches!(state.expires_at, Some(at) if Instant::now() >= at) {
+            state.expired = true;
+            return Some(Err(Error::with_kind(
+                ErrorKind::TimedOut,
+                "browser reached its maximum duration",
+            )));
+        }
+
+        if !state.refreshed.is_empty() {
+            return Some(state.refreshed.remove(0));
+        }
+
+        NETWORK.with(|n| {
+            let network = n.borrow();
+
+            while let Some((t, event)) = network.events.get(state.cursor) {
+                state.cursor += 1;
+
+                if !is_same_type(t, &state.service_type) {
+                    continue;
+                }
+
+                match event {
+                    MockEvent::Removed(name, copy) => Self::remove_copies(state, t, name, copy),
+                    MockEvent::Found(e)
+                        if is_in_domains(e, &state.domains)
+                            && !(state.ignore_local
+                                && is_registered(e, &network.registrations))
+                            && has_name_prefix(e, state.name_prefix.as_deref()) =>
+                    {
+                        // recorded before looking ahead, so that a removal that follows is
+                        // reported
+                        if let Ok(discovery) = e {
+                            let name = discovery.name();
+                            if !state.discovered.iter().any(|(n, _)| n == name) {
+                                state.stats.record_seen(name);
+                            }
+
+                            let copy = (name.clone(), copy_of(discovery));
+                            if !state.discovered.contains(&copy) {
+                                state.discovered.push(copy);
+                            }
+                        }
+                        return Some(e.clone());
+                    }
+                    MockEvent::Found(_) => {}
+                }
+            }
+
+            None
+        })
+    }
+
+    /// Forgets the discovered copies of `name` matching `copy`, or all of them if `None`, and
+    /// queues the removal of the service from each domain it no longer has a copy in.
+    fn remove_copies(
+        state: &mut MockBrowserState,
+        service_type: &ServiceType,
+        name: &str,
+        copy: &Option<(u32, String)>,
+    ) {
+        let mut domains: Vec<String> = vec![];
+
+        state.discovered.retain(|(n, c)| {
+            let removed = n == name && (copy.is_none() || copy.as_ref() == Some(c));
+            if removed && !domains.contains(&c.1) {
+                domains.push(c.1.clone());
+            }
+            !removed
+        });
+
+        // the service is still reachable in a domain it has another copy in
+        domains.retain(|d| !state.discovered.iter().any(|(n, c)| n == name && c.1 == *d));
+
+        if !domains.is_empty() && !state.discovered.iter().any(|(n, _)| n == name) {
+            state.stats.record_removed(name);
+        }
+
+        for domain in domains {
+            state.removals.push_back(
+                ServiceRemoval::builder()
+                    .name(name.to_string())
+                    .service_type(service_type.clone())
+                    .domain(domain)
+                    .build()
+                    .unwrap(),
+            );
+        }
+    }
+
+    /// Queues the services delivered so far to be delivered again if the refresh interval has
+    /// elapsed.
+    fn refresh_if_due(state: &mut MockBrowserState) {
+        let interval = match state.refresh_interval {
+            Some(interval) if !state.expired => interval,
+            _ => return,
+        };
+
+        let now = Instant::now();
+        match state.next_refresh_at {
+            Some(at) if now < at => return,
+            Some(_) => {}
+            None => {
+                state.next_refresh_at = Some(now + interval);
+                return;
+            }
+        }
+
+        state.next_refresh_at = Some(now + interval);
+
+        let mut names: Vec<String> = vec![];
+        for (name, _) in &state.discovered {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+
+        for name in names {
+            if let Some(discovery) = find_discovery(&name, &state.service_type) {
+                state.refreshed.push(Ok(discovery));
+            }
+        }
+    }
+
+    /// Delivers pending events in a single batch, flagging all but the last as `more_coming`.
+    fn poll_browser(state: &RefCell<MockBrowserState>) -> usize {
+        Self::refresh_if_due(&mut state.borrow_mut());
+
+        let limit = state.borrow().max_concurrent_resolves;
+        let mut handled = 0;
+        let mut removed = 0;
+        let mut next = Self::next_event(&mut state.borrow_mut());
+
+        while let Some(mut result) = next.take() {
+            let (mut callback, user_context, resolve_address) = {
+                let mut state = state.borrow_mut();
+
+                if let Ok(discovery) = &result {
+                    state.stats.record_resolved(discovery.name());
+                }
+
+                next = Self::next_event(&mut state);
+                (
+                    state.service_discovered_callback.take(),
+                    state.user_context.clone(),
+                    state.resolve_address,
+                )
+            };
+
+            if let Ok(discovery) = &mut result {
+                discovery.set_more_coming(next.is_some());
+                if !resolve_address {
+                    discovery.clear_address();
+                }
+            }
+
+            // the state is not borrowed while the callback runs so it may use other mocks freely
+            let flow = match &mut callback {
+                Some(f) => f(result, user_context),
+                None => ControlFlow::Continue(()),
+            };
+
+            {
+                let mut state = state.borrow_mut();
+                state.service_discovered_callback = callback;
+                handled += 1;
+
+                // events that were looked ahead are replayed if browsing is restarted
+                if flow.is_break() {
+                    state.stopped = true;
+                    break;
+                }
+
+                // the rest are still waiting to be resolved
+                if matches!(limit, Some(limit) if handled >= limit) {
+                    if let Some(next) = next.take() {
+                        state.refreshed.insert(0, next);
+                    }
+                    break;
+                }
+            }
+
+            removed += Self::deliver_removals(state);
+        }
+
+        if !state.borrow().stopped {
+            removed += Self::deliver_removals(state);
+        }
+
+        handled + removed
+    }
+
+    /// Passes the removals found while looking for the next discovery to the removed callback,
+    /// in the order they happened.
+    fn deliver_removals(state: &RefCell<MockBrowserState>) -> usize {
+        let mut delivered = 0;
+
+        loop {
+            let (removal, mut callback, user_context) = {
+                let mut state = state.borrow_mut();
+                let removal = match state.removals.pop_front() {
+                    Some(removal) if !state.stopped => removal,
+                    _ => return delivered,
+                };
+                (
+                    removal,
+                    state.service_removed_callback.take(),
+                    state.user_context.clone(),
+                )
+            };
+
+            let flow = match &mut callback {
+                Some(f) => f(removal, user_context),
+                None => ControlFlow::Continue(()),
+            };
+
+            let mut state = state.borrow_mut();
+            state.service_removed_callback = callback;
+            state.stopped = flow.is_break();
+            delivered += 1;
+        }
+    }
+}
+
+impl<'a> TEventLoop for MockEventLoop<'a> {
+    /// Delivers all pending events to the callback. Never blocks.
+    fn poll(&self, _timeout: Duration) -> Result<()> {
+        self.try_poll().map(|_| ())
+    }
+
+    fn try_poll(&self) -> Result<usize> {
+        if self.is_stopped() {
+            return Ok(0);
+        }
+
+        Ok(match &self.target {
+            MockTarget::Service(state) => Self::poll_service(state),
+            MockTarget::Browser(state) => Self::poll_browser(state),
+        })
+    }
+
+    fn wait_for_registration(&self, _timeout: Duration) -> Result<ServiceRegistration> {
+        match &self.target {
+            MockTarget::Service(state) => {
+                if state.borrow().registration.is_none() {
+                    self.try_poll()?;
+                }
+
+                state
+                    .borrow()
+                    .registration
+                    .clone()
+                    .unwrap_or_else(|| Err("event loop stopped before registration".into()))
+            }
+            MockTarget::Browser(_) => Err("event loop is not driving a service".into()),
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        match &self.target {
+            MockTarget::Service(state) => state.borrow().stopped,
+            MockTarget::Browser(state) => state.borrow().stopped,
+        }
+    }
+}
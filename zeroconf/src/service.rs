@@ -1,15 +1,78 @@
 //! Trait definition for cross-platform service.
 
-use crate::{EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
+use crate::error::{Error, ErrorKind};
+use crate::event_loop::TEventLoop;
+use crate::{NetworkInterface, Result, ServiceType, TxtRecord};
 use std::any::Any;
-use std::sync::Arc;
+use std::ops::{BitOr, BitOrAssign, ControlFlow};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 /// Interface for interacting with underlying mDNS service implementation registration
 /// capabilities.
+///
+/// Callbacks are only ever dispatched while an `EventLoop` is being polled, and an `EventLoop`
+/// borrows the service it drives, so a service cannot be dropped during a dispatch. Dropping a
+/// service frees its daemon objects before the state shared with its callbacks, so the
+/// [`ServiceRegisteredCallback`] is never invoked once dropping has begun.
+///
+/// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
 pub trait TMdnsService {
+    /// The [`TEventLoop`] returned by `register()` to drive this service.
+    ///
+    /// [`TEventLoop`]: ../event_loop/trait.TEventLoop.html
+    type EventLoop<'a>: TEventLoop
+    where
+        Self: 'a;
+
     /// Creates a new `MdnsService` with the specified `ServiceType` (e.g. `_http._tcp`) and `port`.
+    ///
+    /// If `service_type` was created with `ServiceType::new_with_domain()`, the service is
+    /// registered in its domain, as if `set_domain()` had been called with it.
     fn new(service_type: ServiceType, port: u16) -> Self;
 
+    /// Creates a new `MdnsService` configured from the specified [`ServiceInfo`] at once, ready
+    /// to be `register()`ed. Fields of `info` that are `None` keep their defaults.
+    ///
+    /// Returns `Err` with [`ErrorKind::Unsupported`] if `info` specifies a TTL, since neither
+    /// Bonjour nor Avahi allow choosing the TTL of a service's records.
+    ///
+    /// [`ServiceInfo`]: struct.ServiceInfo.html
+    /// [`ErrorKind::Unsupported`]: ../error/enum.ErrorKind.html#variant.Unsupported
+    fn from_info(info: ServiceInfo) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        if info.ttl.is_some() {
+            return Err(Error::with_kind(
+                ErrorKind::Unsupported,
+                "the TTL of a service's records cannot be set",
+            ));
+        }
+
+        let mut service = Self::new(info.service_type, info.port);
+
+        if let Some(name) = &info.name {
+            service.set_name(name);
+        }
+
+        if let Some(domain) = &info.domain {
+            service.set_domain(domain);
+        }
+
+        if let Some(host) = &info.host {
+            service.set_host(host);
+        }
+
+        if let Some(txt) = info.txt {
+            service.set_txt_record(txt);
+        }
+
+        service.set_network_interface(info.interface);
+
+        Ok(service)
+    }
+
     /// Sets the name to register this service under.
     fn set_name(&mut self, name: &str);
 
@@ -19,6 +82,14 @@ pub trait TMdnsService {
     /// all available interfaces.
     fn set_network_interface(&mut self, interface: NetworkInterface);
 
+    /// Registers the service so that it is only visible to browsers on this machine. When
+    /// enabled, this takes precedence over the interface set with `set_network_interface()`.
+    ///
+    /// On macOS this registers on Bonjour's local-only interface. On Linux, the service is
+    /// published on the loopback interface only, which requires Avahi to be allowed to use the
+    /// loopback interface (see `allow-interfaces` in `avahi-daemon.conf`).
+    fn set_local_only(&mut self, local_only: bool);
+
     /// Sets the domain on which to advertise the service.
     ///
     /// Most applications will want to use the default value of `ptr::null()` to register to the
@@ -28,35 +99,355 @@ pub trait TMdnsService {
     /// Sets the SRV target host name.
     ///
     /// Most applications will want to use the default value of `ptr::null()` to use the machine's
-    /// default host name.
+    /// default host name. A host set here must be fully qualified, e.g. `printer.local`.
     fn set_host(&mut self, _host: &str);
 
     /// Sets the optional `TxtRecord` to register this service with.
+    ///
+    /// DNS-SD requires every service to have a TXT record, and neither Bonjour nor Avahi allow
+    /// omitting it. If no `TxtRecord` is set, or the one set is empty, the service is advertised
+    /// with an empty TXT record consisting of a single zero-length string (see [RFC 6763 section
+    /// 6.1]). Both cases are therefore indistinguishable to browsers, which discover the service
+    /// with a `ServiceDiscovery::txt()` of `None`.
+    ///
+    /// [RFC 6763 section 6.1]: https://datatracker.ietf.org/doc/html/rfc6763#section-6.1
     fn set_txt_record(&mut self, txt_record: TxtRecord);
 
+    /// Sets several separate TXT records to register this service with, for protocols that
+    /// split their metadata across multiple TXT records.
+    ///
+    /// The first record is registered as the service's TXT record, exactly like
+    /// `set_txt_record()`. On macOS, each further record is added to the registration as an
+    /// additional TXT record with `DNSServiceAddRecord()`. Avahi only supports one TXT record per
+    /// service, so on Linux the further records are ignored and a warning is logged.
+    ///
+    /// Browsers resolving the service only report the first record on both platforms; the
+    /// additional records must be queried for directly.
+    fn set_txt_records(&mut self, txt_records: Vec<TxtRecord>);
+
+    /// Sets the TTL in seconds of the additional records registered alongside the service,
+    /// i.e. the further TXT records set with `set_txt_records()`, so that records which change
+    /// at a different rate than the SRV/PTR records can expire sooner or later. A `ttl` of `0`
+    /// lets the daemon choose, which is the default.
+    ///
+    /// On macOS this is the TTL passed to `DNSServiceAddRecord()`. Avahi does not publish
+    /// additional records, so on Linux this returns `Err` with [`ErrorKind::Unsupported`]. The
+    /// TTL of the main records cannot be chosen on either platform.
+    ///
+    /// [`ErrorKind::Unsupported`]: ../error/enum.ErrorKind.html#variant.Unsupported
+    fn set_additional_record_ttl(&mut self, ttl: u32) -> Result<()>;
+
     /// Sets the [`ServiceRegisteredCallback`] that is invoked when the service has been
     /// registered.
     ///
     /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
     fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>);
 
+    /// Sets the [`PublishFlags`] passed to Avahi when the service is added to its entry group,
+    /// for advanced control over the probe/announce cycle. Defaults to `PublishFlags::empty()`.
+    ///
+    /// Bonjour has no equivalent, so on macOS this returns `Err` with
+    /// [`ErrorKind::Unsupported`].
+    ///
+    /// [`PublishFlags`]: struct.PublishFlags.html
+    /// [`ErrorKind::Unsupported`]: ../error/enum.ErrorKind.html#variant.Unsupported
+    fn set_publish_flags(&mut self, flags: PublishFlags) -> Result<()>;
+
     /// Sets the optional user context to pass through to the callback. This is useful if you need
     /// to share state between pre and post-callback. The context type must implement `Any`.
     fn set_context(&mut self, context: Box<dyn Any>);
 
     /// Registers and start's the service. Returns an `EventLoop` which can be called to keep
     /// the service alive.
-    fn register(&mut self) -> Result<EventLoop>;
+    ///
+    /// Returns `Err` with [`ErrorKind::NameConflict`] without contacting the daemon if another
+    /// service in this process is already registered with the same type, name, domain and port,
+    /// and with [`ErrorKind::AlreadyRegistered`] if this service is already registered. Call
+    /// `unregister()` first to register it again, e.g. after changing its settings. Settings
+    /// that are invalid together, such as a host set with `set_host()` that is not fully
+    /// qualified, fail with [`ErrorKind::InvalidConfiguration`], also before contacting the daemon.
+    ///
+    /// The service stays registered until it is dropped, at which point it is withdrawn and a
+    /// goodbye (TTL 0) is sent so that browsers on the network are notified promptly instead of
+    /// waiting for the records to expire.
+    ///
+    /// [`ErrorKind::NameConflict`]: ../error/enum.ErrorKind.html#variant.NameConflict
+    /// [`ErrorKind::AlreadyRegistered`]: ../error/enum.ErrorKind.html#variant.AlreadyRegistered
+    /// [`ErrorKind::InvalidConfiguration`]: ../error/enum.ErrorKind.html#variant.InvalidConfiguration
+    fn register(&mut self) -> Result<Self::EventLoop<'_>>;
+
+    /// Withdraws the service registered with `register()`, sending a goodbye for its records, and
+    /// stops the `EventLoop` that was returned for it. The service can then be registered again.
+    ///
+    /// Does nothing if the service is not registered.
+    fn unregister(&mut self) -> Result<()>;
+
+    /// Withdraws and republishes the records of the service registered with `register()`, so
+    /// that a new burst of announcements is sent, e.g. to reach browsers that joined the network
+    /// after the first announcements were missed. Returns a new `EventLoop` to drive the service,
+    /// as the one returned by `register()` must be dropped first.
+    ///
+    /// The service keeps the name and port it was finally registered under, even if it was
+    /// renamed after a conflict. The registered callback is invoked again once the service has
+    /// been republished. Returns `Err` if the service is not registered.
+    fn reannounce(&mut self) -> Result<Self::EventLoop<'_>>;
+
+    /// Returns the registrations that are currently established for this service, with the
+    /// name, port and domain they were finally registered under.
+    ///
+    /// This is updated before the [`ServiceRegisteredCallback`] is invoked and is empty until the
+    /// service has been registered, or while it is being re-registered (e.g. after a name
+    /// conflict).
+    ///
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    fn registrations(&self) -> Vec<ServiceRegistration>;
+
+    /// Returns the name the service is currently advertised under, or `None` if it is not
+    /// registered. Unlike the name passed to the [`ServiceRegisteredCallback`], this reflects a
+    /// rename after a later conflict, e.g. to display the live name in a status UI.
+    ///
+    /// Neither daemon provides a way to query the name of a published service, so this is the
+    /// name of the most recent entry in `registrations()`, which is kept up to date from the
+    /// daemon's notifications. Bonjour renames the service itself (e.g. `"name (2)"`), while on
+    /// Avahi the service is published again under the alternative name Avahi suggests (e.g.
+    /// `"name #2"`).
+    ///
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    fn current_name(&self) -> Option<String> {
+        self.registrations().last().map(|r| r.name().clone())
+    }
+}
+
+/// Flags controlling how Avahi publishes a service's records. Flags can be combined with `|`.
+///
+/// See `AvahiPublishFlags` in the [Avahi documentation] for details.
+///
+/// [Avahi documentation]: https://avahi.org/doxygen/html/defs_8h.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PublishFlags(u32);
+
+impl PublishFlags {
+    /// The records are unique to this host: Avahi probes for conflicting records before
+    /// announcing them and treats any conflict as a collision.
+    pub const UNIQUE: Self = Self(1);
+    /// Skip the probing phase and announce the records immediately, even if another host already
+    /// owns them. Only sensible for records that are known to be unique.
+    pub const NO_PROBE: Self = Self(1 << 1);
+    /// Do not send the unsolicited announcements after probing; the records are only sent in
+    /// response to queries.
+    pub const NO_ANNOUNCE: Self = Self(1 << 2);
+    /// Allow other hosts to publish records with the same name and type without it being
+    /// treated as a conflict.
+    pub const ALLOW_MULTIPLE: Self = Self(1 << 3);
+
+    /// Returns a value with no flags set, which is Avahi's default behavior.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the raw `AvahiPublishFlags` bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if every flag in `other` is also set in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for PublishFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PublishFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
 }
 
 /// Callback invoked from [`MdnsService`] once it has successfully registered.
 ///
+/// Like [`ServiceDiscoveredCallback`], this is invoked on the thread polling the service's
+/// [`EventLoop`] and may therefore mutate captured state directly. Returning
+/// `ControlFlow::Break(())` stops the event loop after the current iteration.
+///
 /// # Arguments
 /// * `service` - The service information that was registered
 /// * `context` - The optional user context passed through
 ///
 /// [`MdnsService`]: type.MdnsService.html
-pub type ServiceRegisteredCallback = dyn Fn(Result<ServiceRegistration>, Option<Arc<dyn Any>>);
+/// [`ServiceDiscoveredCallback`]: type.ServiceDiscoveredCallback.html
+/// [`EventLoop`]: type.EventLoop.html
+pub type ServiceRegisteredCallback =
+    dyn FnMut(Result<ServiceRegistration>, Option<Arc<dyn Any>>) -> ControlFlow<()>;
+
+/// Holds the most recent result passed to a service's [`ServiceRegisteredCallback`], shared with
+/// the service's [`EventLoop`] so it can wait on it.
+///
+/// [`ServiceRegisteredCallback`]: type.ServiceRegisteredCallback.html
+/// [`EventLoop`]: type.EventLoop.html
+pub(crate) type RegistrationSlot = Arc<Mutex<Option<Result<ServiceRegistration>>>>;
+
+static CLAIMED_REGISTRATIONS: Mutex<Vec<RegistrationIdentity>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RegistrationIdentity {
+    kind: String,
+    name: Option<String>,
+    domain: Option<String>,
+    port: u16,
+}
+
+/// Checks the settings of a service that are only meaningful together, so that a misconfigured
+/// service fails in `register()` with [`ErrorKind::InvalidConfiguration`] instead of with an
+/// opaque error from the daemon.
+///
+/// An SRV target `host` (e.g. a proxy for another device) must be fully qualified, i.e. include
+/// the domain its address record is published in, and the `domain`, if set, cannot be empty.
+///
+/// [`ErrorKind::InvalidConfiguration`]: ../error/enum.ErrorKind.html#variant.InvalidConfiguration
+pub(crate) fn validate_settings(host: Option<&str>, domain: Option<&str>) -> Result<()> {
+    let invalid = |description: String| {
+        Err(Error::with_kind(
+            ErrorKind::InvalidConfiguration,
+            description,
+        ))
+    };
+
+    if domain == Some("") {
+        return invalid("the domain cannot be empty".to_string());
+    }
+
+    if let Some(host) = host {
+        let is_qualified = matches!(host.trim_end_matches('.').split_once('.'),
+            Some((label, domain)) if !label.is_empty() && !domain.is_empty());
+
+        if !is_qualified {
+            return invalid(format!(
+                "the host `{}` must be fully qualified with its domain, e.g. `{}.local`",
+                host,
+                host.trim_end_matches('.')
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reserves a service identity for the lifetime of the returned claim, so that a second service
+/// in this process registering the same identity fails before reaching the daemon.
+#[derive(Debug)]
+pub(crate) struct RegistrationClaim(RegistrationIdentity);
+
+impl RegistrationClaim {
+    pub(crate) fn new(
+        kind: &str,
+        name: Option<&str>,
+        domain: Option<&str>,
+        port: u16,
+    ) -> Result<Self> {
+        let identity = RegistrationIdentity {
+            kind: kind.to_string(),
+            name: name.map(|n| n.to_string()),
+            domain: domain.map(|d| d.to_string()),
+            port,
+        };
+
+        let mut claimed = CLAIMED_REGISTRATIONS.lock().unwrap();
+
+        if claimed.contains(&identity) {
+            return Err(Error::with_kind(
+                ErrorKind::NameConflict,
+                format!(
+                    "service `{}` of type `{}` on port {} is already registered by this process",
+                    name.unwrap_or("<default>"),
+                    kind,
+                    port
+                ),
+            ));
+        }
+
+        claimed.push(identity.clone());
+
+        Ok(Self(identity))
+    }
+
+    /// Moves the claim to `name` once the daemon has renamed the service after a name collision,
+    /// so that the name the service is actually registered under is reserved. A claim without a
+    /// name stands for the daemon's default name and is left as it is.
+    pub(crate) fn rename(&mut self, name: &str) {
+        if self.0.name.is_none() {
+            return;
+        }
+
+        let mut claimed = CLAIMED_REGISTRATIONS.lock().unwrap();
+        let name = Some(name.to_string());
+
+        if let Some(c) = claimed.iter_mut().find(|c| **c == self.0) {
+            c.name = name.clone();
+        }
+
+        self.0.name = name;
+    }
+}
+
+/// Returns `true` if a service in this process has claimed a registration of `service_type`
+/// (ignoring sub-types) on `port`.
+pub(crate) fn is_claimed(service_type: &ServiceType, port: u16) -> bool {
+    CLAIMED_REGISTRATIONS.lock().unwrap().iter().any(|c| {
+        c.port == port
+            && matches!(ServiceType::from_str(&c.kind), Ok(t)
+                if t.name() == service_type.name() && t.protocol() == service_type.protocol())
+    })
+}
+
+impl Drop for RegistrationClaim {
+    fn drop(&mut self) {
+        let mut claimed = CLAIMED_REGISTRATIONS.lock().unwrap();
+        if let Some(i) = claimed.iter().position(|c| *c == self.0) {
+            claimed.remove(i);
+        }
+    }
+}
+
+/// Describes a service to register with [`TMdnsService::from_info()`], e.g. as read from a
+/// configuration file.
+///
+/// [`TMdnsService::from_info()`]: trait.TMdnsService.html#method.from_info
+#[derive(
+    Builder, BuilderDelegate, Debug, Getters, Clone, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct ServiceInfo {
+    /// The name to register the service under, or `None` to let the daemon choose one
+    #[builder(default)]
+    #[serde(default)]
+    name: Option<String>,
+    service_type: ServiceType,
+    /// The domain to register the service in, or `None` for the default domain
+    #[builder(default)]
+    #[serde(default)]
+    domain: Option<String>,
+    /// The SRV target host name, or `None` for this machine's host name
+    #[builder(default)]
+    #[serde(default)]
+    host: Option<String>,
+    port: u16,
+    #[builder(default)]
+    #[serde(default)]
+    txt: Option<TxtRecord>,
+    #[builder(default)]
+    #[serde(default)]
+    interface: NetworkInterface,
+    /// The TTL of the service's records in seconds, which no platform currently supports
+    #[builder(default)]
+    #[serde(default)]
+    ttl: Option<u32>,
+}
 
 /// Represents a registration event for a [`MdnsService`].
 ///
@@ -65,5 +456,6 @@ pub type ServiceRegisteredCallback = dyn Fn(Result<ServiceRegistration>, Option<
 pub struct ServiceRegistration {
     name: String,
     service_type: ServiceType,
+    port: u16,
     domain: String,
 }
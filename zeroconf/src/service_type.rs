@@ -1,5 +1,6 @@
 //! Data type for constructing a service type
 
+use crate::error::{Error, ErrorKind};
 use crate::Result;
 use std::str::FromStr;
 
@@ -9,39 +10,110 @@ pub struct ServiceType {
     name: String,
     protocol: String,
     sub_types: Vec<String>,
+    #[getter(skip)]
+    #[serde(default)]
+    domain: Option<String>,
 }
 
 impl ServiceType {
     /// Creates a new `ServiceType` with the specified name (e.g. `http`) and protocol (e.g. `tcp`)
     pub fn new(name: &str, protocol: &str) -> Result<Self> {
+        Self::validate(name, protocol)?;
+
         Ok(Self {
-            name: Self::check_part(name)?.to_string(),
-            protocol: Self::check_part(protocol)?.to_string(),
+            name: name.to_string(),
+            protocol: protocol.to_string(),
             sub_types: vec![],
+            domain: None,
         })
     }
 
+    /// Creates a new `ServiceType` with the specified name (e.g. `http`) and protocol (e.g. `tcp`)
+    /// that is pinned to `domain` (e.g. `example.com`).
+    ///
+    /// Services created with this type are registered in `domain`, and browsers created with it
+    /// browse `domain` only, as if `set_domain()` or `set_domains()` had been called with it.
+    pub fn new_with_domain(name: &str, protocol: &str, domain: &str) -> Result<Self> {
+        if domain.is_empty() {
+            return Err(invalid("domain cannot be empty"));
+        }
+
+        let mut service_type = Self::new(name, protocol)?;
+        service_type.domain = Some(domain.to_string());
+
+        Ok(service_type)
+    }
+
+    /// Returns the domain this type is pinned to, or `local.` if it was created without one.
+    pub fn domain(&self) -> &str {
+        self.domain.as_deref().unwrap_or("local.")
+    }
+
+    /// Returns the domain this type was explicitly pinned to with `new_with_domain()`.
+    pub(crate) fn explicit_domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    /// Returns `true` if [`ServiceType::new()`] would accept the specified name and protocol.
+    ///
+    /// [`ServiceType::new()`]: #method.new
+    pub fn is_valid(name: &str, protocol: &str) -> bool {
+        Self::validate(name, protocol).is_ok()
+    }
+
+    /// Checks the specified name and protocol with the same rules as [`ServiceType::new()`],
+    /// returning the `Err` it would return without constructing a `ServiceType`.
+    ///
+    /// [`ServiceType::new()`]: #method.new
+    pub fn validate(name: &str, protocol: &str) -> Result<()> {
+        Self::check_part(name)?;
+        Self::check_part(protocol)?;
+        Ok(())
+    }
+
     /// Creates a new `ServiceType` with the specified name (e.g. `http`) and protocol (e.g. `tcp`)
     /// and sub-types.
+    ///
+    /// Sub-types are only meaningful on a valid base type, so the name and protocol are validated
+    /// the same way as [`ServiceType::new()`]. Each sub-type may only be specified once.
+    ///
+    /// [`ServiceType::new()`]: #method.new
     pub fn with_sub_types(name: &str, protocol: &str, sub_types: Vec<&str>) -> Result<Self> {
-        for sub_type in &sub_types {
-            Self::check_part(sub_type)?;
+        let mut service_type = Self::new(name, protocol)
+            .map_err(|e| invalid(format!("invalid base type for sub-types: {}", e)))?;
+
+        for sub_type in sub_types {
+            Self::check_part(sub_type)
+                .map_err(|e| invalid(format!("invalid sub-type `{}`: {}", sub_type, e)))?;
+
+            if service_type.sub_types.iter().any(|s| s == sub_type) {
+                return Err(invalid(format!("duplicate sub-type: {}", sub_type)));
+            }
+
+            service_type.sub_types.push(sub_type.to_string());
         }
 
-        Ok(Self {
-            name: name.to_string(),
-            protocol: protocol.to_string(),
-            sub_types: sub_types.iter().map(|s| s.to_string()).collect(),
-        })
+        Ok(service_type)
+    }
+
+    /// Returns the form of this type that is passed to the daemon when registering, with any
+    /// sub-types appended as a comma separated list (e.g. `_ipp._tcp,_universal`).
+    pub fn as_registration_string(&self) -> String {
+        let mut s = format!("_{}._{}", self.name, self.protocol);
+        for sub_type in &self.sub_types {
+            s.push_str(",_");
+            s.push_str(sub_type);
+        }
+        s
     }
 
     fn check_part(part: &str) -> Result<&str> {
         if part.contains('.') {
-            Err("invalid character: .".into())
+            Err(invalid("invalid character: ."))
         } else if part.contains(',') {
-            Err("invalid character: ,".into())
+            Err(invalid("invalid character: ,"))
         } else if part.is_empty() {
-            Err("cannot be empty".into())
+            Err(invalid("cannot be empty"))
         } else {
             Ok(part)
         }
@@ -56,18 +128,83 @@ impl ServiceType {
     }
 }
 
+macro_rules! well_known {
+    ($($(#[$doc:meta])* $method:ident => ($name:expr, $protocol:expr)),* $(,)?) => {
+        /// Constructors for well-known DNS-SD service types, as registered with IANA.
+        impl ServiceType {
+            $(
+                $(#[$doc])*
+                pub fn $method() -> Self {
+                    Self {
+                        name: $name.to_string(),
+                        protocol: $protocol.to_string(),
+                        sub_types: vec![],
+                        domain: None,
+                    }
+                }
+            )*
+
+            /// Returns every service type that has a constructor here, e.g. to offer a choice
+            /// of common types in a UI.
+            pub fn well_known() -> Vec<Self> {
+                vec![$(Self::$method()),*]
+            }
+        }
+    };
+}
+
+well_known! {
+    /// `_http._tcp`, for web servers.
+    http => ("http", "tcp"),
+    /// `_https._tcp`, for web servers that are only reachable over TLS.
+    https => ("https", "tcp"),
+    /// `_ipp._tcp`, for printers supporting the Internet Printing Protocol.
+    ipp => ("ipp", "tcp"),
+    /// `_ipps._tcp`, for printers supporting the Internet Printing Protocol over TLS.
+    ipps => ("ipps", "tcp"),
+    /// `_printer._tcp`, for printers supporting the Line Printer Daemon protocol.
+    printer => ("printer", "tcp"),
+    /// `_pdl-datastream._tcp`, for printers accepting raw print jobs on a socket.
+    pdl_datastream => ("pdl-datastream", "tcp"),
+    /// `_scanner._tcp`, for network scanners.
+    scanner => ("scanner", "tcp"),
+    /// `_airplay._tcp`, for AirPlay video receivers.
+    airplay => ("airplay", "tcp"),
+    /// `_raop._tcp`, for AirPlay audio receivers.
+    raop => ("raop", "tcp"),
+    /// `_googlecast._tcp`, for Google Cast receivers.
+    googlecast => ("googlecast", "tcp"),
+    /// `_spotify-connect._tcp`, for Spotify Connect speakers.
+    spotify_connect => ("spotify-connect", "tcp"),
+    /// `_hap._tcp`, for HomeKit accessories.
+    hap => ("hap", "tcp"),
+    /// `_ssh._tcp`, for SSH servers.
+    ssh => ("ssh", "tcp"),
+    /// `_sftp-ssh._tcp`, for SFTP servers.
+    sftp_ssh => ("sftp-ssh", "tcp"),
+    /// `_smb._tcp`, for SMB file shares.
+    smb => ("smb", "tcp"),
+    /// `_afpovertcp._tcp`, for Apple Filing Protocol file shares.
+    afpovertcp => ("afpovertcp", "tcp"),
+    /// `_nfs._tcp`, for NFS file shares.
+    nfs => ("nfs", "tcp"),
+    /// `_ftp._tcp`, for FTP servers.
+    ftp => ("ftp", "tcp"),
+    /// `_workstation._tcp`, advertised by hosts running a workstation, such as Linux desktops.
+    workstation => ("workstation", "tcp"),
+    /// `_device-info._tcp`, which carries a host's model in its TXT record.
+    device_info => ("device-info", "tcp"),
+    /// `_mqtt._tcp`, for MQTT brokers.
+    mqtt => ("mqtt", "tcp"),
+}
+
+fn invalid(description: impl Into<String>) -> Error {
+    Error::with_kind(ErrorKind::InvalidServiceType, description)
+}
+
 impl ToString for ServiceType {
     fn to_string(&self) -> String {
-        format!(
-            "_{}._{}{}",
-            self.name,
-            self.protocol,
-            if !self.sub_types.is_empty() {
-                format!(",_{}", self.sub_types.join(",_"))
-            } else {
-                "".to_string()
-            }
-        )
+        self.as_registration_string()
     }
 }
 
@@ -77,12 +214,12 @@ impl FromStr for ServiceType {
     fn from_str(s: &str) -> Result<Self> {
         let parts: Vec<&str> = s.split(',').collect();
         if parts.is_empty() {
-            return Err("could not parse ServiceType from string".into());
+            return Err(invalid("could not parse ServiceType from string"));
         }
 
         let head: Vec<&str> = parts[0].split('.').collect();
         if head.len() != 2 {
-            return Err("invalid name and protocol".into());
+            return Err(invalid("invalid name and protocol"));
         }
 
         let name = Self::lstrip_underscore(head[0]);
@@ -113,6 +250,63 @@ mod tests {
         ServiceType::new("http", "").expect_err("cannot be empty");
     }
 
+    #[test]
+    fn validate_matches_new() {
+        for (name, protocol) in [
+            ("http", "tcp"),
+            (".http", "tcp"),
+            ("http", ",tcp"),
+            ("", "tcp"),
+        ] {
+            assert_eq!(
+                ServiceType::validate(name, protocol),
+                ServiceType::new(name, protocol).map(|_| ())
+            );
+            assert_eq!(
+                ServiceType::is_valid(name, protocol),
+                ServiceType::new(name, protocol).is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn new_with_domain_success() {
+        let service_type = ServiceType::new_with_domain("http", "tcp", "example.com").unwrap();
+        assert_eq!(service_type.domain(), "example.com");
+        assert_eq!(service_type.as_registration_string(), "_http._tcp");
+        assert_eq!(ServiceType::new("http", "tcp").unwrap().domain(), "local.");
+    }
+
+    #[test]
+    fn new_with_domain_invalid() {
+        ServiceType::new_with_domain("http", "tcp", "").expect_err("domain cannot be empty");
+        ServiceType::new_with_domain(".http", "tcp", "example.com")
+            .expect_err("invalid character: .");
+    }
+
+    #[test]
+    fn with_sub_types_invalid_base() {
+        ServiceType::with_sub_types("", "tcp", vec!["api-v1"])
+            .expect_err("invalid base type for sub-types: cannot be empty");
+        ServiceType::with_sub_types("http", ".tcp", vec!["api-v1"])
+            .expect_err("invalid base type for sub-types: invalid character: .");
+    }
+
+    #[test]
+    fn with_sub_types_invalid_sub_type() {
+        ServiceType::with_sub_types("http", "tcp", vec![""])
+            .expect_err("invalid sub-type ``: cannot be empty");
+        ServiceType::with_sub_types("http", "tcp", vec!["api.v1"])
+            .expect_err("invalid sub-type `api.v1`: invalid character: .");
+    }
+
+    #[test]
+    fn with_sub_types_duplicate() {
+        let err = ServiceType::with_sub_types("http", "tcp", vec!["api-v1", "api-v1"]).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate sub-type: api-v1");
+        assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+    }
+
     #[test]
     fn must_have_name_and_protocol() {
         ServiceType::from_str("_http").expect_err("invalid name and protocol");
@@ -136,6 +330,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn as_registration_string_no_sub_types() {
+        assert_eq!(
+            ServiceType::new("ipp", "tcp")
+                .unwrap()
+                .as_registration_string(),
+            "_ipp._tcp"
+        );
+    }
+
+    #[test]
+    fn as_registration_string_one_sub_type() {
+        assert_eq!(
+            ServiceType::with_sub_types("ipp", "tcp", vec!["universal"])
+                .unwrap()
+                .as_registration_string(),
+            "_ipp._tcp,_universal"
+        );
+    }
+
+    #[test]
+    fn as_registration_string_many_sub_types() {
+        assert_eq!(
+            ServiceType::with_sub_types("ipp", "tcp", vec!["universal", "print", "cups"])
+                .unwrap()
+                .as_registration_string(),
+            "_ipp._tcp,_universal,_print,_cups"
+        );
+    }
+
+    #[test]
+    fn well_known_success() {
+        assert_eq!(
+            ServiceType::http(),
+            ServiceType::new("http", "tcp").unwrap()
+        );
+        assert_eq!(
+            ServiceType::pdl_datastream().as_registration_string(),
+            "_pdl-datastream._tcp"
+        );
+
+        let well_known = ServiceType::well_known();
+        assert!(well_known.contains(&ServiceType::airplay()));
+
+        for (i, service_type) in well_known.iter().enumerate() {
+            assert!(ServiceType::is_valid(
+                service_type.name(),
+                service_type.protocol()
+            ));
+            assert_eq!(
+                &ServiceType::from_str(&service_type.to_string()).unwrap(),
+                service_type
+            );
+            assert!(!well_known[..i].contains(service_type));
+        }
+    }
+
     #[test]
     fn from_str_success() {
         assert_eq!(
@@ -0,0 +1,422 @@
+use crate::error::ErrorKind;
+use crate::prelude::*;
+use crate::{MdnsBrowser, ResolvedRecord, ServiceDiscovery, ServiceType, TxtRecord};
+use std::cell::Cell;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+struct Metadata {
+    version: String,
+    path: Option<String>,
+    port: Option<u16>,
+    secure: Option<bool>,
+}
+
+fn discovery(txt: Option<TxtRecord>) -> ServiceDiscovery {
+    ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address("127.0.0.1".to_string())
+        .port(8080)
+        .txt(txt)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn txt_as_success() {
+    super::setup();
+
+    let mut txt = TxtRecord::new();
+    txt.insert("version", "1.0").unwrap();
+    txt.insert("path", "/api").unwrap();
+    txt.insert("port", "8443").unwrap();
+
+    assert_eq!(
+        discovery(Some(txt)).txt_as::<Metadata>().unwrap(),
+        Metadata {
+            version: "1.0".to_string(),
+            path: Some("/api".to_string()),
+            port: Some(8443),
+            secure: None,
+        }
+    );
+}
+
+#[test]
+fn txt_as_missing_field() {
+    super::setup();
+
+    let mut txt = TxtRecord::new();
+    txt.insert("path", "/api").unwrap();
+
+    discovery(Some(txt))
+        .txt_as::<Metadata>()
+        .expect_err("missing field `version`");
+    discovery(None)
+        .txt_as::<Metadata>()
+        .expect_err("missing field `version`");
+}
+
+#[test]
+fn txt_as_mismatched_field() {
+    super::setup();
+
+    let mut txt = TxtRecord::new();
+    txt.insert("version", "1.0").unwrap();
+    txt.insert("port", "https").unwrap();
+
+    discovery(Some(txt))
+        .txt_as::<Metadata>()
+        .expect_err("invalid digit found in string");
+}
+
+#[test]
+fn resolve_unknown_service_times_out() {
+    super::setup();
+
+    let start = Instant::now();
+
+    MdnsBrowser::resolve(
+        "resolve_unknown_service_times_out",
+        ServiceType::new("http", "tcp").unwrap(),
+        Duration::from_millis(500),
+    )
+    .expect_err("timed out resolving service `resolve_unknown_service_times_out`");
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn resolve_start_cancelled_in_flight_delivers_nothing() {
+    super::setup();
+
+    let delivered: Rc<Cell<usize>> = Rc::default();
+
+    let mut handle = MdnsBrowser::resolve_start(
+        "resolve_start_cancelled_in_flight_delivers_nothing",
+        ServiceType::new("http", "tcp").unwrap(),
+        Duration::from_secs(10),
+        Box::new({
+            let delivered = delivered.clone();
+            move |_, _| {
+                delivered.set(delivered.get() + 1);
+                ControlFlow::Continue(())
+            }
+        }),
+    )
+    .unwrap();
+
+    handle.poll(Duration::from_millis(100)).unwrap();
+    assert!(!handle.is_done());
+
+    handle.cancel();
+    assert!(handle.is_done());
+
+    handle.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(0, delivered.get());
+}
+
+#[test]
+#[ignore = "requires a network that advertises a browse domain via b._dns-sd._udp"]
+fn browse_domains_returns_advertised_domains() {
+    super::setup();
+
+    let domains = MdnsBrowser::browse_domains(Duration::from_secs(5)).unwrap();
+
+    assert!(!domains.is_empty());
+    assert!(domains.iter().all(|d| !d.is_empty()));
+}
+
+#[test]
+fn endpoints_ipv4() {
+    super::setup();
+    assert_eq!(
+        discovery(None).endpoints(),
+        vec!["127.0.0.1:8080".parse().unwrap()]
+    );
+}
+
+#[test]
+fn endpoints_link_local_ipv6_is_scoped() {
+    super::setup();
+
+    let service = ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address("fe80::1234:5678:9abc:def0".to_string())
+        .port(8080)
+        .txt(None)
+        .interface_index(2)
+        .build()
+        .unwrap();
+
+    let endpoints = service.endpoints();
+    assert_eq!(
+        endpoints,
+        vec!["[fe80::1234:5678:9abc:def0%2]:8080".parse().unwrap()]
+    );
+    assert_eq!(
+        endpoints[0].to_string(),
+        "[fe80::1234:5678:9abc:def0%2]:8080"
+    );
+}
+
+#[test]
+fn to_url_link_local_ipv6_is_scoped() {
+    super::setup();
+
+    let service_at = |address: &str| {
+        ServiceDiscovery::builder()
+            .name("foo".to_string())
+            .service_type(ServiceType::new("http", "tcp").unwrap())
+            .domain("local".to_string())
+            .host_name(String::new())
+            .address(address.to_string())
+            .port(8080)
+            .txt(None)
+            .interface_index(2)
+            .build()
+            .unwrap()
+    };
+
+    assert_eq!(
+        Some("http://[fe80::1234:5678:9abc:def0%252]:8080/".to_string()),
+        service_at("fe80::1234:5678:9abc:def0").to_url()
+    );
+    assert_eq!(
+        Some("http://[2001:db8::1]:8080/".to_string()),
+        service_at("2001:db8::1").to_url()
+    );
+}
+
+fn discovery_at(address: &str) -> ServiceDiscovery {
+    ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address(address.to_string())
+        .port(8080)
+        .txt(None)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn address_families_ipv4_only() {
+    let service = discovery_at("192.168.1.2");
+    assert_eq!(1, service.address_count());
+    assert!(service.has_ipv4());
+    assert!(!service.has_ipv6());
+}
+
+#[test]
+fn address_families_ipv6_only() {
+    let service = discovery_at("fe80::1");
+    assert_eq!(1, service.address_count());
+    assert!(!service.has_ipv4());
+    assert!(service.has_ipv6());
+}
+
+fn discovery_with(addresses: &[&str]) -> ServiceDiscovery {
+    ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address(addresses[0].to_string())
+        .addresses(addresses.iter().map(|a| a.parse().unwrap()).collect())
+        .port(8080)
+        .txt(None)
+        .interface_index(2)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn address_families_dual_stack() {
+    let service = discovery_with(&["192.168.1.2", "fe80::1", "2001:db8::1"]);
+
+    assert_eq!(3, service.address_count());
+    assert!(service.has_ipv4());
+    assert!(service.has_ipv6());
+}
+
+#[test]
+fn endpoints_include_every_address() {
+    super::setup();
+
+    let service = discovery_with(&["192.168.1.2", "fe80::1", "2001:db8::1"]);
+
+    assert_eq!(
+        service.endpoints(),
+        vec![
+            "192.168.1.2:8080".parse().unwrap(),
+            "[fe80::1%2]:8080".parse().unwrap(),
+            "[2001:db8::1]:8080".parse().unwrap(),
+        ]
+    );
+}
+
+#[test]
+fn address_families_unresolved() {
+    let service = discovery_at("");
+    assert_eq!(0, service.address_count());
+    assert!(!service.has_ipv4());
+    assert!(!service.has_ipv6());
+}
+
+#[test]
+fn endpoints_invalid_address_is_empty() {
+    super::setup();
+
+    let service = ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address("not an address".to_string())
+        .port(8080)
+        .txt(None)
+        .build()
+        .unwrap();
+
+    assert!(service.endpoints().is_empty());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn browser_no_fail_browses_with_running_daemon() {
+    super::setup();
+
+    let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_no_fail(true);
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let event_loop = browser.browse_services().unwrap();
+    event_loop.poll(Duration::from_secs(0)).unwrap();
+}
+
+#[test]
+fn to_url_http() {
+    super::setup();
+
+    assert_eq!(
+        Some("http://foo.local:8080/".to_string()),
+        discovery(None).to_url()
+    );
+}
+
+#[test]
+fn to_url_https() {
+    super::setup();
+
+    let discovery = ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("https", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name(String::new())
+        .address("fe80::1".to_string())
+        .port(8443)
+        .txt(None)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        Some("https://[fe80::1]:8443/".to_string()),
+        discovery.to_url()
+    );
+}
+
+#[test]
+fn to_url_with_path() {
+    super::setup();
+
+    let mut txt = TxtRecord::new();
+    txt.insert("path", "/api").unwrap();
+
+    assert_eq!(
+        Some("http://foo.local:8080/api".to_string()),
+        discovery(Some(txt)).to_url()
+    );
+}
+
+#[test]
+fn to_url_non_http_is_none() {
+    super::setup();
+
+    let discovery = ServiceDiscovery::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("ipp", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address("127.0.0.1".to_string())
+        .port(631)
+        .txt(None)
+        .build()
+        .unwrap();
+
+    assert_eq!(None, discovery.to_url());
+}
+
+#[test]
+fn records_without_resolution_are_empty() {
+    assert!(discovery(None).records().is_empty());
+}
+
+#[test]
+fn resolved_record_srv_encodes_target() {
+    let record = ResolvedRecord::srv("foo._http._tcp.local", 8080, "my-host.local.", None);
+
+    assert_eq!(ResolvedRecord::SRV, *record.rrtype());
+    assert_eq!("foo._http._tcp.local", record.name());
+    assert_eq!(
+        b"\0\0\0\0\x1f\x90\x07my-host\x05local\0".to_vec(),
+        *record.rdata()
+    );
+    assert_eq!(None, *record.ttl());
+}
+
+#[test]
+fn resolved_record_empty_txt_holds_empty_string() {
+    let record = ResolvedRecord::txt("foo._http._tcp.local", &[], None);
+
+    assert_eq!(ResolvedRecord::TXT, *record.rrtype());
+    assert_eq!(vec![0], *record.rdata());
+}
+
+#[test]
+fn resolved_record_address() {
+    let v4 = ResolvedRecord::address("my-host.local", "192.168.1.2".parse().unwrap(), Some(120));
+    assert_eq!(ResolvedRecord::A, *v4.rrtype());
+    assert_eq!(vec![192, 168, 1, 2], *v4.rdata());
+    assert_eq!(Some(120), *v4.ttl());
+
+    let v6 = ResolvedRecord::address("my-host.local", "fe80::1".parse().unwrap(), None);
+    assert_eq!(ResolvedRecord::AAAA, *v6.rrtype());
+    assert_eq!(16, v6.rdata().len());
+}
+
+#[test]
+fn browser_force_multicast_with_wide_area_is_invalid_in_either_order() {
+    let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_wide_area("example.com");
+    browser.set_force_multicast(true);
+
+    let error = browser.browse_services().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+
+    let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_force_multicast(true);
+    browser.set_wide_area("example.com");
+
+    let error = browser.browse_services().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+}
@@ -0,0 +1,31 @@
+use crate::{daemon_info, resolve_host};
+use std::ffi::CStr;
+use std::time::Duration;
+
+#[test]
+fn daemon_info_reports_backend() {
+    super::setup();
+
+    let info = daemon_info().unwrap();
+
+    #[cfg(target_os = "linux")]
+    assert_eq!("avahi", info.backend());
+    #[cfg(target_vendor = "apple")]
+    assert_eq!("bonjour", info.backend());
+
+    assert!(info.version().is_some());
+}
+
+#[test]
+fn resolve_host_resolves_this_machine() {
+    super::setup();
+
+    let mut buf = [0 as libc::c_char; 256];
+    assert_eq!(0, unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) });
+
+    let host_name = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+    let host = format!("{}.local", host_name.trim_end_matches(".local"));
+
+    let addresses = resolve_host(&host, None, Duration::from_secs(10)).unwrap();
+    assert!(!addresses.is_empty());
+}
@@ -0,0 +1,49 @@
+use crate::prelude::*;
+use crate::{MdnsBrowser, ServiceType};
+use std::ops::ControlFlow;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn try_poll_idle_returns_zero() {
+    super::setup();
+
+    let mut browser = MdnsBrowser::new(ServiceType::new("zeroconf-idle", "tcp").unwrap());
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let event_loop = browser.browse_services().unwrap();
+
+    // drain the events emitted while the browser starts up
+    let settle = Instant::now();
+    while settle.elapsed() < Duration::from_secs(1) {
+        event_loop.try_poll().unwrap();
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let start = Instant::now();
+    assert_eq!(event_loop.try_poll().unwrap(), 0);
+    assert!(start.elapsed() < Duration::from_millis(100));
+    assert!(event_loop.last_error().is_none());
+}
+
+#[test]
+fn configure_socket_exposes_daemon_connection() {
+    super::setup();
+
+    let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let event_loop = browser.browse_services().unwrap();
+    let result = event_loop.configure_socket(|fd| {
+        assert!(fd >= 0);
+        Ok(())
+    });
+
+    #[cfg(target_os = "linux")]
+    assert_eq!(
+        crate::error::ErrorKind::Unsupported,
+        result.unwrap_err().kind()
+    );
+    #[cfg(target_vendor = "apple")]
+    result.unwrap();
+}
@@ -0,0 +1,48 @@
+use crate::prelude::*;
+use crate::{list_interfaces, ServiceDiscovery, ServiceType};
+
+#[test]
+fn list_interfaces_flags_loopback() {
+    super::setup();
+    let interfaces = list_interfaces().unwrap();
+    let lo = interfaces.iter().find(|i| i.is_loopback()).unwrap();
+    assert!(lo.name().starts_with("lo"));
+    assert!(lo.is_up());
+    assert!(interfaces.iter().filter(|i| i.is_loopback()).count() == 1);
+}
+
+#[test]
+fn discovery_interface_name_matches_list_interfaces() {
+    super::setup();
+    let lo = list_interfaces()
+        .unwrap()
+        .into_iter()
+        .find(|i| i.is_loopback())
+        .unwrap();
+
+    let discovery = |index| {
+        ServiceDiscovery::builder()
+            .name("discovery_interface_name".to_string())
+            .service_type(ServiceType::new("http", "tcp").unwrap())
+            .domain("local".to_string())
+            .host_name("localhost".to_string())
+            .address("127.0.0.1".to_string())
+            .port(8080)
+            .txt(None)
+            .interface_index(index)
+            .build()
+            .unwrap()
+    };
+
+    assert_eq!(
+        Some(lo.name().clone()),
+        discovery(*lo.index()).interface_name()
+    );
+    // served from the cache
+    assert_eq!(
+        Some(lo.name().clone()),
+        discovery(*lo.index()).interface_name()
+    );
+    assert_eq!(None, discovery(0).interface_name());
+    assert_eq!(None, discovery(u32::MAX).interface_name());
+}
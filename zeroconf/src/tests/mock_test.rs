@@ -0,0 +1,1143 @@
+use crate::error::ErrorKind;
+use crate::mock::{self, MockMdnsBrowser, MockMdnsService};
+use crate::prelude::*;
+use crate::{
+    NetworkInterface, PublishFlags, ServiceDiscovery, ServiceEvent, ServiceInfo, ServiceType,
+    TxtRecord,
+};
+use std::cell::RefCell;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Consumer code that is generic over the backend.
+fn discover_all<B: TMdnsBrowser>(service_type: ServiceType) -> Vec<ServiceDiscovery> {
+    let discovered: Rc<RefCell<Vec<ServiceDiscovery>>> = Rc::default();
+    let mut browser = B::new(service_type);
+
+    browser.set_service_discovered_callback(Box::new({
+        let discovered = discovered.clone();
+        move |service, _| {
+            discovered.borrow_mut().push(service.unwrap());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let event_loop = browser.browse_services().unwrap();
+    event_loop.poll(Duration::from_secs(0)).unwrap();
+
+    let discovered = discovered.borrow().clone();
+    discovered
+}
+
+#[test]
+fn mock_service_is_browsable() {
+    super::setup();
+    mock::reset();
+
+    let mut txt = TxtRecord::new();
+    txt.insert("foo", "bar").unwrap();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_service_is_browsable");
+    service.set_txt_record(txt.clone());
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Break(())));
+
+    let event_loop = service.register().unwrap();
+    let registration = event_loop
+        .wait_for_registration(Duration::from_secs(0))
+        .unwrap();
+    assert_eq!("mock_service_is_browsable", registration.name());
+    assert!(event_loop.is_stopped());
+    assert_eq!(vec![registration.clone()], service.registrations());
+    assert_eq!(vec![registration], mock::registrations());
+
+    let discovered = discover_all::<MockMdnsBrowser>(ServiceType::new("http", "tcp").unwrap());
+    assert_eq!(1, discovered.len());
+    assert_eq!("mock_service_is_browsable", discovered[0].name());
+    assert_eq!(8080, *discovered[0].port());
+    assert_eq!(Some(txt), *discovered[0].txt());
+    assert!(discovered[0].is_local());
+
+    assert!(discover_all::<MockMdnsBrowser>(ServiceType::new("ipp", "tcp").unwrap()).is_empty());
+
+    drop(service);
+    assert!(mock::registrations().is_empty());
+}
+
+#[test]
+fn mock_browser_delivers_injected_events() {
+    super::setup();
+    mock::reset();
+
+    let service_type = ServiceType::new("http", "tcp").unwrap();
+
+    let discovery = ServiceDiscovery::builder()
+        .name("injected".to_string())
+        .service_type(service_type.clone())
+        .domain("local".to_string())
+        .host_name("injected.local".to_string())
+        .address("192.168.1.2".to_string())
+        .port(80)
+        .txt(None)
+        .build()
+        .unwrap();
+
+    let results: Rc<RefCell<Vec<crate::Result<ServiceDiscovery>>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(service_type.clone());
+
+    browser.set_service_discovered_callback(Box::new({
+        let results = results.clone();
+        move |service, _| {
+            results.borrow_mut().push(service);
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let event_loop = browser.browse_services().unwrap();
+    assert_eq!(0, event_loop.try_poll().unwrap());
+
+    mock::inject_discovery(discovery.clone());
+    mock::inject_error(service_type.clone(), "browser failure".into());
+    assert_eq!(2, event_loop.try_poll().unwrap());
+    assert_eq!(0, event_loop.try_poll().unwrap());
+
+    let mut more_coming = discovery.clone();
+    more_coming.set_more_coming(true);
+
+    assert_eq!(
+        vec![Ok(more_coming), Err("browser failure".into())],
+        *results.borrow()
+    );
+
+    assert_eq!(
+        Ok(discovery),
+        MockMdnsBrowser::resolve("injected", service_type, Duration::from_secs(0))
+    );
+}
+
+#[test]
+fn mock_browser_flags_more_coming_until_last() {
+    super::setup();
+    mock::reset();
+
+    let services: Vec<MockMdnsService> = (0..3)
+        .map(|i| {
+            let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+            service.set_name(&format!("mock_browser_flags_more_coming_until_last_{}", i));
+            service.register().unwrap();
+            service
+        })
+        .collect();
+
+    let discovered = discover_all::<MockMdnsBrowser>(ServiceType::new("http", "tcp").unwrap());
+
+    assert_eq!(services.len(), discovered.len());
+    assert_eq!(
+        vec![true, true, false],
+        discovered
+            .iter()
+            .map(|d| d.more_coming())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn mock_browser_pause_and_resume() {
+    super::setup();
+    mock::reset();
+
+    let names: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+    browser.set_service_discovered_callback(Box::new({
+        let names = names.clone();
+        move |service, _| {
+            names.borrow_mut().push(service.unwrap().name().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    assert!(browser.resume().is_err());
+
+    let mut first = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    first.set_name("mock_browser_pause_and_resume_0");
+    first.register().unwrap();
+
+    assert_eq!(1, browser.browse_services().unwrap().try_poll().unwrap());
+
+    browser.pause();
+
+    let mut second = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    second.set_name("mock_browser_pause_and_resume_1");
+    second.register().unwrap();
+
+    assert_eq!(vec!["mock_browser_pause_and_resume_0"], *names.borrow());
+
+    let event_loop = browser.resume().unwrap();
+    assert_eq!(2, event_loop.try_poll().unwrap());
+    drop(event_loop);
+
+    assert_eq!(
+        vec![
+            "mock_browser_pause_and_resume_0",
+            "mock_browser_pause_and_resume_0",
+            "mock_browser_pause_and_resume_1"
+        ],
+        *names.borrow()
+    );
+
+    assert!(browser.resume().is_err());
+}
+
+#[test]
+fn mock_browser_close_and_browse_again() {
+    super::setup();
+    mock::reset();
+
+    let names: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+    browser.set_service_discovered_callback(Box::new({
+        let names = names.clone();
+        move |service, _| {
+            names.borrow_mut().push(service.unwrap().name().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_browser_close_and_browse_again");
+    service.register().unwrap();
+
+    assert_eq!(1, browser.browse_services().unwrap().try_poll().unwrap());
+
+    browser.close().unwrap();
+
+    assert!(browser.poll(Duration::from_secs(0)).is_err());
+    assert!(browser.resume().is_err());
+    assert_eq!(vec!["mock_browser_close_and_browse_again"], *names.borrow());
+
+    assert_eq!(1, browser.browse_services().unwrap().try_poll().unwrap());
+    assert_eq!(
+        vec![
+            "mock_browser_close_and_browse_again",
+            "mock_browser_close_and_browse_again"
+        ],
+        *names.borrow()
+    );
+}
+
+#[test]
+fn mock_resolve_cancelled_before_completion_delivers_nothing() {
+    super::setup();
+    mock::reset();
+
+    let delivered: Rc<RefCell<usize>> = Rc::default();
+
+    let mut handle = MockMdnsBrowser::resolve_start(
+        "mock_resolve_cancelled_before_completion_delivers_nothing",
+        ServiceType::new("http", "tcp").unwrap(),
+        Duration::from_secs(0),
+        Box::new({
+            let delivered = delivered.clone();
+            move |_, _| {
+                *delivered.borrow_mut() += 1;
+                ControlFlow::Continue(())
+            }
+        }),
+    )
+    .unwrap();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_resolve_cancelled_before_completion_delivers_nothing");
+    service.register().unwrap();
+
+    handle.cancel();
+    assert!(handle.is_done());
+
+    handle.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(0, *delivered.borrow());
+}
+
+#[test]
+fn mock_resolve_start_delivers_once() {
+    super::setup();
+    mock::reset();
+
+    let names: Rc<RefCell<Vec<String>>> = Rc::default();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_resolve_start_delivers_once");
+    service.register().unwrap();
+
+    let mut handle = MockMdnsBrowser::resolve_start(
+        "mock_resolve_start_delivers_once",
+        ServiceType::new("http", "tcp").unwrap(),
+        Duration::from_secs(1),
+        Box::new({
+            let names = names.clone();
+            move |service, _| {
+                names.borrow_mut().push(service.unwrap().name().clone());
+                ControlFlow::Continue(())
+            }
+        }),
+    )
+    .unwrap();
+
+    assert!(!handle.is_done());
+
+    handle.poll(Duration::from_secs(0)).unwrap();
+    handle.poll(Duration::from_secs(0)).unwrap();
+
+    assert!(handle.is_done());
+    assert_eq!(vec!["mock_resolve_start_delivers_once"], *names.borrow());
+}
+
+#[test]
+fn mock_browser_browses_each_domain() {
+    super::setup();
+    mock::reset();
+
+    let services: Vec<MockMdnsService> = ["local", "corp.example.com", "other.example.com"]
+        .iter()
+        .map(|domain| {
+            let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+            service.set_name(&format!("mock_browser_browses_each_domain_{}", domain));
+            service.set_domain(domain);
+            service.register().unwrap();
+            service
+        })
+        .collect();
+
+    let discovered: Rc<RefCell<Vec<ServiceDiscovery>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+    browser.set_domains(vec!["local.".to_string(), "corp.example.com.".to_string()]);
+    browser.set_service_discovered_callback(Box::new({
+        let discovered = discovered.clone();
+        move |service, _| {
+            discovered.borrow_mut().push(service.unwrap());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    assert_eq!(2, browser.browse_services().unwrap().try_poll().unwrap());
+
+    let discovered = discovered.borrow();
+    assert_eq!(services.len() - 1, discovered.len());
+    assert_eq!(
+        "mock_browser_browses_each_domain_local",
+        discovered[0].name()
+    );
+    assert_eq!("local", discovered[0].domain());
+    assert_eq!(
+        "mock_browser_browses_each_domain_corp.example.com",
+        discovered[1].name()
+    );
+    assert_eq!("corp.example.com", discovered[1].domain());
+}
+
+#[test]
+fn mock_force_multicast_keeps_domains_in_either_order() {
+    let domains = vec!["local.".to_string(), "corp.example.com.".to_string()];
+
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_domains(domains.clone());
+    browser.set_force_multicast(true);
+    assert_eq!(domains, browser.domains());
+    assert!(browser.force_multicast());
+
+    browser.set_force_multicast(false);
+    assert_eq!(domains, browser.domains());
+
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_force_multicast(true);
+    browser.set_domains(domains.clone());
+    assert_eq!(domains, browser.domains());
+    assert!(browser.force_multicast());
+    assert!(browser.browse_services().is_ok());
+}
+
+#[test]
+fn mock_force_multicast_with_wide_area_is_invalid_in_either_order() {
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_wide_area("example.com");
+    browser.set_force_multicast(true);
+
+    let error = browser.browse_services().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_force_multicast(true);
+    browser.set_wide_area("example.com");
+
+    let error = browser.browse_services().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+
+    browser.set_force_multicast(false);
+    assert!(browser.browse_services().is_ok());
+}
+
+#[test]
+fn mock_browse_domains_feeds_set_domains() {
+    super::setup();
+    mock::reset();
+
+    let _services: Vec<MockMdnsService> = ["local", "corp.example.com", "local"]
+        .iter()
+        .enumerate()
+        .map(|(i, domain)| {
+            let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+            service.set_name(&format!("mock_browse_domains_feeds_set_domains_{}", i));
+            service.set_domain(domain);
+            service.register().unwrap();
+            service
+        })
+        .collect();
+
+    let domains = MockMdnsBrowser::browse_domains(Duration::from_secs(0)).unwrap();
+    assert_eq!(vec!["local", "corp.example.com"], domains);
+
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_domains(domains.clone());
+    assert_eq!(domains, browser.domains());
+}
+
+#[test]
+fn mock_browser_start_and_poll_without_event_loop() {
+    super::setup();
+    mock::reset();
+
+    struct Discovery {
+        browser: MockMdnsBrowser,
+        names: Rc<RefCell<Vec<String>>>,
+    }
+
+    let mut discovery = Discovery {
+        browser: MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap()),
+        names: Rc::default(),
+    };
+
+    discovery.browser.set_service_discovered_callback(Box::new({
+        let names = discovery.names.clone();
+        move |service, _| {
+            names.borrow_mut().push(service.unwrap().name().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    assert!(discovery.browser.poll(Duration::from_secs(0)).is_err());
+
+    discovery.browser.start().unwrap();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_browser_start_and_poll_without_event_loop");
+    service.register().unwrap();
+
+    discovery.browser.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(
+        vec!["mock_browser_start_and_poll_without_event_loop"],
+        *discovery.names.borrow()
+    );
+
+    discovery.browser.pause();
+    assert!(discovery.browser.poll(Duration::from_secs(0)).is_err());
+}
+
+#[test]
+fn mock_browser_without_address_resolution() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_browser_without_address_resolution");
+    service.set_host("printer.local");
+    service.register().unwrap();
+
+    let discovered: Rc<RefCell<Vec<ServiceDiscovery>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+    browser.set_resolve_address(false);
+    browser.set_service_discovered_callback(Box::new({
+        let discovered = discovered.clone();
+        move |service, _| {
+            discovered.borrow_mut().push(service.unwrap());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    browser.start().unwrap();
+    browser.poll(Duration::from_secs(0)).unwrap();
+
+    let discovered = discovered.borrow();
+    assert_eq!(1, discovered.len());
+    assert_eq!("printer.local", discovered[0].host_name());
+    assert_eq!(8080, *discovered[0].port());
+    assert!(discovered[0].address().is_empty());
+    assert!(discovered[0].endpoints().is_empty());
+}
+
+#[test]
+fn mock_service_with_multiple_txt_records() {
+    super::setup();
+    mock::reset();
+
+    let records: Vec<TxtRecord> = (0..3)
+        .map(|i| {
+            let mut txt = TxtRecord::new();
+            txt.insert("part", &i.to_string()).unwrap();
+            txt
+        })
+        .collect();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("mock_service_with_multiple_txt_records");
+    service.set_txt_records(records.clone());
+    service.register().unwrap();
+
+    assert_eq!(records, service.txt_records());
+
+    let discovered = discover_all::<MockMdnsBrowser>(ServiceType::new("http", "tcp").unwrap());
+    assert_eq!(1, discovered.len());
+    assert_eq!(Some(records[0].clone()), *discovered[0].txt());
+}
+
+#[test]
+fn mock_service_publish_flags() {
+    super::setup();
+    mock::reset();
+
+    let flags = PublishFlags::NO_PROBE | PublishFlags::NO_ANNOUNCE;
+    assert!(flags.contains(PublishFlags::NO_PROBE));
+    assert!(!flags.contains(PublishFlags::UNIQUE));
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    assert_eq!(PublishFlags::empty(), service.publish_flags());
+
+    service.set_publish_flags(flags).unwrap();
+    assert_eq!(flags, service.publish_flags());
+}
+
+#[test]
+fn mock_service_additional_record_ttl() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    assert_eq!(0, service.additional_record_ttl());
+
+    service.set_additional_record_ttl(120).unwrap();
+    assert_eq!(120, service.additional_record_ttl());
+}
+
+#[test]
+fn mock_service_register_twice_requires_unregister() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    drop(service.register().unwrap());
+
+    let error = service.register().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::AlreadyRegistered, error.kind());
+
+    service.unregister().unwrap();
+    assert!(mock::registrations().is_empty());
+
+    assert!(service.register().is_ok());
+    assert_eq!(1, mock::registrations().len());
+}
+
+#[test]
+fn mock_service_reannounce_is_discovered_again() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("reannounced");
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    assert!(service.reannounce().is_err());
+
+    let registration = service
+        .register()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(0))
+        .unwrap();
+
+    let discovered: Rc<RefCell<Vec<ServiceDiscovery>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_service_discovered_callback(Box::new({
+        let discovered = discovered.clone();
+        move |service, _| {
+            discovered.borrow_mut().push(service.unwrap());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let browser_loop = browser.browse_services().unwrap();
+    browser_loop.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(1, discovered.borrow().len());
+
+    let reannounced = service
+        .reannounce()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(0))
+        .unwrap();
+
+    assert_eq!(registration, reannounced);
+
+    browser_loop.poll(Duration::from_secs(0)).unwrap();
+
+    let discovered = discovered.borrow();
+    assert_eq!(2, discovered.len());
+    assert_eq!("reannounced", discovered[1].name());
+    assert_eq!(8080, *discovered[1].port());
+}
+
+#[test]
+fn mock_browser_stops_after_max_duration() {
+    super::setup();
+    mock::reset();
+
+    let results: Rc<RefCell<Vec<crate::Result<ServiceDiscovery>>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_max_duration(Duration::from_millis(50));
+    browser.set_service_discovered_callback(Box::new({
+        let results = results.clone();
+        move |service, _| {
+            results.borrow_mut().push(service);
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let event_loop = browser.browse_services().unwrap();
+
+    let mut before = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    before.set_name("before");
+    before.register().unwrap();
+
+    event_loop.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(1, results.borrow().len());
+    assert_eq!("before", results.borrow()[0].as_ref().unwrap().name());
+
+    std::thread::sleep(Duration::from_millis(60));
+
+    let mut after = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8081);
+    after.set_name("after");
+    after.register().unwrap();
+
+    event_loop.poll(Duration::from_secs(0)).unwrap();
+    event_loop.poll(Duration::from_secs(0)).unwrap();
+
+    let results = results.borrow();
+    assert_eq!(2, results.len());
+    assert_eq!(ErrorKind::TimedOut, results[1].as_ref().unwrap_err().kind());
+    assert!(!event_loop.is_stopped());
+}
+
+#[test]
+fn mock_service_type_with_domain() {
+    super::setup();
+    mock::reset();
+
+    let service_type = ServiceType::new_with_domain("http", "tcp", "example.com").unwrap();
+
+    let mut service = MockMdnsService::new(service_type.clone(), 8080);
+    service.set_name("mock_service_type_with_domain");
+    service.register().unwrap();
+
+    assert_eq!("example.com", mock::registrations()[0].domain());
+
+    let browser = MockMdnsBrowser::new(service_type);
+    assert_eq!(vec!["example.com"], browser.domains());
+
+    assert_eq!(
+        1,
+        discover_all::<MockMdnsBrowser>(
+            ServiceType::new_with_domain("http", "tcp", "example.com").unwrap()
+        )
+        .len()
+    );
+    assert!(discover_all::<MockMdnsBrowser>(
+        ServiceType::new_with_domain("http", "tcp", "example.org").unwrap()
+    )
+    .is_empty());
+}
+
+#[test]
+fn mock_service_from_info() {
+    super::setup();
+    mock::reset();
+
+    let info: ServiceInfo = serde_json::from_str(
+        r#"{
+            "name": "mock_service_from_info",
+            "service_type": { "name": "http", "protocol": "tcp", "sub_types": [] },
+            "domain": "example.com",
+            "port": 8080,
+            "txt": { "path": "/" },
+            "interface": { "AtIndex": 1 }
+        }"#,
+    )
+    .unwrap();
+
+    let mut service = MockMdnsService::from_info(info.clone()).unwrap();
+    assert_eq!(NetworkInterface::AtIndex(1), service.network_interface());
+    assert_eq!(
+        info.txt().clone().into_iter().collect::<Vec<_>>(),
+        service.txt_records()
+    );
+
+    service.register().unwrap();
+
+    let registration = mock::registrations().remove(0);
+    assert_eq!("mock_service_from_info", registration.name());
+    assert_eq!("example.com", registration.domain());
+    assert_eq!(8080, *registration.port());
+}
+
+#[test]
+fn mock_service_from_info_with_ttl_is_unsupported() {
+    super::setup();
+
+    let info = ServiceInfo::builder()
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .port(8080)
+        .ttl(Some(120))
+        .build()
+        .unwrap();
+
+    let error = MockMdnsService::from_info(info).unwrap_err();
+    assert_eq!(ErrorKind::Unsupported, error.kind());
+}
+
+#[test]
+fn mock_discovery_is_wide_area_outside_local() {
+    super::setup();
+    mock::reset();
+
+    let mut local = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    local.set_name("local");
+    local.register().unwrap();
+
+    let mut unicast = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    unicast.set_name("unicast");
+    unicast.set_domain("example.com");
+    unicast.register().unwrap();
+
+    let discovered = discover_all::<MockMdnsBrowser>(ServiceType::new("http", "tcp").unwrap());
+    assert_eq!(2, discovered.len());
+    assert!(!discovered[0].is_wide_area());
+    assert!(discovered[1].is_wide_area());
+}
+
+#[test]
+fn mock_browser_ignore_local_skips_own_services() {
+    super::setup();
+    mock::reset();
+
+    let mut own = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    own.set_name("own");
+    own.register().unwrap();
+
+    let peer = ServiceDiscovery::builder()
+        .name("peer".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("peer.local".to_string())
+        .address("192.168.1.2".to_string())
+        .port(8080)
+        .txt(None)
+        .build()
+        .unwrap();
+    mock::inject_discovery(peer);
+
+    let names: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_ignore_local(true);
+    browser.set_service_discovered_callback(Box::new({
+        let names = names.clone();
+        move |service, _| {
+            names.borrow_mut().push(service.unwrap().name().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    browser.browse_services().unwrap().try_poll().unwrap();
+
+    assert_eq!(vec!["peer"], *names.borrow());
+}
+
+#[test]
+fn mock_browser_refreshes_at_interval() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("refreshed");
+    service.register().unwrap();
+
+    let names: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_refresh_interval(Duration::from_millis(50));
+    browser.set_service_discovered_callback(Box::new({
+        let names = names.clone();
+        move |service, _| {
+            names.borrow_mut().push(service.unwrap().name().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let event_loop = browser.browse_services().unwrap();
+    assert_eq!(1, event_loop.try_poll().unwrap());
+    assert_eq!(0, event_loop.try_poll().unwrap());
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(1, event_loop.try_poll().unwrap());
+    assert_eq!(0, event_loop.try_poll().unwrap());
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(1, event_loop.try_poll().unwrap());
+
+    assert_eq!(vec!["refreshed", "refreshed", "refreshed"], *names.borrow());
+}
+
+#[test]
+fn mock_browser_counts_flapping_instance() {
+    super::setup();
+    mock::reset();
+
+    let service_type = ServiceType::new("http", "tcp").unwrap();
+    let mut service = MockMdnsService::new(service_type.clone(), 8080);
+    service.set_name("flapping");
+    service.register().unwrap().try_poll().unwrap();
+
+    let mut browser = MockMdnsBrowser::new(service_type.clone());
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    assert_eq!(1, browser.browse_services().unwrap().try_poll().unwrap());
+    assert!(browser.instance_stats("missing").is_none());
+
+    let first = browser.instance_stats("flapping").unwrap();
+    assert_eq!(1, *first.times_seen());
+    assert_eq!(0, *first.times_removed());
+    assert_eq!(1, *first.times_resolved());
+
+    mock::inject_removal(service_type, "flapping");
+    browser.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(
+        1,
+        *browser.instance_stats("flapping").unwrap().times_removed()
+    );
+    assert!(browser.refresh("flapping").is_err());
+
+    service.reannounce().unwrap();
+    browser.poll(Duration::from_secs(0)).unwrap();
+
+    let stats = browser.instance_stats("flapping").unwrap();
+    assert_eq!(2, *stats.times_seen());
+    assert_eq!(1, *stats.times_removed());
+    assert_eq!(2, *stats.times_resolved());
+    assert!(*stats.last_seen() >= *first.last_seen());
+
+    browser.close().unwrap();
+    assert!(browser.instance_stats("flapping").is_none());
+}
+
+#[test]
+fn mock_browser_reports_malformed_txt_and_continues() {
+    super::setup();
+    mock::reset();
+
+    let discovery = |name: &str| {
+        ServiceDiscovery::builder()
+            .name(name.to_string())
+            .service_type(ServiceType::new("http", "tcp").unwrap())
+            .domain("local".to_string())
+            .host_name("localhost.local".to_string())
+            .address("127.0.0.1".to_string())
+            .port(8080)
+            .txt(None)
+            .build()
+            .unwrap()
+    };
+
+    mock::inject_raw_txt(discovery("before"), b"\x07foo=bar\x04flag");
+    // the second entry claims 9 bytes but only 3 follow
+    mock::inject_raw_txt(discovery("truncated"), b"\x07foo=bar\x09a=b");
+    mock::inject_raw_txt(discovery("binary"), b"\x05k=\xff\xfe\xfd");
+    mock::inject_raw_txt(discovery("empty_key"), b"\x04=bar");
+    mock::inject_raw_txt(discovery("after"), b"");
+
+    let results: Rc<RefCell<Vec<crate::Result<ServiceDiscovery>>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_service_discovered_callback(Box::new({
+        let results = results.clone();
+        move |result, _| {
+            results.borrow_mut().push(result);
+            ControlFlow::Continue(())
+        }
+    }));
+
+    assert_eq!(5, browser.browse_services().unwrap().try_poll().unwrap());
+
+    let results = results.borrow();
+    let before = results[0].as_ref().unwrap();
+    assert_eq!("before", before.name());
+    let txt = before.txt().as_ref().unwrap();
+    assert_eq!(Some("bar".to_string()), txt.get("foo"));
+    assert_eq!(Some(true), txt.get_bool("flag"));
+
+    for result in &results[1..4] {
+        assert_eq!(ErrorKind::Malformed, result.as_ref().unwrap_err().kind());
+    }
+
+    let after = results[4].as_ref().unwrap();
+    assert_eq!("after", after.name());
+    assert!(after.txt().is_none());
+}
+
+#[test]
+fn mock_browser_skips_names_without_prefix() {
+    super::setup();
+    mock::reset();
+
+    let mut services = vec![];
+    for name in &["Printer-1", "Scanner-1", "Printer-2", "printer-3"] {
+        let mut service = MockMdnsService::new(ServiceType::new("ipp", "tcp").unwrap(), 631);
+        service.set_name(name);
+        service.register().unwrap();
+        services.push(service);
+    }
+
+    let names: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("ipp", "tcp").unwrap());
+    browser.set_name_prefix("Printer-");
+    browser.set_refresh_interval(Duration::from_millis(50));
+    browser.set_service_discovered_callback(Box::new({
+        let names = names.clone();
+        move |service, _| {
+            names.borrow_mut().push(service.unwrap().name().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let event_loop = browser.browse_services().unwrap();
+    while event_loop.try_poll().unwrap() > 0 {}
+    assert_eq!(vec!["Printer-1", "Printer-2"], *names.borrow());
+
+    // only the matching services are resolved again when refreshing
+    std::thread::sleep(Duration::from_millis(60));
+    while event_loop.try_poll().unwrap() > 0 {}
+    assert_eq!(
+        vec!["Printer-1", "Printer-2", "Printer-1", "Printer-2"],
+        *names.borrow()
+    );
+}
+
+#[test]
+fn mock_browser_into_iter_yields_discoveries() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("iterated");
+    service.register().unwrap();
+
+    mock::inject_error(
+        ServiceType::new("http", "tcp").unwrap(),
+        "resolve failed".into(),
+    );
+
+    let browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    let mut events = browser.into_iter().with_timeout(Duration::from_secs(0));
+
+    match events.next().unwrap().unwrap() {
+        ServiceEvent::Discovered(service) => assert_eq!("iterated", service.name()),
+        event => panic!("unexpected event: {:?}", event),
+    }
+
+    assert!(events.next().unwrap().is_err());
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn mock_browser_into_iter_yields_removals() {
+    super::setup();
+    mock::reset();
+
+    let service_type = ServiceType::new("http", "tcp").unwrap();
+
+    let mut service = MockMdnsService::new(service_type.clone(), 8080);
+    service.set_name("removed");
+    service.set_domain("example.com");
+    service.register().unwrap();
+
+    mock::inject_removal(service_type.clone(), "removed");
+    // never discovered, so there is nothing to report
+    mock::inject_removal(service_type.clone(), "unknown");
+
+    let browser = MockMdnsBrowser::new(service_type.clone());
+    let events: Vec<ServiceEvent> = browser
+        .into_iter()
+        .with_timeout(Duration::from_secs(0))
+        .map(|e| e.unwrap())
+        .collect();
+
+    assert_eq!(2, events.len());
+    assert!(matches!(&events[0], ServiceEvent::Discovered(s) if s.name() == "removed"));
+    assert_eq!(
+        ServiceEvent::Removed {
+            name: "removed".to_string(),
+            service_type,
+            domain: "example.com".to_string(),
+        },
+        events[1]
+    );
+}
+
+#[test]
+fn mock_browser_reports_removal_once_every_copy_is_gone() {
+    super::setup();
+    mock::reset();
+
+    let service_type = ServiceType::new("http", "tcp").unwrap();
+
+    for (interface_index, domain) in [(1, "local"), (2, "local"), (1, "example.com")] {
+        mock::inject_discovery(
+            ServiceDiscovery::builder()
+                .name("copies".to_string())
+                .service_type(service_type.clone())
+                .domain(domain.to_string())
+                .host_name("copies.local".to_string())
+                .address("192.168.1.2".to_string())
+                .port(8080)
+                .txt(None)
+                .interface_index(interface_index)
+                .build()
+                .unwrap(),
+        );
+    }
+
+    let removed: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(service_type.clone());
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+    browser.set_service_removed_callback(Box::new({
+        let removed = removed.clone();
+        move |removal, _| {
+            removed.borrow_mut().push(removal.domain().clone());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    assert_eq!(3, browser.browse_services().unwrap().try_poll().unwrap());
+    assert_eq!(1, *browser.instance_stats("copies").unwrap().times_seen());
+
+    // still reachable on the other interface
+    mock::inject_removal_on(service_type.clone(), "copies", 1, "local.");
+    browser.poll(Duration::from_secs(0)).unwrap();
+    assert!(removed.borrow().is_empty());
+    assert!(browser.refresh("copies").is_ok());
+
+    mock::inject_removal_on(service_type.clone(), "copies", 2, "local");
+    browser.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(vec!["local"], *removed.borrow());
+    assert_eq!(
+        0,
+        *browser.instance_stats("copies").unwrap().times_removed()
+    );
+
+    mock::inject_removal_on(service_type, "copies", 1, "example.com");
+    browser.poll(Duration::from_secs(0)).unwrap();
+    assert_eq!(vec!["local", "example.com"], *removed.borrow());
+    assert_eq!(
+        1,
+        *browser.instance_stats("copies").unwrap().times_removed()
+    );
+    assert!(browser.refresh("copies").is_err());
+}
+
+#[test]
+fn mock_service_current_name() {
+    super::setup();
+    mock::reset();
+
+    let mut service = MockMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("current");
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+    assert_eq!(None, service.current_name());
+
+    let event_loop = service.register().unwrap();
+    event_loop
+        .wait_for_registration(Duration::from_secs(0))
+        .unwrap();
+    drop(event_loop);
+
+    assert_eq!(Some("current".to_string()), service.current_name());
+
+    service.unregister().unwrap();
+    assert_eq!(None, service.current_name());
+}
+
+#[test]
+fn mock_browser_bounds_concurrent_resolves() {
+    super::setup();
+    mock::reset();
+
+    for i in 0..100 {
+        mock::inject_discovery(
+            ServiceDiscovery::builder()
+                .name(format!("service-{}", i))
+                .service_type(ServiceType::new("http", "tcp").unwrap())
+                .domain("local".to_string())
+                .host_name("localhost.local".to_string())
+                .address("127.0.0.1".to_string())
+                .port(8080)
+                .txt(None)
+                .build()
+                .unwrap(),
+        );
+    }
+
+    let discovered: Rc<RefCell<Vec<ServiceDiscovery>>> = Rc::default();
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_max_concurrent_resolves(8);
+    browser.set_service_discovered_callback(Box::new({
+        let discovered = discovered.clone();
+        move |service, _| {
+            discovered.borrow_mut().push(service.unwrap());
+            ControlFlow::Continue(())
+        }
+    }));
+
+    let event_loop = browser.browse_services().unwrap();
+
+    loop {
+        let handled = event_loop.try_poll().unwrap();
+        assert!(handled <= 8);
+
+        if handled == 0 {
+            break;
+        }
+    }
+
+    let discovered = discovered.borrow();
+    assert_eq!(100, discovered.len());
+    assert!(discovered[..99].iter().all(|d| d.more_coming()));
+    assert!(!discovered[99].more_coming());
+}
+
+#[test]
+#[cfg(unix)]
+fn mock_event_loop_configure_socket_is_unsupported() {
+    super::setup();
+    mock::reset();
+
+    let mut browser = MockMdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    let event_loop = browser.browse_services().unwrap();
+
+    let error = event_loop.configure_socket(|_| Ok(())).unwrap_err();
+    assert_eq!(ErrorKind::Unsupported, error.kind());
+}
@@ -1,3 +1,5 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
 use std::sync::Once;
 
 static INIT: Once = Once::new();
@@ -6,5 +8,43 @@ pub(crate) fn setup() {
     INIT.call_once(env_logger::init);
 }
 
+/// Counts the allocations made by each thread, so tests can compare how much an operation
+/// allocates with `count_allocations()`.
+///
+/// This is the global allocator of the whole test binary, not only of the tests that use it, but
+/// it only adds a thread-local counter to the system allocator.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations the current thread made while running `f`.
+pub(crate) fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(|a| a.get());
+    f();
+    ALLOCATIONS.with(|a| a.get()) - before
+}
+
+mod browser_test;
+mod daemon_test;
+mod event_loop_test;
+mod interface_test;
+#[cfg(feature = "mock")]
+mod mock_test;
 mod service_test;
 mod txt_record_test;
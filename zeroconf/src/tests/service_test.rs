@@ -1,7 +1,10 @@
+use crate::error::ErrorKind;
 use crate::prelude::*;
-use crate::{MdnsBrowser, MdnsService, ServiceType, TxtRecord};
+use crate::service::{self, RegistrationClaim};
+use crate::{MdnsBrowser, MdnsService, ServiceEvent, ServiceType, TxtRecord};
+use std::ops::ControlFlow;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[test]
 fn service_register_is_browsable() {
@@ -52,28 +55,633 @@ fn service_register_is_browsable() {
                 mtx.is_discovered = true;
 
                 debug!("Service discovered");
+
+                return ControlFlow::Break(());
             }
+
+            ControlFlow::Continue(())
         }));
 
         let event_loop = browser.browse_services().unwrap();
 
-        loop {
+        while !event_loop.is_stopped() {
             event_loop.poll(Duration::from_secs(0)).unwrap();
-            if context.lock().unwrap().is_discovered {
-                break;
+        }
+
+        ControlFlow::Break(())
+    }));
+
+    let event_loop = service.register().unwrap();
+
+    while !event_loop.is_stopped() {
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+    }
+
+    let mut mtx = context.lock().unwrap();
+    assert!(mtx.is_discovered);
+    assert_eq!(txt, mtx.txt.take().unwrap());
+}
+
+#[test]
+fn service_register_with_empty_txt_record_is_browsed_without_txt() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_register_with_empty_txt_record_is_browsed_without_txt";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    let txt: Arc<Mutex<Option<Option<TxtRecord>>>> = Arc::default();
+
+    service.set_name(SERVICE_NAME);
+    service.set_txt_record(TxtRecord::new());
+    service.set_context(Box::new(txt.clone()));
+
+    service.set_registered_callback(Box::new(|_, context| {
+        let txt = context
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<Arc<Mutex<Option<Option<TxtRecord>>>>>()
+            .unwrap()
+            .clone();
+
+        let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+        browser.set_service_discovered_callback(Box::new(move |service, _| {
+            let service = service.unwrap();
+
+            if service.name() == SERVICE_NAME {
+                *txt.lock().unwrap() = Some(service.txt().clone());
+                return ControlFlow::Break(());
             }
+
+            ControlFlow::Continue(())
+        }));
+
+        let event_loop = browser.browse_services().unwrap();
+
+        while !event_loop.is_stopped() {
+            event_loop.poll(Duration::from_secs(0)).unwrap();
         }
+
+        ControlFlow::Break(())
     }));
 
     let event_loop = service.register().unwrap();
 
-    loop {
+    while !event_loop.is_stopped() {
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+    }
+
+    // advertised as a single zero-length string, which browsers report as no TXT record
+    assert_eq!(Some(None), *txt.lock().unwrap());
+}
+
+#[test]
+fn service_registrations_reports_registered_service() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_registrations_reports_registered_service";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name(SERVICE_NAME);
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    assert!(service.registrations().is_empty());
+
+    service
+        .register()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    let registrations = service.registrations();
+    assert_eq!(1, registrations.len());
+    assert_eq!(SERVICE_NAME, registrations[0].name());
+    assert_eq!(8080, *registrations[0].port());
+    assert_eq!("local", registrations[0].domain());
+}
+
+#[test]
+fn service_registrations_reports_explicit_domain() {
+    super::setup();
+
+    let service_type = ServiceType::new_with_domain("http", "tcp", "local.").unwrap();
+    let mut service = MdnsService::new(service_type, 8080);
+    service.set_name("service_registrations_reports_explicit_domain");
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    service
+        .register()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    assert_eq!("local", service.registrations()[0].domain());
+}
+
+#[test]
+fn service_discovery_of_own_service_is_local() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_discovery_of_own_service_is_local";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    let is_local: Arc<Mutex<Option<bool>>> = Arc::default();
+
+    service.set_name(SERVICE_NAME);
+    service.set_context(Box::new(is_local.clone()));
+
+    service.set_registered_callback(Box::new(|_, context| {
+        let is_local = context
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<Arc<Mutex<Option<bool>>>>()
+            .unwrap()
+            .clone();
+
+        let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+        browser.set_service_discovered_callback(Box::new(move |service, _| {
+            let service = service.unwrap();
+
+            if service.name() == SERVICE_NAME {
+                *is_local.lock().unwrap() = Some(service.is_local());
+                return ControlFlow::Break(());
+            }
+
+            ControlFlow::Continue(())
+        }));
+
+        let event_loop = browser.browse_services().unwrap();
+
+        while !event_loop.is_stopped() {
+            event_loop.poll(Duration::from_secs(0)).unwrap();
+        }
+
+        ControlFlow::Break(())
+    }));
+
+    let event_loop = service.register().unwrap();
+
+    while !event_loop.is_stopped() {
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+    }
+
+    assert_eq!(Some(true), *is_local.lock().unwrap());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn service_register_with_host_fqdn_is_browsable() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_register_with_host_fqdn_is_browsable";
+    static HOST_FQDN: &str = "zeroconf-host-fqdn.local";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    let host_name: Arc<Mutex<Option<String>>> = Arc::default();
+
+    service.set_name(SERVICE_NAME);
+    service.set_host_fqdn(HOST_FQDN);
+    service.set_context(Box::new(host_name.clone()));
+
+    service.set_registered_callback(Box::new(|_, context| {
+        let host_name = context
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<Arc<Mutex<Option<String>>>>()
+            .unwrap()
+            .clone();
+
+        let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+
+        browser.set_service_discovered_callback(Box::new(move |service, _| {
+            let service = service.unwrap();
+
+            if service.name() == SERVICE_NAME {
+                *host_name.lock().unwrap() = Some(service.host_name().clone());
+                return ControlFlow::Break(());
+            }
+
+            ControlFlow::Continue(())
+        }));
+
+        let event_loop = browser.browse_services().unwrap();
+
+        while !event_loop.is_stopped() {
+            event_loop.poll(Duration::from_secs(0)).unwrap();
+        }
+
+        ControlFlow::Break(())
+    }));
+
+    let event_loop = service.register().unwrap();
+
+    while !event_loop.is_stopped() {
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+    }
+
+    assert_eq!(HOST_FQDN, host_name.lock().unwrap().as_ref().unwrap());
+}
+
+#[test]
+fn wait_for_registration_leaves_loop_usable() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "wait_for_registration_leaves_loop_usable";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name(SERVICE_NAME);
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let event_loop = service.register().unwrap();
+
+    let registration = event_loop
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    assert_eq!(SERVICE_NAME, registration.name());
+    assert!(!event_loop.is_stopped());
+    event_loop.poll(Duration::from_secs(0)).unwrap();
+
+    assert_eq!(
+        registration,
+        event_loop
+            .wait_for_registration(Duration::from_secs(0))
+            .unwrap()
+    );
+}
+
+#[test]
+fn browser_ignore_local_skips_own_service() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "browser_ignore_local_skips_own_service";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8090);
+    service.set_name(SERVICE_NAME);
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let event_loop = service.register().unwrap();
+    event_loop
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    let browse = |ignore_local: bool| {
+        let names: Arc<Mutex<Vec<String>>> = Arc::default();
+        let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+        browser.set_ignore_local(ignore_local);
+        browser.set_service_discovered_callback(Box::new({
+            let names = names.clone();
+            move |service, _| {
+                names.lock().unwrap().push(service.unwrap().name().clone());
+                ControlFlow::Continue(())
+            }
+        }));
+        (browser, names)
+    };
+
+    let (mut all, all_names) = browse(false);
+    let (mut filtered, filtered_names) = browse(true);
+    all.start().unwrap();
+    filtered.start().unwrap();
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while !all_names.lock().unwrap().iter().any(|n| n == SERVICE_NAME) {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "service not discovered"
+        );
+        all.poll(Duration::from_millis(100)).unwrap();
+        filtered.poll(Duration::from_millis(100)).unwrap();
+    }
+
+    filtered.poll(Duration::from_secs(1)).unwrap();
+
+    assert!(!filtered_names
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|n| n == SERVICE_NAME));
+}
+
+#[test]
+fn registration_claim_is_found_by_type_and_port() {
+    super::setup();
+
+    let claim =
+        RegistrationClaim::new("_claimed._tcp,_printer", Some("claimed"), None, 8091).unwrap();
+    let service_type = ServiceType::new("claimed", "tcp").unwrap();
+
+    assert!(service::is_claimed(&service_type, 8091));
+    assert!(!service::is_claimed(&service_type, 8092));
+    assert!(!service::is_claimed(
+        &ServiceType::new("claimed", "udp").unwrap(),
+        8091
+    ));
+
+    drop(claim);
+    assert!(!service::is_claimed(&service_type, 8091));
+}
+
+#[test]
+fn service_register_same_identity_twice_conflicts() {
+    super::setup();
+
+    let service_type = ServiceType::new("http", "tcp").unwrap();
+
+    let mut first = MdnsService::new(service_type.clone(), 8080);
+    first.set_name("service_register_same_identity_twice_conflicts");
+    drop(first.register().unwrap());
+
+    let mut second = MdnsService::new(service_type, 8080);
+    second.set_name("service_register_same_identity_twice_conflicts");
+
+    let error = second.register().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::NameConflict, error.kind());
+
+    drop(first);
+    assert!(second.register().is_ok());
+}
+
+#[test]
+fn service_register_same_name_is_renamed() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_register_same_name_is_renamed";
+
+    let service_type = ServiceType::new("http", "tcp").unwrap();
+
+    let mut first = MdnsService::new(service_type.clone(), 8093);
+    first.set_name(SERVICE_NAME);
+    first.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let first_loop = first.register().unwrap();
+    first_loop
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    let mut second = MdnsService::new(service_type, 8094);
+    second.set_name(SERVICE_NAME);
+    second.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let second_loop = second.register().unwrap();
+    let registration = second_loop
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    assert_ne!(SERVICE_NAME, registration.name());
+    assert!(registration.name().starts_with(SERVICE_NAME));
+    assert_eq!(Some(registration.name().clone()), second.current_name());
+    assert_eq!(Some(SERVICE_NAME.to_string()), first.current_name());
+}
+
+#[test]
+fn registration_claim_released_on_drop() {
+    super::setup();
+
+    let name = Some("registration_claim_released_on_drop");
+    let claim = RegistrationClaim::new("_http._tcp", name, None, 8080).unwrap();
+
+    assert_eq!(
+        ErrorKind::NameConflict,
+        RegistrationClaim::new("_http._tcp", name, None, 8080)
+            .unwrap_err()
+            .kind()
+    );
+    assert!(RegistrationClaim::new("_http._tcp", name, None, 8081).is_ok());
+    assert!(RegistrationClaim::new("_http._tcp", name, Some("example.com"), 8080).is_ok());
+
+    drop(claim);
+    assert!(RegistrationClaim::new("_http._tcp", name, None, 8080).is_ok());
+}
+
+#[test]
+fn registration_claim_follows_rename() {
+    super::setup();
+
+    let name = Some("registration_claim_follows_rename");
+    let renamed = Some("registration_claim_follows_rename #2");
+    let mut claim = RegistrationClaim::new("_http._tcp", name, None, 8080).unwrap();
+    claim.rename(renamed.unwrap());
+
+    assert!(RegistrationClaim::new("_http._tcp", name, None, 8080).is_ok());
+    assert_eq!(
+        ErrorKind::NameConflict,
+        RegistrationClaim::new("_http._tcp", renamed, None, 8080)
+            .unwrap_err()
+            .kind()
+    );
+
+    drop(claim);
+    assert!(RegistrationClaim::new("_http._tcp", renamed, None, 8080).is_ok());
+}
+
+#[test]
+fn service_register_twice_requires_unregister() {
+    super::setup();
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name("service_register_twice_requires_unregister");
+    drop(service.register().unwrap());
+
+    let error = service.register().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::AlreadyRegistered, error.kind());
+
+    service.unregister().unwrap();
+    assert!(service.registrations().is_empty());
+    assert!(service.register().is_ok());
+}
+
+#[test]
+fn service_register_with_unqualified_host_is_invalid() {
+    super::setup();
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_host("printer");
+
+    let error = service.register().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+}
+
+#[test]
+fn service_register_with_host_missing_domain_label_is_invalid() {
+    super::setup();
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_host("printer.");
+
+    let error = service.register().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+}
+
+#[test]
+fn service_register_with_empty_domain_is_invalid() {
+    super::setup();
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_domain("");
+
+    let error = service.register().map(|_| ()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidConfiguration, error.kind());
+}
+
+#[test]
+fn validate_settings_accepts_qualified_host() {
+    assert!(service::validate_settings(None, None).is_ok());
+    assert!(service::validate_settings(Some("printer.local"), None).is_ok());
+    assert!(service::validate_settings(Some("printer.example.com."), Some("example.com")).is_ok());
+}
+
+#[test]
+fn service_reannounce_is_announced_again_to_running_browser() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_reannounce_is_announced_again_to_running_browser";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name(SERVICE_NAME);
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let registration = service
+        .register()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+    browser.start().unwrap();
+
+    let times_seen = |browser: &MdnsBrowser| {
+        browser
+            .instance_stats(SERVICE_NAME)
+            .map_or(0, |s| *s.times_seen())
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while times_seen(&browser) == 0 && Instant::now() < deadline {
+        browser.poll(Duration::from_millis(100)).unwrap();
+    }
+
+    assert_eq!(1, times_seen(&browser));
+
+    let event_loop = service.reannounce().unwrap();
+
+    let reannounced = event_loop
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    assert_eq!(registration, reannounced);
+
+    // the browser only sees the instance again if a new announcement went out
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while times_seen(&browser) < 2 && Instant::now() < deadline {
         event_loop.poll(Duration::from_secs(0)).unwrap();
+        browser.poll(Duration::from_millis(100)).unwrap();
+    }
+
+    assert!(times_seen(&browser) >= 2);
+}
+
+#[test]
+fn service_iter_reports_unregistered_service_as_removed() {
+    super::setup();
 
-        let mut mtx = context.lock().unwrap();
-        if mtx.is_discovered {
-            assert_eq!(txt, mtx.txt.take().unwrap());
-            break;
+    static SERVICE_NAME: &str = "service_iter_reports_unregistered_service_as_removed";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name(SERVICE_NAME);
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    service
+        .register()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    let browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    let mut events = browser.into_iter().with_timeout(Duration::from_secs(10));
+
+    let discovered = events
+        .by_ref()
+        .map(|e| e.unwrap())
+        .find(|e| matches!(e, ServiceEvent::Discovered(s) if s.name() == SERVICE_NAME));
+    assert!(discovered.is_some());
+
+    service.unregister().unwrap();
+
+    let removed = events
+        .map(|e| e.unwrap())
+        .find(|e| matches!(e, ServiceEvent::Removed { name, .. } if name == SERVICE_NAME));
+    assert!(removed.is_some());
+}
+
+#[test]
+fn service_additional_record_ttl_platform_support() {
+    super::setup();
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    let result = service.set_additional_record_ttl(120);
+
+    if cfg!(target_os = "linux") {
+        assert_eq!(ErrorKind::Unsupported, result.unwrap_err().kind());
+    } else {
+        result.unwrap();
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn service_no_fail_registers_with_running_daemon() {
+    super::setup();
+
+    static SERVICE_NAME: &str = "service_no_fail_registers_with_running_daemon";
+
+    let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+    service.set_name(SERVICE_NAME);
+    service.set_no_fail(true);
+    service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+    let registration = service
+        .register()
+        .unwrap()
+        .wait_for_registration(Duration::from_secs(10))
+        .unwrap();
+
+    assert_eq!(SERVICE_NAME, registration.name());
+}
+
+#[test]
+fn service_register_and_drop_repeatedly_while_browsing() {
+    super::setup();
+
+    let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+    browser.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+    let browser_loop = browser.browse_services().unwrap();
+
+    for i in 0..50 {
+        let mut service = MdnsService::new(ServiceType::new("http", "tcp").unwrap(), 8080);
+        service.set_name(&format!("service_register_and_drop_repeatedly_{}", i));
+        service.set_registered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+
+        let event_loop = service.register().unwrap();
+
+        // drop some services before they are established and others while events are flowing
+        for _ in 0..(i % 3) {
+            event_loop.poll(Duration::from_millis(10)).unwrap();
+            browser_loop.poll(Duration::from_millis(10)).unwrap();
         }
+
+        // browsers that are dropped while resolving the service
+        let mut late = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+        late.set_service_discovered_callback(Box::new(|_, _| ControlFlow::Continue(())));
+        late.browse_services()
+            .unwrap()
+            .poll(Duration::from_millis(10))
+            .unwrap();
     }
+
+    browser_loop.poll(Duration::from_millis(10)).unwrap();
 }
@@ -1,5 +1,7 @@
+use super::count_allocations;
+use crate::error::ErrorKind;
 use crate::prelude::*;
-use crate::TxtRecord;
+use crate::{txt_record, TxtRecord};
 use std::collections::HashMap;
 
 #[test]
@@ -11,6 +13,139 @@ fn insert_get_success() {
     assert_eq!(record.get("baz"), None);
 }
 
+#[test]
+fn insert_empty_key_fails() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    let err = record.insert("", "bar").unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+    assert!(record.is_empty());
+}
+
+#[test]
+fn insert_key_with_equals_fails() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    let err = record.insert("foo=bar", "baz").unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+    assert!(record.is_empty());
+}
+
+#[test]
+fn insert_non_ascii_key_fails() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    let err = record.insert("caf\u{e9}", "bar").unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+    assert!(record.is_empty());
+}
+
+#[test]
+fn insert_key_over_recommended_length_succeeds() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("123456789", "bar").unwrap();
+    record.insert("description", "baz").unwrap();
+    assert_eq!(record.get("description").unwrap(), "baz");
+    assert_eq!(2, record.len());
+}
+
+#[test]
+fn insert_oversized_entry_fails() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("foo", &"a".repeat(251)).unwrap();
+
+    let err = record.insert("bar", &"a".repeat(252)).unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+    assert!(!record.contains_key("bar"));
+}
+
+#[test]
+fn get_or_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("foo", "bar").unwrap();
+    assert_eq!(record.get_or("foo", "baz"), "bar");
+    assert_eq!(record.get_or("qux", "baz"), "baz");
+}
+
+#[test]
+fn get_or_default_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("foo", "bar").unwrap();
+    assert_eq!(record.get_or_default("foo"), "bar");
+    assert_eq!(record.get_or_default("qux"), "");
+}
+
+#[test]
+fn get_bool_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("flag", "").unwrap();
+    record.insert("one", "1").unwrap();
+    record.insert("secure", "TRUE").unwrap();
+    record.insert("zero", "0").unwrap();
+    record.insert("insecure", "false").unwrap();
+    assert_eq!(record.get_bool("flag"), Some(true));
+    assert_eq!(record.get_bool("one"), Some(true));
+    assert_eq!(record.get_bool("secure"), Some(true));
+    assert_eq!(record.get_bool("zero"), Some(false));
+    assert_eq!(record.get_bool("insecure"), Some(false));
+    assert_eq!(record.get_bool("missing"), None);
+}
+
+#[test]
+fn get_bool_malformed() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("flag", "maybe").unwrap();
+    assert_eq!(record.get_bool("flag"), None);
+}
+
+#[test]
+fn get_i64_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("port", "8080").unwrap();
+    record.insert("offset", "-3").unwrap();
+    assert_eq!(record.get_i64("port"), Some(8080));
+    assert_eq!(record.get_i64("offset"), Some(-3));
+    assert_eq!(record.get_i64("missing"), None);
+}
+
+#[test]
+fn get_i64_malformed() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("port", "80a").unwrap();
+    record.insert("ratio", "1.5").unwrap();
+    record.insert("empty", "").unwrap();
+    assert_eq!(record.get_i64("port"), None);
+    assert_eq!(record.get_i64("ratio"), None);
+    assert_eq!(record.get_i64("empty"), None);
+}
+
+#[test]
+fn get_f64_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("ratio", "1.5").unwrap();
+    record.insert("count", "3").unwrap();
+    assert_eq!(record.get_f64("ratio"), Some(1.5));
+    assert_eq!(record.get_f64("count"), Some(3.0));
+    assert_eq!(record.get_f64("missing"), None);
+}
+
+#[test]
+fn get_f64_malformed() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("ratio", "one and a half").unwrap();
+    assert_eq!(record.get_f64("ratio"), None);
+}
+
 #[test]
 fn remove_success() {
     super::setup();
@@ -37,6 +172,131 @@ fn len_success() {
     assert_eq!(record.len(), 1);
 }
 
+#[test]
+fn byte_len_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    assert_eq!(record.byte_len(), 0);
+    assert!(record.to_bytes().is_empty());
+
+    record.insert("foo", "bar").unwrap();
+    record
+        .insert("path", "/a/value/longer/than/sixteen/bytes")
+        .unwrap();
+
+    let bytes = record.to_bytes();
+    assert_eq!(record.byte_len(), bytes.len());
+    assert_eq!(record.byte_len(), 1 + 7 + 1 + 39);
+    assert!(bytes.windows(8).any(|w| w == b"\x07foo=bar"));
+}
+
+#[test]
+fn byte_len_flag_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("flag", "").unwrap();
+    record.insert("foo", "bar").unwrap();
+
+    let bytes = record.to_bytes();
+    assert_eq!(record.byte_len(), bytes.len());
+    assert_eq!(txt_record::split_wire_format(&bytes).unwrap().len(), 2);
+}
+
+#[test]
+fn validate_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.validate().unwrap();
+
+    record.insert("foo", "bar").unwrap();
+    record.insert("flag", "").unwrap();
+    record.validate().unwrap();
+}
+
+#[test]
+fn validate_invalid_key_fails() {
+    super::setup();
+    let entries: Vec<(&[u8], &[u8])> = vec![(b"foo", b"bar"), (b"", b"baz")];
+    let err = txt_record::validate_entries(entries.into_iter()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+
+    let entries: Vec<(&[u8], &[u8])> = vec![(b"caf\xc3", b"bar")];
+    let err = txt_record::validate_entries(entries.into_iter()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+}
+
+#[test]
+fn validate_oversized_entry_fails() {
+    super::setup();
+    let value = "a".repeat(251);
+    let entries: Vec<(&[u8], &[u8])> = vec![(b"foo", value.as_bytes())];
+    txt_record::validate_entries(entries.into_iter()).unwrap();
+
+    let value = "a".repeat(252);
+    let entries: Vec<(&[u8], &[u8])> = vec![(b"foo", value.as_bytes())];
+    let err = txt_record::validate_entries(entries.into_iter()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+    assert!(err.to_string().contains("`foo`"));
+}
+
+#[test]
+fn validate_oversized_record_fails() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    let value = "a".repeat(200);
+
+    for i in 0..6 {
+        record.insert(&format!("key{}", i), &value).unwrap();
+    }
+    record.validate().unwrap();
+
+    record.insert("key6", &value).unwrap();
+    assert!(record.byte_len() > 1300);
+    let err = record.validate().unwrap_err();
+    assert_eq!(ErrorKind::InvalidServiceType, err.kind());
+}
+
+#[test]
+fn split_wire_format_success() {
+    super::setup();
+    let entries = txt_record::split_wire_format(b"\x07foo=bar\x00\x04flag").unwrap();
+    let expected: Vec<(&[u8], &[u8])> = vec![(b"foo", b"bar"), (b"flag", b"")];
+    assert_eq!(expected, entries);
+    assert!(txt_record::split_wire_format(b"").unwrap().is_empty());
+}
+
+#[test]
+fn split_wire_format_truncated_fails() {
+    super::setup();
+    let err = txt_record::split_wire_format(b"\x07foo=bar\x09a=b").unwrap_err();
+    assert_eq!(ErrorKind::Malformed, err.kind());
+}
+
+#[test]
+fn check_received_success() {
+    super::setup();
+    // keys longer than 9 characters are only discouraged, so they are accepted from peers
+    let entries: Vec<(&[u8], &[u8])> = vec![
+        (b"foo", b"bar"),
+        (b"longer_key", b""),
+        (b"u", "\u{e9}".as_bytes()),
+    ];
+    txt_record::check_received(entries.into_iter()).unwrap();
+}
+
+#[test]
+fn check_received_malformed_fails() {
+    super::setup();
+    for entry in [
+        (&b""[..], &b"bar"[..]),
+        (&b"caf\xc3\xa9"[..], &b"bar"[..]),
+        (&b"foo"[..], &b"\xff"[..]),
+    ] {
+        let err = txt_record::check_received(vec![entry].into_iter()).unwrap_err();
+        assert_eq!(ErrorKind::Malformed, err.kind());
+    }
+}
+
 #[test]
 #[ignore]
 fn iter_success() {
@@ -84,6 +344,45 @@ fn values_success() {
     }
 }
 
+#[test]
+fn iter_raw_success() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("foo", "bar").unwrap();
+    record.insert("baz", "").unwrap();
+
+    let mut entries: Vec<(&[u8], &[u8])> = record.iter_raw().collect();
+    entries.sort();
+
+    assert_eq!(
+        entries,
+        vec![(&b"baz"[..], &b""[..]), (&b"foo"[..], &b"bar"[..])]
+    );
+    assert_eq!(TxtRecord::new().iter_raw().count(), 0);
+}
+
+#[test]
+fn iter_raw_does_not_allocate_per_entry() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    for i in 0..32 {
+        record.insert(&format!("key{}", i), "value").unwrap();
+    }
+
+    // every entry is visited, whatever order the platform keeps them in
+    let raw = count_allocations(|| {
+        assert_eq!(32, record.iter_raw().count());
+    });
+
+    let owned = count_allocations(|| {
+        assert_eq!(32, record.iter().count());
+    });
+
+    // only the boxed iterator itself is allocated
+    assert_eq!(raw, 1);
+    assert!(owned >= 32, "iter() made {} allocations", owned);
+}
+
 #[test]
 fn from_hashmap_success() {
     super::setup();
@@ -96,6 +395,42 @@ fn from_hashmap_success() {
     assert_eq!(record.get("foo").unwrap(), "bar");
 }
 
+#[test]
+fn from_hashmap_skips_invalid_keys() {
+    super::setup();
+
+    let mut map = HashMap::new();
+    map.insert("description", "bar");
+    map.insert("", "empty");
+    map.insert("foo=bar", "equals");
+    map.insert("caf\u{e9}", "non-ascii");
+
+    let record: TxtRecord = map.into();
+
+    assert_eq!(1, record.len());
+    assert_eq!(record.get("description").unwrap(), "bar");
+}
+
+#[test]
+fn deserialize_accepts_long_keys() {
+    super::setup();
+
+    let mut record = TxtRecord::new();
+    record.insert("txtvers_x", "1").unwrap();
+    record.insert("description", "bar").unwrap();
+
+    let json = serde_json::to_string(&record).unwrap();
+    let record_de: TxtRecord = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(record.to_map(), record_de.to_map());
+}
+
+#[test]
+fn deserialize_invalid_key_fails() {
+    super::setup();
+    serde_json::from_str::<TxtRecord>(r#"{"foo=bar":"baz"}"#).unwrap_err();
+}
+
 #[test]
 fn clone_success() {
     super::setup();
@@ -105,3 +440,19 @@ fn clone_success() {
 
     assert_eq!(record.clone(), record);
 }
+
+#[test]
+fn clone_is_independent() {
+    super::setup();
+    let mut record = TxtRecord::new();
+    record.insert("foo", "bar").unwrap();
+
+    let mut clone = record.clone();
+    clone.insert("baz", "qux").unwrap();
+    clone.insert("foo", "quux").unwrap();
+
+    assert_eq!(record.len(), 1);
+    assert_eq!(record.get("foo").unwrap(), "bar");
+    assert!(!record.contains_key("baz"));
+    assert_eq!(clone.get("baz").unwrap(), "qux");
+}
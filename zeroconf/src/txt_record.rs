@@ -1,19 +1,43 @@
 //! TxtRecord utilities common to all platforms
 
+use crate::error::{Error, ErrorKind};
 use crate::{Result, TxtRecord};
-use serde::de::{MapAccess, Visitor};
+use serde::de::value;
+use serde::de::{Error as _, IntoDeserializer, MapAccess, Visitor};
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
 
+/// The maximum length of a TXT record key recommended by RFC 6763.
+const MAX_KEY_LEN: usize = 9;
+
+/// The maximum length of a single `key=value` entry, which is prefixed with its length in a
+/// single byte.
+const MAX_ENTRY_LEN: usize = 255;
+
+/// The total size RFC 6763 recommends keeping a TXT record within, so that it fits in a single
+/// multicast UDP packet along with the other records of the service.
+const MAX_RECOMMENDED_LEN: usize = 1300;
+
 /// Interface for interacting with underlying mDNS implementation TXT record capabilities
 pub trait TTxtRecord: Clone + PartialEq + Eq + Debug {
     /// Constructs a new TXT record
     fn new() -> Self;
 
     /// Inserts the specified value at the specified key.
+    ///
+    /// Following [RFC 6763 section 6.4], `key` must be at least one printable ASCII character
+    /// (`0x20` to `0x7E`) and may not contain `=`. Returns `Err` with
+    /// [`ErrorKind::InvalidServiceType`] for any other key, rather than leaving the daemon to
+    /// reject or mangle it when the service is registered. Keys should be no longer than 9
+    /// characters; longer keys are accepted with a warning, since that limit is only a
+    /// recommendation. The `key=value` entry must fit in 255 bytes, since it is prefixed with its
+    /// length in a single byte on the wire.
+    ///
+    /// [RFC 6763 section 6.4]: https://datatracker.ietf.org/doc/html/rfc6763#section-6.4
+    /// [`ErrorKind::InvalidServiceType`]: ../error/enum.ErrorKind.html#variant.InvalidServiceType
     fn insert(&mut self, key: &str, value: &str) -> Result<()>;
 
     /// Returns the value at the specified key or `None` if no such key exists.
@@ -22,6 +46,47 @@ pub trait TTxtRecord: Clone + PartialEq + Eq + Debug {
     /// implementation provides access to the underlying value pointer.
     fn get(&self, key: &str) -> Option<String>;
 
+    /// Returns the value at the specified key or `default` if no such key exists.
+    fn get_or(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+
+    /// Returns the value at the specified key or an empty `String` if no such key exists.
+    fn get_or_default(&self, key: &str) -> String {
+        self.get(key).unwrap_or_default()
+    }
+
+    /// Returns the value at the specified key interpreted as a boolean, or `None` if no such key
+    /// exists or the value is not a boolean.
+    ///
+    /// Following the convention for boolean attributes in [RFC 6763 section 6.4], a key that is
+    /// present with an empty value is `true`. Otherwise `1` and `true` are `true`, and `0` and
+    /// `false` are `false`, ignoring ASCII case.
+    ///
+    /// [RFC 6763 section 6.4]: https://datatracker.ietf.org/doc/html/rfc6763#section-6.4
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        let value = self.get(key)?;
+        if value.is_empty() || value == "1" || value.eq_ignore_ascii_case("true") {
+            Some(true)
+        } else if value == "0" || value.eq_ignore_ascii_case("false") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value at the specified key parsed as an `i64`, or `None` if no such key exists
+    /// or the value is not an integer.
+    fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// Returns the value at the specified key parsed as an `f64`, or `None` if no such key exists
+    /// or the value is not a number.
+    fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key)?.parse().ok()
+    }
+
     /// Removes the value at the specified key. Returns `Err` if no such key exists.
     fn remove(&mut self, key: &str) -> Result<()>;
 
@@ -34,6 +99,14 @@ pub trait TTxtRecord: Clone + PartialEq + Eq + Debug {
     /// Returns a new iterator for iterating over the record as you would a `HashMap`.
     fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (String, String)> + 'a>;
 
+    /// Returns a new iterator over the record's entries as raw `(key, value)` bytes, borrowed
+    /// from the record's underlying buffer without copying them.
+    ///
+    /// Unlike `iter()`, this does not allocate for each entry, which makes it suitable for
+    /// scanning records in hot paths. The slices borrow from `self`, so the record cannot be
+    /// modified while they are alive. An entry without a `=` is reported with an empty value.
+    fn iter_raw<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a [u8], &'a [u8])> + 'a>;
+
     /// Returns a new iterator over the records keys.
     fn keys<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a>;
 
@@ -45,6 +118,53 @@ pub trait TTxtRecord: Clone + PartialEq + Eq + Debug {
         self.len() == 0
     }
 
+    /// Returns the record encoded as it is sent on the wire: a sequence of `key=value` strings,
+    /// each prefixed with its length in a single byte.
+    ///
+    /// An entry with an empty value is encoded as just its key. An empty record encodes to no
+    /// bytes here; the platform publishes it as a single empty string as required by RFC 6763.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.byte_len());
+        for (key, value) in self.iter_raw() {
+            bytes.push(entry_len(key, value) as u8);
+            bytes.extend_from_slice(key);
+            if !value.is_empty() {
+                bytes.push(b'=');
+                bytes.extend_from_slice(value);
+            }
+        }
+        bytes
+    }
+
+    /// Returns the length of [`to_bytes()`] without encoding the record, which is useful for
+    /// staying within the recommended TXT record size before registering a service.
+    ///
+    /// [`to_bytes()`]: #method.to_bytes
+    fn byte_len(&self) -> usize {
+        self.iter_raw()
+            .map(|(key, value)| 1 + entry_len(key, value))
+            .sum()
+    }
+
+    /// Checks the record against the DNS-SD rules for TXT records, returning `Err` with
+    /// [`ErrorKind::InvalidServiceType`] describing the first violation found:
+    ///
+    /// * each key must be valid as described by [`insert()`]
+    /// * each `key=value` entry may be at most 255 bytes long
+    /// * the record as a whole should stay within the 1300 bytes recommended by
+    ///   [RFC 6763 section 6.2], as measured by [`byte_len()`]
+    ///
+    /// This is useful as a pre-flight check for records built from configuration, before
+    /// registering a service with them.
+    ///
+    /// [`ErrorKind::InvalidServiceType`]: ../error/enum.ErrorKind.html#variant.InvalidServiceType
+    /// [`insert()`]: #tymethod.insert
+    /// [RFC 6763 section 6.2]: https://datatracker.ietf.org/doc/html/rfc6763#section-6.2
+    /// [`byte_len()`]: #method.byte_len
+    fn validate(&self) -> Result<()> {
+        validate_entries(self.iter_raw())
+    }
+
     /// Returns a new `HashMap` with this record's keys and values.
     fn to_map(&self) -> HashMap<String, String> {
         let mut m = HashMap::new();
@@ -55,11 +175,170 @@ pub trait TTxtRecord: Clone + PartialEq + Eq + Debug {
     }
 }
 
+/// Checks that `key` can be inserted into a TXT record, as described by `TTxtRecord::insert()`.
+pub(crate) fn validate_key(key: &str) -> Result<()> {
+    let reason = if key.is_empty() {
+        "cannot be empty"
+    } else if key.contains('=') {
+        "invalid character: ="
+    } else if !key.bytes().all(|b| (0x20..=0x7e).contains(&b)) {
+        "not printable ASCII"
+    } else {
+        if key.len() > MAX_KEY_LEN {
+            warn!(
+                "TXT record key `{}` is longer than the recommended {} characters",
+                key, MAX_KEY_LEN
+            );
+        }
+
+        return Ok(());
+    };
+
+    Err(Error::with_kind(
+        ErrorKind::InvalidServiceType,
+        format!("invalid TXT record key `{}`: {}", key, reason),
+    ))
+}
+
+/// Checks that `key` and `value` can be inserted into a TXT record together, as described by
+/// `TTxtRecord::insert()`.
+pub(crate) fn validate_entry(key: &str, value: &[u8]) -> Result<()> {
+    validate_key(key)?;
+    check_entry_len(key, value)
+}
+
+/// Returns the length of the `key=value` entry on the wire, leaving out the `=` for an empty
+/// value.
+fn entry_len(key: &[u8], value: &[u8]) -> usize {
+    if value.is_empty() {
+        key.len()
+    } else {
+        key.len() + 1 + value.len()
+    }
+}
+
+fn check_entry_len(key: &str, value: &[u8]) -> Result<()> {
+    let entry_len = entry_len(key.as_bytes(), value);
+    if entry_len > MAX_ENTRY_LEN {
+        return Err(Error::with_kind(
+            ErrorKind::InvalidServiceType,
+            format!(
+                "TXT record entry `{}` is {} bytes, exceeding the maximum of {}",
+                key, entry_len, MAX_ENTRY_LEN
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks raw `(key, value)` entries as described by `TTxtRecord::validate()`.
+pub(crate) fn validate_entries<'a>(
+    entries: impl Iterator<Item = (&'a [u8], &'a [u8])>,
+) -> Result<()> {
+    let mut total_len = 0;
+
+    for (key, value) in entries {
+        let key = std::str::from_utf8(key).map_err(|_| {
+            Error::with_kind(
+                ErrorKind::InvalidServiceType,
+                format!(
+                    "invalid TXT record key `{}`: not printable ASCII",
+                    String::from_utf8_lossy(key)
+                ),
+            )
+        })?;
+        validate_entry(key, value)?;
+
+        total_len += 1 + entry_len(key.as_bytes(), value);
+    }
+
+    if total_len > MAX_RECOMMENDED_LEN {
+        return Err(Error::with_kind(
+            ErrorKind::InvalidServiceType,
+            format!(
+                "TXT record is {} bytes, exceeding the recommended maximum of {}",
+                total_len, MAX_RECOMMENDED_LEN
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Splits TXT data in wire format into its raw `(key, value)` entries, skipping zero-length
+/// entries. Returns `Err` with `ErrorKind::Malformed` if an entry's length runs past the end of
+/// `bytes`.
+pub(crate) fn split_wire_format(mut bytes: &[u8]) -> Result<Vec<(&[u8], &[u8])>> {
+    let mut entries = vec![];
+
+    while let Some((len, rest)) = bytes.split_first() {
+        let len = *len as usize;
+        if len > rest.len() {
+            return Err(malformed(format!(
+                "TXT record entry of {} bytes is truncated to {}",
+                len,
+                rest.len()
+            )));
+        }
+
+        let (entry, rest) = rest.split_at(len);
+        bytes = rest;
+
+        if !entry.is_empty() {
+            entries.push(split_entry(entry));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Checks that TXT entries received from the network can be read through the `TxtRecord` API:
+/// keys must be non-empty printable ASCII as RFC 6763 requires, and values must be UTF-8.
+/// Returns `Err` with `ErrorKind::Malformed` otherwise.
+pub(crate) fn check_received<'a>(
+    entries: impl Iterator<Item = (&'a [u8], &'a [u8])>,
+) -> Result<()> {
+    for (key, value) in entries {
+        if key.is_empty() || !key.iter().all(|b| (0x20..=0x7e).contains(b)) {
+            return Err(malformed(format!(
+                "invalid TXT record key `{}`",
+                String::from_utf8_lossy(key)
+            )));
+        }
+
+        if std::str::from_utf8(value).is_err() {
+            return Err(malformed(format!(
+                "TXT record value of `{}` is not valid UTF-8",
+                String::from_utf8_lossy(key)
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn malformed(description: String) -> Error {
+    Error::with_kind(ErrorKind::Malformed, description)
+}
+
+/// Splits a raw `key=value` TXT entry at its first `=`.
+pub(crate) fn split_entry(entry: &[u8]) -> (&[u8], &[u8]) {
+    match entry.iter().position(|b| *b == b'=') {
+        Some(i) => (&entry[..i], &entry[i + 1..]),
+        None => (entry, &[]),
+    }
+}
+
 impl From<HashMap<String, String>> for TxtRecord {
+    /// Inserts each entry of `map`, skipping with a warning any key that `TTxtRecord::insert()`
+    /// rejects.
     fn from(map: HashMap<String, String>) -> TxtRecord {
         let mut record = TxtRecord::new();
         for (key, value) in map {
-            record.insert(&key, &value).unwrap();
+            if let Err(e) = record.insert(&key, &value) {
+                warn!("Skipping TXT record entry: {}", e);
+            }
         }
         record
     }
@@ -114,7 +393,7 @@ impl<'de> Visitor<'de> for TxtRecordVisitor {
         let mut map = TxtRecord::new();
 
         while let Some((key, value)) = access.next_entry()? {
-            map.insert(key, value).unwrap();
+            map.insert(key, value).map_err(M::Error::custom)?;
         }
 
         Ok(map)
@@ -130,6 +409,72 @@ impl<'de> Deserialize<'de> for TxtRecord {
     }
 }
 
+/// Deserializer for a single TXT record value, used by [`ServiceDiscovery::txt_as()`].
+///
+/// TXT values are always strings; this parses them into the requested primitive type and treats
+/// a present value as `Some` for `Option` fields.
+///
+/// [`ServiceDiscovery::txt_as()`]: ../struct.ServiceDiscovery.html#method.txt_as
+pub(crate) struct TxtValueDeserializer(pub(crate) String);
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.0.parse().map_err(value::Error::custom)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for TxtValueDeserializer {
+    type Error = value::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, value::Error> for TxtValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
 impl Debug for TxtRecord {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TxtRecord")